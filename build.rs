@@ -12,6 +12,10 @@ fn main() {
         Path::new(&dir).join("lib").display()
     );
 
+    // The shared libs are gitignored, which hides them from cargo's default change detection
+    println!("cargo:rerun-if-changed=lib");
+    println!("cargo:rerun-if-changed=build.rs");
+
     copy_dynamic_libraries();
 }
 
@@ -46,11 +50,13 @@ fn copy_library_file(src_path: &Path, target_path: &Path) {
     for path in &[target_path, &deps_path] {
         let dst_path = path.join(src_path.file_name().expect("Path missing filename"));
 
-        fs::copy(&src_path, &dst_path).expect(&format!(
-            "Failed to copy dynamic library from {} to {}",
-            src_path.to_string_lossy(),
-            dst_path.to_string_lossy()
-        ));
+        fs::copy(src_path, &dst_path).unwrap_or_else(|_| {
+            panic!(
+                "Failed to copy dynamic library from {} to {}",
+                src_path.to_string_lossy(),
+                dst_path.to_string_lossy()
+            )
+        });
     }
 }
 
@@ -58,7 +64,7 @@ fn copy_dynamic_libraries() {
     let src_path = Path::new(&var("CARGO_MANIFEST_DIR").unwrap()).join("lib");
     let target_path = find_cargo_target_dir();
 
-    for entry in std::fs::read_dir(&src_path).unwrap_or_else(|_| panic!("Couldn't readdir lib")) {
+    for entry in std::fs::read_dir(&src_path).unwrap_or_else(|_| panic!("Couldn't readdir lib, build AiNav from recast_src and copy the shared lib into lib")) {
         let entry = entry.expect("Error looking at lib dir");
         if let Ok(file_type) = entry.file_type() {
             if file_type.is_file() {
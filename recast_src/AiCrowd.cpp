@@ -1,4 +1,5 @@
 #include "AiCrowd.hpp"
+#include <DetourCommon.h>
 
 AiCrowd::AiCrowd()
 {
@@ -18,9 +19,10 @@ int AiCrowd::GetVersion()
 int AiCrowd::Init(NavigationMesh * navmesh, int maxAgents, float maxRadius)
 {
 	m_navMesh = navmesh->GetDtNavMesh();
-	m_navQuery = navmesh->GetNavmeshQuery();
-
-	crowd->init(maxAgents, maxRadius, m_navMesh);
+	if (!crowd->init(maxAgents, maxRadius, m_navMesh))
+		return 0;
+	// The crowd's own query.  The navmesh's shared query isn't safe to use from the thread updating the crowd
+	m_navQuery = crowd->getNavMeshQuery();
 
 	dtObstacleAvoidanceParams params;
 	memcpy(&params, crowd->getObstacleAvoidanceParams(0), sizeof(dtObstacleAvoidanceParams));
@@ -101,6 +103,7 @@ void AiCrowd::GetAgentParams(int idx, DtAgentParams* agentParams)
 	agentParams->pathOptimizationRange = ag->params.pathOptimizationRange;
 	agentParams->obstacleAvoidanceType = ag->params.obstacleAvoidanceType;
 	agentParams->separationWeight = ag->params.separationWeight;
+	agentParams->queryFilterType = ag->params.queryFilterType;
 
 	agentParams->anticipateTurns = ag->params.updateFlags & DT_CROWD_ANTICIPATE_TURNS ? 1 : 0;
 	agentParams->optimizeVis = ag->params.updateFlags & DT_CROWD_OPTIMIZE_VIS ? 1 : 0;
@@ -109,11 +112,80 @@ void AiCrowd::GetAgentParams(int idx, DtAgentParams* agentParams)
 	agentParams->crowdSeparation = ag->params.updateFlags & DT_CROWD_SEPARATION ? 1 : 0;
 }
 
+int AiCrowd::SetFilter(int idx, DtQueryFilter* filter)
+{
+	dtQueryFilter* crowdFilter = crowd->getEditableFilter(idx);
+	if (!crowdFilter)
+		return 0;
+
+	ToDetourFilter(filter, crowdFilter);
+	return 1;
+}
+
+int AiCrowd::GetFilter(int idx, DtQueryFilter* filter)
+{
+	const dtQueryFilter* crowdFilter = crowd->getFilter(idx);
+	if (!crowdFilter)
+		return 0;
+
+	for (int i = 0; i < DT_MAX_AREAS; i++)
+	{
+		filter->areaCost[i] = crowdFilter->getAreaCost(i);
+	}
+	filter->includeFlags = crowdFilter->getIncludeFlags();
+	filter->excludeFlags = crowdFilter->getExcludeFlags();
+	return 1;
+}
+
+int AiCrowd::SetObstacleAvoidanceParams(int idx, DtObstacleAvoidanceParams* params)
+{
+	if (idx < 0 || idx >= DT_CROWD_MAX_OBSTAVOIDANCE_PARAMS)
+		return 0;
+
+	dtObstacleAvoidanceParams avoidanceParams;
+	avoidanceParams.velBias = params->velBias;
+	avoidanceParams.weightDesVel = params->weightDesVel;
+	avoidanceParams.weightCurVel = params->weightCurVel;
+	avoidanceParams.weightSide = params->weightSide;
+	avoidanceParams.weightToi = params->weightToi;
+	avoidanceParams.horizTime = params->horizTime;
+	avoidanceParams.gridSize = params->gridSize;
+	avoidanceParams.adaptiveDivs = params->adaptiveDivs;
+	avoidanceParams.adaptiveRings = params->adaptiveRings;
+	avoidanceParams.adaptiveDepth = params->adaptiveDepth;
+	crowd->setObstacleAvoidanceParams(idx, &avoidanceParams);
+	return 1;
+}
+
+int AiCrowd::GetObstacleAvoidanceParams(int idx, DtObstacleAvoidanceParams* params)
+{
+	const dtObstacleAvoidanceParams* avoidanceParams = crowd->getObstacleAvoidanceParams(idx);
+	if (!avoidanceParams)
+		return 0;
+
+	params->velBias = avoidanceParams->velBias;
+	params->weightDesVel = avoidanceParams->weightDesVel;
+	params->weightCurVel = avoidanceParams->weightCurVel;
+	params->weightSide = avoidanceParams->weightSide;
+	params->weightToi = avoidanceParams->weightToi;
+	params->horizTime = avoidanceParams->horizTime;
+	params->gridSize = avoidanceParams->gridSize;
+	params->adaptiveDivs = avoidanceParams->adaptiveDivs;
+	params->adaptiveRings = avoidanceParams->adaptiveRings;
+	params->adaptiveDepth = avoidanceParams->adaptiveDepth;
+	return 1;
+}
+
 int AiCrowd::GetNearestPoly(int idx, float3 position, uint64_t* nearestPoly, float3* nearestPoint)
 {
 	dtPolyRef startPoly;
 	float m_targetPos[3];
-	const dtQueryFilter* filter = crowd->getFilter(0);
+	const dtCrowdAgent* ag = crowd->getAgent(idx);
+	if (!ag || !ag->active)
+		return 0;
+
+	// Snap with the agent's own filter so it isn't given a target its filter rejects
+	const dtQueryFilter* filter = crowd->getFilter(ag->params.queryFilterType);
 	const float* halfExtents = crowd->getQueryExtents();
 
 	dtStatus status;
@@ -122,7 +194,7 @@ int AiCrowd::GetNearestPoly(int idx, float3 position, uint64_t* nearestPoly, flo
 		return 0;
 	}
 
-	nearestPoly = &startPoly;
+	*nearestPoly = startPoly;
 	nearestPoint->x = m_targetPos[0];
 	nearestPoint->y = m_targetPos[1];
 	nearestPoint->z = m_targetPos[2];
@@ -130,7 +202,7 @@ int AiCrowd::GetNearestPoly(int idx, float3 position, uint64_t* nearestPoly, flo
 	return 1;
 }
 
-int AiCrowd::RequestMoveByPoly(int idx, uint64_t polyRef, float3 position)
+dtStatus AiCrowd::RequestMoveByPoly(int idx, uint64_t polyRef, float3 position)
 {
 	float m_targetPos[3];
 	m_targetPos[0] = position.x;
@@ -141,34 +213,55 @@ int AiCrowd::RequestMoveByPoly(int idx, uint64_t polyRef, float3 position)
 	if (ag && ag->active)
 	{
 		bool moveStatus = crowd->requestMoveTarget(idx, polyRef, m_targetPos);
-		return moveStatus ? 1 : 0;
+		return moveStatus ? DT_SUCCESS : DT_FAILURE | DT_INVALID_PARAM;
 	}
 
-	return 0;
+	return DT_FAILURE | DT_INVALID_PARAM;
 
 }
 
-int AiCrowd::RequestMove(int idx, float3 position)
+dtStatus AiCrowd::RequestMove(int idx, float3 position)
 {
 	dtPolyRef startPoly;
 	float m_targetPos[3];
-	const dtQueryFilter* filter = crowd->getFilter(0);
 	const float* halfExtents = crowd->getQueryExtents();
 
+	const dtCrowdAgent* ag = crowd->getAgent(idx);
+	if (!ag || !ag->active)
+		return DT_FAILURE | DT_INVALID_PARAM;
+	const dtQueryFilter* filter = crowd->getFilter(ag->params.queryFilterType);
+
 	dtStatus status;
 	status = m_navQuery->findNearestPoly(&position.x, halfExtents, filter, &startPoly, m_targetPos);
-	if (dtStatusFailed(status) || startPoly == 0) {
-		return 0;
+	if (dtStatusFailed(status)) {
+		return status;
 	}
-	const dtCrowdAgent* ag = crowd->getAgent(idx);
-	if (ag && ag->active)
-	{
-		bool moveStatus = crowd->requestMoveTarget(idx, startPoly, m_targetPos);
-		return moveStatus ? 1 : 0;
+	if (startPoly == 0) {
+		return DT_FAILURE | AINAV_NO_NEAREST_POLY;
 	}
 
-	return 0;
-		
+	bool moveStatus = crowd->requestMoveTarget(idx, startPoly, m_targetPos);
+	return moveStatus ? DT_SUCCESS : DT_FAILURE | DT_INVALID_PARAM;
+}
+
+dtStatus AiCrowd::RequestMoveVelocity(int idx, float3 velocity)
+{
+	const dtCrowdAgent* ag = crowd->getAgent(idx);
+	if (!ag || !ag->active)
+		return DT_FAILURE | DT_INVALID_PARAM;
+
+	bool moveStatus = crowd->requestMoveVelocity(idx, &velocity.x);
+	return moveStatus ? DT_SUCCESS : DT_FAILURE | DT_INVALID_PARAM;
+}
+
+dtStatus AiCrowd::ResetMoveTarget(int idx)
+{
+	const dtCrowdAgent* ag = crowd->getAgent(idx);
+	if (!ag || !ag->active)
+		return DT_FAILURE | DT_INVALID_PARAM;
+
+	bool moveStatus = crowd->resetMoveTarget(idx);
+	return moveStatus ? DT_SUCCESS : DT_FAILURE | DT_INVALID_PARAM;
 }
 
 
@@ -181,18 +274,30 @@ void AiCrowd::GetActiveAgents(DtCrowdAgentsResult * result)
 	{
 		const dtCrowdAgent* ag = crowd->getAgent(i);
 		if (!ag->active) continue;
-		
-		DtCrowdAgent ca = result->agents[index];
-		ca.index = i;
-		ca.active = ag->active ? 1 : 0;
-		ca.desiredSpeed = ag->desiredSpeed;
-		ca.partial = ag->partial ? 1 : 0;
-		ca.position.x = ag->npos[0];
-		ca.position.y = ag->npos[1];
-		ca.position.z = ag->npos[2];
-		
-		result->agents[index] = ca;
 
+		FillAgent(i, ag, &result->agents[index]);
+		index++;
+	}
+	result->agentCount = index;
+}
+
+void AiCrowd::GetAgentSnapshot(DtCrowdAgentSnapshot* result)
+{
+	int index = 0;
+	int agentCount = crowd->getAgentCount();
+	for (int i = 0; i < agentCount && index < result->maxAgents; ++i)
+	{
+		const dtCrowdAgent* ag = crowd->getAgent(i);
+		if (!ag->active) continue;
+
+		result->indices[index] = i;
+		result->positions[index].x = ag->npos[0];
+		result->positions[index].y = ag->npos[1];
+		result->positions[index].z = ag->npos[2];
+		result->velocities[index].x = ag->vel[0];
+		result->velocities[index].y = ag->vel[1];
+		result->velocities[index].z = ag->vel[2];
+		result->states[index] = ag->state;
 		index++;
 	}
 	result->agentCount = index;
@@ -209,6 +314,12 @@ int AiCrowd::GetAgent(int idx, DtCrowdAgent* result)
 	if (ag == 0) {
 		return 0;
 	}
+	FillAgent(idx, ag, result);
+	return 1;
+}
+
+void AiCrowd::FillAgent(int idx, const dtCrowdAgent* ag, DtCrowdAgent* result)
+{
 	result->index = idx;
 	result->state = ag->state;
 	result->active = ag->active ? 1 : 0;
@@ -222,10 +333,63 @@ int AiCrowd::GetAgent(int idx, DtCrowdAgent* result)
 	result->velocity.x = ag->vel[0];
 	result->velocity.y = ag->vel[1];
 	result->velocity.z = ag->vel[2];
+}
 
+int AiCrowd::GetAgentInfo(int idx, DtCrowdAgentInfo* result)
+{
+	const dtCrowdAgent* ag = crowd->getAgent(idx);
+	if (!ag || !ag->active) {
+		return 0;
+	}
+	memset(result, 0, sizeof(DtCrowdAgentInfo));
+	FillAgent(idx, ag, &result->agent);
+
+	memcpy(&result->desiredVelocity.x, ag->dvel, sizeof(float) * 3);
+	memcpy(&result->steeringVelocity.x, ag->nvel, sizeof(float) * 3);
+	result->currentPoly = ag->corridor.getFirstPoly();
+
+	result->targetState = ag->targetState;
+	result->targetPoly = ag->targetRef;
+	memcpy(&result->targetPosition.x, ag->targetPos, sizeof(float) * 3);
+
+	result->numCorners = ag->ncorners;
+	for (int i = 0; i < ag->ncorners; ++i)
+	{
+		memcpy(&result->cornerVerts[i].x, &ag->cornerVerts[i * 3], sizeof(float) * 3);
+		result->cornerFlags[i] = ag->cornerFlags[i];
+		result->cornerPolys[i] = ag->cornerPolys[i];
+	}
+
+	result->numNeighbours = ag->nneis;
+	for (int i = 0; i < ag->nneis; ++i)
+	{
+		result->neighbourIndices[i] = ag->neis[i].idx;
+		result->neighbourDistances[i] = ag->neis[i].dist;
+	}
+
+	result->numBoundarySegments = ag->boundary.getSegmentCount();
+	for (int i = 0; i < result->numBoundarySegments; ++i)
+	{
+		const float* segment = ag->boundary.getSegment(i);
+		memcpy(&result->boundarySegments[i * 2].x, segment, sizeof(float) * 3);
+		memcpy(&result->boundarySegments[i * 2 + 1].x, segment + 3, sizeof(float) * 3);
+	}
+
+	result->corridorLength = ag->corridor.getPathCount();
 	return 1;
 }
 
+int AiCrowd::GetAgentCorridor(int idx, uint64_t* polys, int maxPolys)
+{
+	const dtCrowdAgent* ag = crowd->getAgent(idx);
+	if (!ag || !ag->active) {
+		return -1;
+	}
+	int count = dtMin(ag->corridor.getPathCount(), maxPolys);
+	memcpy(polys, ag->corridor.getPath(), sizeof(dtPolyRef) * count);
+	return count;
+}
+
 void AiCrowd::Update(const float dt)
 {
 	//dtCrowdAgentDebugInfo debug;
@@ -245,6 +409,7 @@ dtCrowdAgentParams AiCrowd::CreateParams(DtAgentParams* agentParams)
 	ap.pathOptimizationRange = agentParams->pathOptimizationRange;
 	ap.obstacleAvoidanceType = agentParams->obstacleAvoidanceType;
 	ap.separationWeight = agentParams->separationWeight;
+	ap.queryFilterType = (unsigned char)agentParams->queryFilterType;
 
 	ap.updateFlags = 0;
 	if (agentParams->anticipateTurns)
@@ -7,9 +7,10 @@ class AiCrowd {
 private:
 	int activeAgentCount = 0;
 	dtNavMesh* m_navMesh = nullptr;
-	dtNavMeshQuery* m_navQuery = nullptr;
+	const dtNavMeshQuery* m_navQuery = nullptr;
 	dtCrowd* crowd = nullptr;
 	dtCrowdAgentParams CreateParams(DtAgentParams* agentParams);
+	void FillAgent(int idx, const dtCrowdAgent* ag, DtCrowdAgent* result);
 public:
 	AiCrowd();
 	~AiCrowd();
@@ -19,11 +20,21 @@ public:
 	void RemoveAgent(int idx);
 	void SetAgentParams(int idx, DtAgentParams* agentParams);
 	void GetAgentParams(int idx, DtAgentParams* agentParams);
-	int RequestMove(int idx, float3 position);
-	int RequestMoveByPoly(int idx, uint64_t polyRef, float3 position);
+	int SetFilter(int idx, DtQueryFilter* filter);
+	int GetFilter(int idx, DtQueryFilter* filter);
+	int SetObstacleAvoidanceParams(int idx, DtObstacleAvoidanceParams* params);
+	int GetObstacleAvoidanceParams(int idx, DtObstacleAvoidanceParams* params);
+	dtStatus RequestMove(int idx, float3 position);
+	dtStatus RequestMoveByPoly(int idx, uint64_t polyRef, float3 position);
+	dtStatus RequestMoveVelocity(int idx, float3 velocity);
+	dtStatus ResetMoveTarget(int idx);
 	int GetNearestPoly(int idx, float3 position, uint64_t* polyRef, float3* nearestPoint);
 	int GetAgentCount();
 	int GetAgent(int idx, DtCrowdAgent* result);
+	int GetAgentInfo(int idx, DtCrowdAgentInfo* result);
+	// Returns the number of polys written, -1 if idx is out of range
+	int GetAgentCorridor(int idx, uint64_t* polys, int maxPolys);
 	void GetActiveAgents(DtCrowdAgentsResult* result);
+	void GetAgentSnapshot(DtCrowdAgentSnapshot* result);
 	void Update(const float dt);
 };
\ No newline at end of file
@@ -61,6 +61,16 @@ void SetSettings(NavigationBuilder* nav, DtBuildSettings* buildSettings)
 	nav->SetSettings(*buildSettings);
 }
 
+void SetOffMeshConnections(NavigationBuilder* nav, DtOffMeshConnection* connections, int numConnections)
+{
+	nav->SetOffMeshConnections(connections, numConnections);
+}
+
+void SetConvexVolumes(NavigationBuilder* nav, DtConvexVolume* volumes, int numVolumes)
+{
+	nav->SetConvexVolumes(volumes, numVolumes);
+}
+
 DtGeneratedData* BuildNavmesh(NavigationBuilder* nav,
 	float3* vertices, int numVertices,
 	int* indices, int numIndices, uint8_t* areas)
@@ -68,6 +78,20 @@ DtGeneratedData* BuildNavmesh(NavigationBuilder* nav,
 	return nav->BuildNavmesh(vertices, numVertices, indices, numIndices, areas);
 }
 
+DtBuildStats* GetBuildStats(NavigationBuilder* nav)
+{
+	return nav->GetBuildStats();
+}
+
+DtTileCacheLayersResult* BuildTileCacheLayers(NavigationBuilder* nav,
+	float3* vertices, int numVertices,
+	int* indices, int numIndices, uint8_t* areas,
+	DtTileCacheCompressorCallbacks* compressor)
+{
+	CallbackCompressor callbackCompressor(*compressor);
+	return nav->BuildTileCacheLayers(vertices, numVertices, indices, numIndices, areas, &callbackCompressor);
+}
+
 // Navmesh
 void* CreateNavmesh(float cellTileSize, int tileBits, int polyBits)
 {
@@ -80,22 +104,111 @@ void* CreateNavmesh(float cellTileSize, int tileBits, int polyBits)
 	return navmesh;
 }
 
+void* CreateNavmeshWithParams(dtNavMeshParams* params)
+{
+	NavigationMesh* navmesh = new NavigationMesh();
+	if (!navmesh->Init(params))
+	{
+		delete navmesh;
+		navmesh = nullptr;
+	}
+	return navmesh;
+}
+
 void DestroyNavmesh(NavigationMesh* navmesh)
 {
 	delete navmesh;
 }
 
-int AddTile(NavigationMesh* navmesh, uint8_t* data, int dataLength)
+void GetNavmeshParams(NavigationMesh* navmesh, dtNavMeshParams* params)
+{
+	navmesh->GetParams(params);
+}
+
+dtStatus AddTile(NavigationMesh* navmesh, uint8_t* data, int dataLength)
 {
 	return navmesh->LoadTile(data, dataLength);
 }
 
-int RemoveTile(NavigationMesh* navmesh, int2* tileCoordinate)
+dtStatus AddTileWithRef(NavigationMesh* navmesh, uint8_t* data, int dataLength, dtTileRef tileRef)
+{
+	return navmesh->LoadTile(data, dataLength, tileRef);
+}
+
+int GetTileData(NavigationMesh* navmesh, int2* tileCoordinate, const uint8_t** data, int* dataLength, dtTileRef* tileRef)
+{
+	return navmesh->GetTileData(*tileCoordinate, data, dataLength, tileRef);
+}
+
+dtStatus RemoveTile(NavigationMesh* navmesh, int2* tileCoordinate)
 {
 	int2 coord = *tileCoordinate;
 	return navmesh->RemoveTile(coord);
 }
 
+dtPolyRef GetTilePolyRefBase(NavigationMesh* navmesh, int2* tileCoordinate)
+{
+	return navmesh->GetTilePolyRefBase(*tileCoordinate);
+}
+
+// Tile cache
+
+void* TileCacheCreate(NavigationMesh* navmesh, dtTileCacheParams* params, DtTileCacheCompressorCallbacks* compressor, uint16_t* areaFlags)
+{
+	AiTileCache* tileCache = new AiTileCache();
+	if (!tileCache->Init(navmesh, params, compressor, areaFlags))
+	{
+		delete tileCache;
+		tileCache = nullptr;
+	}
+	return tileCache;
+}
+
+void TileCacheDestroy(AiTileCache* tileCache)
+{
+	delete tileCache;
+}
+
+dtStatus TileCacheAddTileLayer(AiTileCache* tileCache, uint8_t* data, int dataLength)
+{
+	return tileCache->AddTileLayer(data, dataLength);
+}
+
+int TileCacheRemoveTileLayers(AiTileCache* tileCache, int2* tileCoordinate)
+{
+	return tileCache->RemoveTileLayers(*tileCoordinate);
+}
+
+dtStatus TileCacheBuildNavMeshTile(AiTileCache* tileCache, int2* tileCoordinate)
+{
+	return tileCache->BuildNavMeshTile(*tileCoordinate);
+}
+
+dtStatus TileCacheAddObstacle(AiTileCache* tileCache, float3* position, float radius, float height, dtObstacleRef* result)
+{
+	return tileCache->AddObstacle(*position, radius, height, result);
+}
+
+dtStatus TileCacheAddBoxObstacle(AiTileCache* tileCache, float3* min, float3* max, dtObstacleRef* result)
+{
+	return tileCache->AddBoxObstacle(*min, *max, result);
+}
+
+dtStatus TileCacheAddOrientedBoxObstacle(AiTileCache* tileCache, float3* center, float3* halfExtents, float yRadians, dtObstacleRef* result)
+{
+	return tileCache->AddOrientedBoxObstacle(*center, *halfExtents, yRadians, result);
+}
+
+dtStatus TileCacheRemoveObstacle(AiTileCache* tileCache, dtObstacleRef obstacle)
+{
+	return tileCache->RemoveObstacle(obstacle);
+}
+
+dtStatus TileCacheUpdate(AiTileCache* tileCache, float dt, int* upToDate)
+{
+	return tileCache->Update(dt, upToDate);
+}
+
 // Query
 
 void* QueryCreate(NavigationMesh* navmesh, int maxNodes)
@@ -124,34 +237,59 @@ void QueryInvalidate(AiQuery* aiQuery)
 	aiQuery->Invalidate();
 }
 
-void QueryFindStraightPath(AiQuery* aiQuery, NavMeshPathfindQuery* query, NavMeshPathfindResult* result)
+dtStatus QueryFindStraightPath(AiQuery* aiQuery, NavMeshPathfindQuery* query, DtQueryFilter* filter, NavMeshPathfindResult* result)
+{
+	return aiQuery->FindStraightPath(*query, filter, result);
+}
+
+dtStatus QueryFindPolyPath(AiQuery* aiQuery, NavMeshPathfindQuery* query, DtQueryFilter* filter, NavMeshPolyPathResult* result)
+{
+	return aiQuery->FindPolyPath(*query, filter, result);
+}
+
+dtStatus QueryHasPath(AiQuery* aiQuery, NavMeshPathfindQuery* query, DtQueryFilter* filter)
+{
+	return aiQuery->HasPath(*query, filter);
+}
+
+dtStatus QueryInitSlicedPath(AiQuery* aiQuery, NavMeshPathfindQuery* query, DtQueryFilter* filter)
+{
+	return aiQuery->InitSlicedPath(*query, filter);
+}
+
+dtStatus QueryUpdateSlicedPath(AiQuery* aiQuery, int maxIters, int* doneIters)
+{
+	return aiQuery->UpdateSlicedPath(maxIters, doneIters);
+}
+
+dtStatus QueryFinalizeSlicedPath(AiQuery* aiQuery, dtPolyRef* existingPolys, int numExisting, NavMeshPathfindResult* result)
 {
-	aiQuery->FindStraightPath(*query, result);
+	return aiQuery->FinalizeSlicedPath(existingPolys, numExisting, result);
 }
 
-int QueryHasPath(AiQuery* aiQuery, NavMeshPathfindQuery* query)
+dtStatus QueryRaycast(AiQuery* aiQuery, NavMeshRaycastQuery* query, DtQueryFilter* filter, NavMeshRaycastResult* result)
 {
-	return aiQuery->HasPath(*query);
+	return aiQuery->Raycast(*query, filter, result);
 }
 
-void QueryRaycast(AiQuery* aiQuery, NavMeshRaycastQuery* query, NavMeshRaycastResult* result)
+dtStatus QuerySamplePosition(AiQuery* aiQuery, float3* point, float3* extent, DtQueryFilter* filter, float3* result)
 {
-	aiQuery->Raycast(*query, result);
+	return aiQuery->SamplePosition(*point, *extent, filter, result);
 }
 
-int QuerySamplePosition(AiQuery* aiQuery, float3* point, float3* extent, float3* result)
+dtStatus QueryGetRandomPosition(AiQuery* aiQuery, DtQueryFilter* filter, RandomFloatFn randomFn, void* randomContext, float3* result)
 {
-	return aiQuery->SamplePosition(*point, *extent, result);
+	return aiQuery->GetRandomPosition(filter, randomFn, randomContext, result);
 }
 
-int QueryGetRandomPosition(AiQuery* aiQuery, float3* result)
+dtStatus QueryGetRandomPositionAround(AiQuery* aiQuery, float3* center, float3* extent, float radius, DtQueryFilter* filter, RandomFloatFn randomFn, void* randomContext, float3* result)
 {
-	return aiQuery->GetRandomPosition(result);
+	return aiQuery->GetRandomPositionAround(*center, *extent, radius, filter, randomFn, randomContext, result);
 }
 
-int QueryGetLocation(AiQuery* aiQuery, float3* point, float3* extent, float3* result)
+dtStatus QueryGetLocation(AiQuery* aiQuery, float3* point, float3* extent, DtQueryFilter* filter, float3* result)
 {
-	return aiQuery->GetLocation(*point, *extent, result);
+	return aiQuery->GetLocation(*point, *extent, filter, result);
 }
 
 // Crowd
@@ -182,11 +320,26 @@ void CrowdGetAgents(AiCrowd* crowd, DtCrowdAgentsResult* result)
 	crowd->GetActiveAgents(result);
 }
 
+void CrowdGetAgentSnapshot(AiCrowd* crowd, DtCrowdAgentSnapshot* result)
+{
+	crowd->GetAgentSnapshot(result);
+}
+
 int CrowdGetAgent(AiCrowd* crowd, int idx, DtCrowdAgent* result)
 {
 	return crowd->GetAgent(idx, result);
 }
 
+int CrowdGetAgentInfo(AiCrowd* crowd, int idx, DtCrowdAgentInfo* result)
+{
+	return crowd->GetAgentInfo(idx, result);
+}
+
+int CrowdGetAgentCorridor(AiCrowd* crowd, int idx, uint64_t* polys, int maxPolys)
+{
+	return crowd->GetAgentCorridor(idx, polys, maxPolys);
+}
+
 int CrowdAddAgent(AiCrowd* crowd, float3* position, DtAgentParams* params)
 {
 	return crowd->AddAgent(*position, params);
@@ -207,21 +360,51 @@ void CrowdGetAgentParams(AiCrowd* crowd, int idx, DtAgentParams* agentParams)
 	crowd->GetAgentParams(idx, agentParams);
 }
 
+int CrowdSetFilter(AiCrowd* crowd, int idx, DtQueryFilter* filter)
+{
+	return crowd->SetFilter(idx, filter);
+}
+
+int CrowdGetFilter(AiCrowd* crowd, int idx, DtQueryFilter* filter)
+{
+	return crowd->GetFilter(idx, filter);
+}
+
+int CrowdSetObstacleAvoidanceParams(AiCrowd* crowd, int idx, DtObstacleAvoidanceParams* params)
+{
+	return crowd->SetObstacleAvoidanceParams(idx, params);
+}
+
+int CrowdGetObstacleAvoidanceParams(AiCrowd* crowd, int idx, DtObstacleAvoidanceParams* params)
+{
+	return crowd->GetObstacleAvoidanceParams(idx, params);
+}
+
 int CrowdGetNearestPoly(AiCrowd* crowd, int idx, float3* position, uint64_t* nearestPoly, float3* nearestPoint)
 {
 	return crowd->GetNearestPoly(idx, *position, nearestPoly, nearestPoint);
 }
 
-int CrowdRequestMoveAgentByPoly(AiCrowd* crowd, int idx, uint64_t* polyRef, float3* position)
+dtStatus CrowdRequestMoveAgentByPoly(AiCrowd* crowd, int idx, uint64_t* polyRef, float3* position)
 {
 	return crowd->RequestMoveByPoly(idx, *polyRef, *position);
 }
 
-int CrowdRequestMoveAgent(AiCrowd* crowd, int idx, float3* position)
+dtStatus CrowdRequestMoveAgent(AiCrowd* crowd, int idx, float3* position)
 {
 	return crowd->RequestMove(idx, *position);
 }
 
+dtStatus CrowdRequestMoveVelocity(AiCrowd* crowd, int idx, float3* velocity)
+{
+	return crowd->RequestMoveVelocity(idx, *velocity);
+}
+
+dtStatus CrowdResetMoveTarget(AiCrowd* crowd, int idx)
+{
+	return crowd->ResetMoveTarget(idx);
+}
+
 int CrowdGetAgentCount(AiCrowd* crowd)
 {
 	return crowd->GetAgentCount();
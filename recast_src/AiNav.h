@@ -4,6 +4,7 @@
 #include "NavigationMesh.hpp"
 #include "AiCrowd.hpp"
 #include "AiQuery.hpp"
+#include "AiTileCache.hpp"
 
 #ifdef _WIN32
 #define AINAV_API __declspec(dllexport)
@@ -21,23 +22,47 @@ extern "C" AINAV_API void TestReturnArray(DtCrowdAgentsResult * result);
 extern "C" AINAV_API NavigationBuilder * CreateBuilder();
 extern "C" AINAV_API void DestroyBuilder(NavigationBuilder * nav);
 extern "C" AINAV_API void SetSettings(NavigationBuilder * nav, DtBuildSettings * buildSettings);
+extern "C" AINAV_API void SetOffMeshConnections(NavigationBuilder * nav, DtOffMeshConnection * connections, int numConnections);
+extern "C" AINAV_API void SetConvexVolumes(NavigationBuilder * nav, DtConvexVolume * volumes, int numVolumes);
 extern "C" AINAV_API DtGeneratedData * BuildNavmesh(NavigationBuilder * nav, float3 * vertices, int numVertices, int* indices, int numIndices, uint8_t* areas);
+extern "C" AINAV_API DtBuildStats * GetBuildStats(NavigationBuilder * nav);
+extern "C" AINAV_API DtTileCacheLayersResult * BuildTileCacheLayers(NavigationBuilder * nav, float3 * vertices, int numVertices, int* indices, int numIndices, uint8_t* areas, DtTileCacheCompressorCallbacks * compressor);
 extern "C" AINAV_API void* CreateNavmesh(float cellTileSize, int tileBits, int polyBits);
+extern "C" AINAV_API void* CreateNavmeshWithParams(dtNavMeshParams * params);
 extern "C" AINAV_API void DestroyNavmesh(NavigationMesh * navmesh);
-extern "C" AINAV_API int AddTile(NavigationMesh * navmesh, uint8_t * data, int dataLength);
-extern "C" AINAV_API int RemoveTile(NavigationMesh * navmesh, int2* tileCoordinate);
+extern "C" AINAV_API void GetNavmeshParams(NavigationMesh * navmesh, dtNavMeshParams * params);
+extern "C" AINAV_API dtStatus AddTile(NavigationMesh * navmesh, uint8_t * data, int dataLength);
+extern "C" AINAV_API dtStatus AddTileWithRef(NavigationMesh * navmesh, uint8_t * data, int dataLength, dtTileRef tileRef);
+extern "C" AINAV_API int GetTileData(NavigationMesh * navmesh, int2* tileCoordinate, const uint8_t** data, int* dataLength, dtTileRef* tileRef);
+extern "C" AINAV_API dtStatus RemoveTile(NavigationMesh * navmesh, int2* tileCoordinate);
+extern "C" AINAV_API dtPolyRef GetTilePolyRefBase(NavigationMesh * navmesh, int2* tileCoordinate);
 
+extern "C" AINAV_API void* TileCacheCreate(NavigationMesh * navmesh, dtTileCacheParams * params, DtTileCacheCompressorCallbacks * compressor, uint16_t * areaFlags);
+extern "C" AINAV_API void TileCacheDestroy(AiTileCache * tileCache);
+extern "C" AINAV_API dtStatus TileCacheAddTileLayer(AiTileCache * tileCache, uint8_t * data, int dataLength);
+extern "C" AINAV_API int TileCacheRemoveTileLayers(AiTileCache * tileCache, int2* tileCoordinate);
+extern "C" AINAV_API dtStatus TileCacheBuildNavMeshTile(AiTileCache * tileCache, int2* tileCoordinate);
+extern "C" AINAV_API dtStatus TileCacheAddObstacle(AiTileCache * tileCache, float3* position, float radius, float height, dtObstacleRef * result);
+extern "C" AINAV_API dtStatus TileCacheAddBoxObstacle(AiTileCache * tileCache, float3* min, float3* max, dtObstacleRef * result);
+extern "C" AINAV_API dtStatus TileCacheAddOrientedBoxObstacle(AiTileCache * tileCache, float3* center, float3* halfExtents, float yRadians, dtObstacleRef * result);
+extern "C" AINAV_API dtStatus TileCacheRemoveObstacle(AiTileCache * tileCache, dtObstacleRef obstacle);
+extern "C" AINAV_API dtStatus TileCacheUpdate(AiTileCache * tileCache, float dt, int* upToDate);
 
 extern "C" AINAV_API void* QueryCreate(NavigationMesh * navmesh, int maxNodes);
 extern "C" AINAV_API void QueryDestroy(AiQuery * aiQuery);
 extern "C" AINAV_API void QueryInvalidate(AiQuery * aiQuery);
 extern "C" AINAV_API int QueryIsValid(AiQuery * aiQuery);
-extern "C" AINAV_API void QueryFindStraightPath(AiQuery * aiQuery, NavMeshPathfindQuery* query, NavMeshPathfindResult * result);
-extern "C" AINAV_API int QueryHasPath(AiQuery * aiQuery, NavMeshPathfindQuery* query);
-extern "C" AINAV_API void QueryRaycast(AiQuery * aiQuery, NavMeshRaycastQuery* query, NavMeshRaycastResult * result);
-extern "C" AINAV_API int QuerySamplePosition(AiQuery * aiQuery, float3* point, float3* extent, float3 * result);
-extern "C" AINAV_API int QueryGetRandomPosition(AiQuery * aiQuery, float3 * result);
-extern "C" AINAV_API int QueryGetLocation(AiQuery * aiQuery, float3* point, float3* extent, float3 * result);
+extern "C" AINAV_API dtStatus QueryFindStraightPath(AiQuery * aiQuery, NavMeshPathfindQuery* query, DtQueryFilter* filter, NavMeshPathfindResult * result);
+extern "C" AINAV_API dtStatus QueryFindPolyPath(AiQuery * aiQuery, NavMeshPathfindQuery* query, DtQueryFilter* filter, NavMeshPolyPathResult * result);
+extern "C" AINAV_API dtStatus QueryHasPath(AiQuery * aiQuery, NavMeshPathfindQuery* query, DtQueryFilter* filter);
+extern "C" AINAV_API dtStatus QueryInitSlicedPath(AiQuery * aiQuery, NavMeshPathfindQuery* query, DtQueryFilter* filter);
+extern "C" AINAV_API dtStatus QueryUpdateSlicedPath(AiQuery * aiQuery, int maxIters, int* doneIters);
+extern "C" AINAV_API dtStatus QueryFinalizeSlicedPath(AiQuery * aiQuery, dtPolyRef* existingPolys, int numExisting, NavMeshPathfindResult * result);
+extern "C" AINAV_API dtStatus QueryRaycast(AiQuery * aiQuery, NavMeshRaycastQuery* query, DtQueryFilter* filter, NavMeshRaycastResult * result);
+extern "C" AINAV_API dtStatus QuerySamplePosition(AiQuery * aiQuery, float3* point, float3* extent, DtQueryFilter* filter, float3 * result);
+extern "C" AINAV_API dtStatus QueryGetRandomPosition(AiQuery * aiQuery, DtQueryFilter* filter, RandomFloatFn randomFn, void* randomContext, float3 * result);
+extern "C" AINAV_API dtStatus QueryGetRandomPositionAround(AiQuery * aiQuery, float3* center, float3* extent, float radius, DtQueryFilter* filter, RandomFloatFn randomFn, void* randomContext, float3 * result);
+extern "C" AINAV_API dtStatus QueryGetLocation(AiQuery * aiQuery, float3* point, float3* extent, DtQueryFilter* filter, float3 * result);
 
 extern "C" AINAV_API int CrowdGetVersion(AiCrowd * crowd);
 extern "C" AINAV_API void* CrowdCreate(NavigationMesh * navmesh, int maxAgents, float maxAgentRadius);
@@ -47,9 +72,18 @@ extern "C" AINAV_API void CrowdRemoveAgent(AiCrowd * crowd, int idx);
 extern "C" AINAV_API int CrowdGetAgentCount(AiCrowd * crowd);
 extern "C" AINAV_API void CrowdSetAgentParams(AiCrowd * crowd, int idx, DtAgentParams * agentParams);
 extern "C" AINAV_API void CrowdGetAgentParams(AiCrowd * crowd, int idx, DtAgentParams * agentParams);
-extern "C" AINAV_API int CrowdRequestMoveAgent(AiCrowd * crowd, int idx, float3* position);
-extern "C" AINAV_API int CrowdRequestMoveAgentByPoly(AiCrowd * crowd, int idx, uint64_t* polyRef, float3 * position);
+extern "C" AINAV_API int CrowdSetFilter(AiCrowd * crowd, int idx, DtQueryFilter * filter);
+extern "C" AINAV_API int CrowdGetFilter(AiCrowd * crowd, int idx, DtQueryFilter * filter);
+extern "C" AINAV_API int CrowdSetObstacleAvoidanceParams(AiCrowd * crowd, int idx, DtObstacleAvoidanceParams * params);
+extern "C" AINAV_API int CrowdGetObstacleAvoidanceParams(AiCrowd * crowd, int idx, DtObstacleAvoidanceParams * params);
+extern "C" AINAV_API dtStatus CrowdRequestMoveAgent(AiCrowd * crowd, int idx, float3* position);
+extern "C" AINAV_API dtStatus CrowdRequestMoveAgentByPoly(AiCrowd * crowd, int idx, uint64_t* polyRef, float3 * position);
+extern "C" AINAV_API dtStatus CrowdRequestMoveVelocity(AiCrowd * crowd, int idx, float3 * velocity);
+extern "C" AINAV_API dtStatus CrowdResetMoveTarget(AiCrowd * crowd, int idx);
 extern "C" AINAV_API int CrowdGetNearestPoly(AiCrowd * crowd, int idx, float3* position, uint64_t * nearestPoly, float3 * nearestPoint);
 extern "C" AINAV_API void CrowdUpdate(AiCrowd * crowd, const float dt);
 extern "C" AINAV_API int CrowdGetAgent(AiCrowd * crowd, int idx, DtCrowdAgent * result);
+extern "C" AINAV_API int CrowdGetAgentInfo(AiCrowd * crowd, int idx, DtCrowdAgentInfo * result);
+extern "C" AINAV_API int CrowdGetAgentCorridor(AiCrowd * crowd, int idx, uint64_t * polys, int maxPolys);
 extern "C" AINAV_API void CrowdGetAgents(AiCrowd * crowd, DtCrowdAgentsResult * result);
+extern "C" AINAV_API void CrowdGetAgentSnapshot(AiCrowd * crowd, DtCrowdAgentSnapshot * result);
@@ -1,12 +1,33 @@
 #include "NavigationMesh.hpp"
 #include "AiQuery.hpp"
 #include <DetourCommon.h>
+#include <float.h>
+#include <string.h>
 
-static float frand()
+// Detour's random queries take a plain function pointer, so the caller supplied
+// generator is stashed per thread for the duration of the query.
+static thread_local RandomFloatFn t_randomFn = nullptr;
+static thread_local void* t_randomContext = nullptr;
+
+static float callbackRand()
 {
-	return (float)rand() / (float)RAND_MAX;
+	return t_randomFn(t_randomContext);
 }
 
+struct ScopedRandom
+{
+	ScopedRandom(RandomFloatFn randomFn, void* randomContext)
+	{
+		t_randomFn = randomFn;
+		t_randomContext = randomContext;
+	}
+	~ScopedRandom()
+	{
+		t_randomFn = nullptr;
+		t_randomContext = nullptr;
+	}
+};
+
 AiQuery::AiQuery()
 {
 }
@@ -38,173 +59,352 @@ int AiQuery::IsValid()
 	return invalidated == 0;
 }
 
-int AiQuery::GetRandomPosition(float3* result)
+dtStatus AiQuery::GetRandomPosition(const DtQueryFilter* queryFilter, RandomFloatFn randomFn, void* randomContext, float3* result)
 {
-	if (invalidated == 1)
-		return 0;
+	if (invalidated == 1 || !randomFn)
+		return DT_FAILURE | DT_INVALID_PARAM;
 
 	dtPolyRef startPoly;
 	float3 startPoint;
 	dtQueryFilter filter;
+	ToDetourFilter(queryFilter, &filter);
 	dtStatus status;
 
-	status = m_navQuery->findRandomPoint(&filter, frand, &startPoly, &startPoint.x);
+	ScopedRandom random(randomFn, randomContext);
+	status = m_navQuery->findRandomPoint(&filter, callbackRand, &startPoly, &startPoint.x);
 	if (dtStatusFailed(status)) {
-		return 0;
+		return status;
 	}
 	result->x = startPoint.x;
 	result->y = startPoint.y;
 	result->z = startPoint.z;
-	return 1;
+	return status;
+}
+
+dtStatus AiQuery::GetRandomPositionAround(float3 center, float3 extent, float radius, const DtQueryFilter* queryFilter, RandomFloatFn randomFn, void* randomContext, float3* result)
+{
+	if (invalidated == 1 || !randomFn)
+		return DT_FAILURE | DT_INVALID_PARAM;
+
+	dtQueryFilter filter;
+	ToDetourFilter(queryFilter, &filter);
+
+	dtPolyRef centerPoly;
+	float3 centerPoint;
+	dtStatus status = m_navQuery->findNearestPoly(&center.x, &extent.x, &filter, &centerPoly, &centerPoint.x);
+	if (dtStatusFailed(status))
+		return status;
+	if (centerPoly == 0)
+		return DT_FAILURE | AINAV_NO_NEAREST_POLY;
+
+	dtPolyRef randomPoly;
+	float3 randomPoint;
+	ScopedRandom random(randomFn, randomContext);
+	status = m_navQuery->findRandomPointAroundCircle(centerPoly, &centerPoint.x, radius, &filter, callbackRand, &randomPoly, &randomPoint.x);
+	if (dtStatusFailed(status))
+		return status;
+
+	result->x = randomPoint.x;
+	result->y = randomPoint.y;
+	result->z = randomPoint.z;
+	return status;
 }
 
-int AiQuery::SamplePosition(float3 point, float3 extent, float3* result)
+dtStatus AiQuery::SamplePosition(float3 point, float3 extent, const DtQueryFilter* queryFilter, float3* result)
 {
 	if (invalidated == 1)
-		return 0;
+		return DT_FAILURE | DT_INVALID_PARAM;
 
 	dtPolyRef startPoly;
 	float3 startPoint;
 
 	dtQueryFilter filter;
+	ToDetourFilter(queryFilter, &filter);
 	dtStatus status;
 
 	status = m_navQuery->findNearestPoly(&point.x, &extent.x, &filter, &startPoly, &startPoint.x);
-	if (dtStatusFailed(status) || !startPoly) {
-		return 0;
+	if (dtStatusFailed(status)) {
+		return status;
+	}
+	if (!startPoly) {
+		return DT_FAILURE | AINAV_NO_NEAREST_POLY;
 	}
 
 	result->x = startPoint.x;
 	result->y = startPoint.y;
 	result->z = startPoint.z;
-	return 1;
+	return status;
 }
 
-int AiQuery::GetLocation(float3 point, float3 extent, float3* result) {
+dtStatus AiQuery::GetLocation(float3 point, float3 extent, const DtQueryFilter* queryFilter, float3* result) {
 
 	if (invalidated == 1)
-		return 0;
+		return DT_FAILURE | DT_INVALID_PARAM;
 
 	dtPolyRef startPoly;
 	float3 startPoint;
 
 	dtQueryFilter filter;
+	ToDetourFilter(queryFilter, &filter);
 	dtStatus status;
 
 	float npos[3];
 	bool overlay = false;
 
 	status = m_navQuery->findNearestPoly(&point.x, &extent.x, &filter, &startPoly, npos);
-	if (dtStatusFailed(status) || !startPoly) {
-		return 0;
+	if (dtStatusFailed(status)) {
+		return status;
+	}
+	if (!startPoly) {
+		return DT_FAILURE | AINAV_NO_NEAREST_POLY;
 	}
 
 	status = m_navQuery->closestPointOnPoly(startPoly, npos, &startPoint.x, &overlay);
 	if (dtStatusFailed(status)) {
-		return 0;
+		return status;
 	}
 
 	result->x = startPoint.x;
 	result->y = startPoint.y;
 	result->z = startPoint.z;
-	return 1;
+	return status;
 
 }
 
-int AiQuery::HasPath(NavMeshPathfindQuery query)
+// Nearest polys to the query source and target
+dtStatus AiQuery::FindEndPolys(const NavMeshPathfindQuery& query, const dtQueryFilter* filter,
+	dtPolyRef* startPoly, float3* startPoint, dtPolyRef* endPoly, float3* endPoint)
+{
+	dtStatus status;
+	status = m_navQuery->findNearestPoly(&query.source.x, &query.findNearestPolyExtent.x, filter, startPoly, &startPoint->x);
+	if (dtStatusFailed(status))
+		return status;
+	if (*startPoly == 0)
+		return DT_FAILURE | AINAV_NO_NEAREST_POLY;
+	status = m_navQuery->findNearestPoly(&query.target.x, &query.findNearestPolyExtent.x, filter, endPoly, &endPoint->x);
+	if (dtStatusFailed(status))
+		return status;
+	if (*endPoly == 0)
+		return DT_FAILURE | AINAV_NO_NEAREST_POLY;
+	return status;
+}
+
+dtStatus AiQuery::HasPath(NavMeshPathfindQuery query, const DtQueryFilter* queryFilter)
 {
 	if (invalidated == 1)
-		return 0;
+		return DT_FAILURE | DT_INVALID_PARAM;
 
 	dtPolyRef startPoly, endPoly;
 	float3 startPoint, endPoint;
 
 	// Find the starting polygons and point on it to start from
 	dtQueryFilter filter;
-	dtStatus status;
-	status = m_navQuery->findNearestPoly(&query.source.x, &query.findNearestPolyExtent.x, &filter, &startPoly, &startPoint.x);
-	if (dtStatusFailed(status))
-		return 0;
-	status = m_navQuery->findNearestPoly(&query.target.x, &query.findNearestPolyExtent.x, &filter, &endPoly, &endPoint.x);
+	ToDetourFilter(queryFilter, &filter);
+	dtStatus status = FindEndPolys(query, &filter, &startPoly, &startPoint, &endPoly, &endPoint);
 	if (dtStatusFailed(status))
-		return 0;
+		return status;
 
 	vector<dtPolyRef> polys;
 	polys.resize(query.maxPathPoints);
 	int pathPointCount = 0;
-	status = m_navQuery->findPath(startPoly, endPoly, &startPoint.x, &endPoint.x,
+	// A partial result is a success with DT_PARTIAL_RESULT set
+	return m_navQuery->findPath(startPoly, endPoly, &startPoint.x, &endPoint.x,
 		&filter, polys.data(), &pathPointCount, query.maxPathPoints);
-	if (dtStatusFailed(status) || (status & DT_PARTIAL_RESULT) != 0)
-		return 0;
-
-	return 1;
 }
 
-void AiQuery::FindStraightPath(NavMeshPathfindQuery query, NavMeshPathfindResult* result)
+dtStatus AiQuery::FindStraightPath(NavMeshPathfindQuery query, const DtQueryFilter* queryFilter, NavMeshPathfindResult* result)
 {
-	if (invalidated == 1)
-		return;
-
 	// Reset result
 	result->pathFound = false;
+	result->numPathPoints = 0;
+
+	if (invalidated == 1)
+		return DT_FAILURE | DT_INVALID_PARAM;
+
 	dtPolyRef startPoly, endPoly;
 	float3 startPoint, endPoint;
 
 	// Find the starting polygons and point on it to start from
 	dtQueryFilter filter;
-	dtStatus status;
-	status = m_navQuery->findNearestPoly(&query.source.x, &query.findNearestPolyExtent.x, &filter, &startPoly, &startPoint.x);
-	if (dtStatusFailed(status))
-		return;
-	status = m_navQuery->findNearestPoly(&query.target.x, &query.findNearestPolyExtent.x, &filter, &endPoly, &endPoint.x);
+	ToDetourFilter(queryFilter, &filter);
+	dtStatus status = FindEndPolys(query, &filter, &startPoly, &startPoint, &endPoly, &endPoint);
 	if (dtStatusFailed(status))
-		return;
+		return status;
 
 	vector<dtPolyRef> polys;
 	polys.resize(query.maxPathPoints);
 	int pathPointCount = 0;
-	status = m_navQuery->findPath(startPoly, endPoly, &startPoint.x, &endPoint.x,
+	dtStatus pathStatus = m_navQuery->findPath(startPoly, endPoly, &startPoint.x, &endPoint.x,
 		&filter, polys.data(), &pathPointCount, query.maxPathPoints);
-	if (dtStatusFailed(status) || (status & DT_PARTIAL_RESULT) != 0)
-		return;
+	if (dtStatusFailed(pathStatus))
+		return pathStatus;
+
+	status = StraightPathFromCorridor(query, polys.data(), pathPointCount, endPoly, startPoint, endPoint, result);
+	if (dtStatusFailed(status))
+		return status;
+	// Keep DT_PARTIAL_RESULT from the corridor search
+	return status | (pathStatus & DT_STATUS_DETAIL_MASK);
+}
+
+dtStatus AiQuery::StraightPathFromCorridor(const NavMeshPathfindQuery& query, const dtPolyRef* polys, int numPolys,
+	dtPolyRef endPoly, float3 startPoint, float3 endPoint, NavMeshPathfindResult* result)
+{
+	// A partial corridor ends at the reachable polygon closest to the target, walk to the closest point on it
+	if (numPolys > 0 && polys[numPolys - 1] != endPoly)
+	{
+		float3 closestPoint;
+		if (dtStatusSucceed(m_navQuery->closestPointOnPoly(polys[numPolys - 1], &endPoint.x, &closestPoint.x, 0)))
+			endPoint = closestPoint;
+	}
 
-	vector<float3> straightPath;
 	vector<uint8_t> straightPathFlags;
 	vector<dtPolyRef> straightpathPolys;
-	straightPath.resize(query.maxPathPoints);
 	straightPathFlags.resize(query.maxPathPoints);
 	straightpathPolys.resize(query.maxPathPoints);
-	status = m_navQuery->findStraightPath(&startPoint.x, &endPoint.x,
-		polys.data(), pathPointCount,
-		(float*)result->pathPoints, straightPathFlags.data(), straightpathPolys.data(),
-		&result->numPathPoints, query.maxPathPoints);
+	uint8_t* pathFlags = result->pathFlags ? result->pathFlags : straightPathFlags.data();
+	dtPolyRef* pathPolys = result->pathPolys ? result->pathPolys : straightpathPolys.data();
+	dtStatus status = m_navQuery->findStraightPath(&startPoint.x, &endPoint.x,
+		polys, numPolys,
+		(float*)result->pathPoints, pathFlags, pathPolys,
+		&result->numPathPoints, query.maxPathPoints, query.straightPathOptions);
 	if (dtStatusFailed(status))
-		return;
+		return status;
 	result->pathFound = true;
+	return status;
 }
 
-void AiQuery::Raycast(NavMeshRaycastQuery query, NavMeshRaycastResult* result)
+dtStatus AiQuery::InitSlicedPath(NavMeshPathfindQuery query, const DtQueryFilter* queryFilter)
 {
+	m_sliceActive = false;
 	if (invalidated == 1)
-		return;
+		return DT_FAILURE | DT_INVALID_PARAM;
+
+	dtPolyRef startPoly;
+	ToDetourFilter(queryFilter, &m_sliceFilter);
+	dtStatus status = FindEndPolys(query, &m_sliceFilter, &startPoly, &m_sliceStartPoint, &m_sliceEndPoly, &m_sliceEndPoint);
+	if (dtStatusFailed(status))
+		return status;
+
+	status = m_navQuery->initSlicedFindPath(startPoly, m_sliceEndPoly, &m_sliceStartPoint.x, &m_sliceEndPoint.x, &m_sliceFilter);
+	if (dtStatusFailed(status))
+		return status;
+	m_sliceQuery = query;
+	m_sliceActive = true;
+	return status;
+}
+
+dtStatus AiQuery::UpdateSlicedPath(int maxIters, int* doneIters)
+{
+	*doneIters = 0;
+	if (invalidated == 1 || !m_sliceActive)
+		return DT_FAILURE | DT_INVALID_PARAM;
+	return m_navQuery->updateSlicedFindPath(maxIters, doneIters);
+}
 
+dtStatus AiQuery::FinalizeSlicedPath(const dtPolyRef* existingPolys, int numExisting, NavMeshPathfindResult* result)
+{
 	// Reset result
+	result->pathFound = false;
+	result->numPathPoints = 0;
+
+	if (invalidated == 1 || !m_sliceActive)
+		return DT_FAILURE | DT_INVALID_PARAM;
+	m_sliceActive = false;
+
+	vector<dtPolyRef> polys;
+	polys.resize(m_sliceQuery.maxPathPoints);
+	int numPolys = 0;
+	dtStatus pathStatus;
+	if (existingPolys && numExisting > 0)
+		pathStatus = m_navQuery->finalizeSlicedFindPathPartial(existingPolys, numExisting, polys.data(), &numPolys, m_sliceQuery.maxPathPoints);
+	else
+		pathStatus = m_navQuery->finalizeSlicedFindPath(polys.data(), &numPolys, m_sliceQuery.maxPathPoints);
+	if (dtStatusFailed(pathStatus))
+		return pathStatus;
+	// finalizeSlicedFindPathPartial doesn't flag a corridor that stops at an existing polygon short of the target
+	if (numPolys > 0 && polys[numPolys - 1] != m_sliceEndPoly)
+		pathStatus |= DT_PARTIAL_RESULT;
+
+	dtStatus status = StraightPathFromCorridor(m_sliceQuery, polys.data(), numPolys, m_sliceEndPoly, m_sliceStartPoint, m_sliceEndPoint, result);
+	if (dtStatusFailed(status))
+		return status;
+	return status | (pathStatus & DT_STATUS_DETAIL_MASK);
+}
+
+dtStatus AiQuery::FindPolyPath(NavMeshPathfindQuery query, const DtQueryFilter* queryFilter, NavMeshPolyPathResult* result)
+{
+	// Reset result
+	result->pathFound = false;
+	result->numPolys = 0;
+
+	if (invalidated == 1)
+		return DT_FAILURE | DT_INVALID_PARAM;
+
+	dtPolyRef startPoly, endPoly;
+	float3 startPoint, endPoint;
+
+	dtQueryFilter filter;
+	ToDetourFilter(queryFilter, &filter);
+	dtStatus status = FindEndPolys(query, &filter, &startPoly, &startPoint, &endPoly, &endPoint);
+	if (dtStatusFailed(status))
+		return status;
+
+	status = m_navQuery->findPath(startPoly, endPoly, &startPoint.x, &endPoint.x,
+		&filter, result->polys, &result->numPolys, query.maxPathPoints);
+	if (dtStatusFailed(status))
+	{
+		result->numPolys = 0;
+		return status;
+	}
+	result->pathFound = true;
+	return status;
+}
+
+dtStatus AiQuery::Raycast(NavMeshRaycastQuery query, const DtQueryFilter* queryFilter, NavMeshRaycastResult* result)
+{
+	// Reset result
+	result->found = false;
 	result->hit = false;
+	result->t = 1.0f;
+	result->numPolys = 0;
+	result->pathCost = 0.0f;
+
+	if (invalidated == 1)
+		return DT_FAILURE | DT_INVALID_PARAM;
+
 	dtQueryFilter filter;
+	ToDetourFilter(queryFilter, &filter);
 
 	dtPolyRef startPoly;
-	dtStatus status = m_navQuery->findNearestPoly(&query.start.x, &query.findNearestPolyExtent.x, &filter, &startPoly, 0);
+	float3 startPoint;
+	dtStatus status = m_navQuery->findNearestPoly(&query.start.x, &query.findNearestPolyExtent.x, &filter, &startPoly, &startPoint.x);
 	if (dtStatusFailed(status))
-		return;
-
-	float t;
-	vector<dtPolyRef> polys;
-	polys.resize(query.maxPathPoints);
-	int raycastPolyCount = 0;
-	status = m_navQuery->raycast(startPoly, &query.start.x, &query.end.x, &filter, &t, &result->normal.x, polys.data(), &raycastPolyCount, query.maxPathPoints);
+		return status;
+	if (startPoly == 0)
+		return DT_FAILURE | AINAV_NO_NEAREST_POLY;
+
+	dtRaycastHit hit;
+	memset(&hit, 0, sizeof(hit));
+	hit.path = result->polys;
+	hit.maxPath = result->polys ? query.maxPathPoints : 0;
+	status = m_navQuery->raycast(startPoly, &startPoint.x, &query.end.x, &filter, DT_RAYCAST_USE_COSTS, &hit);
 	if (dtStatusFailed(status))
-		return;
-
-	result->hit = true;
-	dtVlerp(&result->position.x, &query.start.x, &query.end.x, t);
+		return status;
+
+	result->found = true;
+	result->numPolys = hit.pathCount;
+	result->pathCost = hit.pathCost;
+	dtVcopy(&result->normal.x, hit.hitNormal);
+
+	// t is FLT_MAX when the ray reached the end without hitting a wall
+	result->hit = hit.t < FLT_MAX;
+	result->t = result->hit ? dtClamp(hit.t, 0.0f, 1.0f) : 1.0f;
+	dtVlerp(&result->position.x, &startPoint.x, &query.end.x, result->t);
+
+	// Snap the height to the surface of the last visited polygon
+	dtPolyRef lastPoly = hit.pathCount > 0 ? hit.path[hit.pathCount - 1] : startPoly;
+	float3 surfacePoint;
+	if (dtStatusSucceed(m_navQuery->closestPointOnPoly(lastPoly, &result->position.x, &surfacePoint.x, 0)))
+		result->position.y = surfacePoint.y;
+	return status;
 }
@@ -9,16 +9,35 @@ private:
 	dtNavMesh* m_navMesh = nullptr;
 	dtNavMeshQuery* m_navQuery = nullptr;
 	int invalidated = 0;
+	// Sliced path state.  Detour keeps a pointer to the filter for the whole search
+	bool m_sliceActive = false;
+	dtQueryFilter m_sliceFilter;
+	NavMeshPathfindQuery m_sliceQuery;
+	dtPolyRef m_sliceEndPoly = 0;
+	float3 m_sliceStartPoint;
+	float3 m_sliceEndPoint;
+	dtStatus FindEndPolys(const NavMeshPathfindQuery& query, const dtQueryFilter* filter,
+		dtPolyRef* startPoly, float3* startPoint, dtPolyRef* endPoly, float3* endPoint);
+	dtStatus StraightPathFromCorridor(const NavMeshPathfindQuery& query, const dtPolyRef* polys, int numPolys,
+		dtPolyRef endPoly, float3 startPoint, float3 endPoint, NavMeshPathfindResult* result);
 public:
 	AiQuery();
 	~AiQuery();
 	int Init(NavigationMesh* navmesh, int maxNodes);
-	void FindStraightPath(NavMeshPathfindQuery query, NavMeshPathfindResult* result);
-	int HasPath(NavMeshPathfindQuery query);
-	void Raycast(NavMeshRaycastQuery query, NavMeshRaycastResult* result);
-	int SamplePosition(float3 point, float3 extent, float3* result);
-	int GetRandomPosition(float3* result);
-	int GetLocation(float3 point, float3 extent, float3* result);
+	// Queries return the detour status, with AINAV_NO_NEAREST_POLY set when a position isn't on the navmesh
+	dtStatus FindStraightPath(NavMeshPathfindQuery query, const DtQueryFilter* queryFilter, NavMeshPathfindResult* result);
+	dtStatus FindPolyPath(NavMeshPathfindQuery query, const DtQueryFilter* queryFilter, NavMeshPolyPathResult* result);
+	dtStatus HasPath(NavMeshPathfindQuery query, const DtQueryFilter* queryFilter);
+	// Sliced path finding, only one sliced search per query at a time
+	dtStatus InitSlicedPath(NavMeshPathfindQuery query, const DtQueryFilter* queryFilter);
+	dtStatus UpdateSlicedPath(int maxIters, int* doneIters);
+	// existingPolys is an earlier corridor to keep as much of as possible, null for a plain finalize
+	dtStatus FinalizeSlicedPath(const dtPolyRef* existingPolys, int numExisting, NavMeshPathfindResult* result);
+	dtStatus Raycast(NavMeshRaycastQuery query, const DtQueryFilter* queryFilter, NavMeshRaycastResult* result);
+	dtStatus SamplePosition(float3 point, float3 extent, const DtQueryFilter* queryFilter, float3* result);
+	dtStatus GetRandomPosition(const DtQueryFilter* queryFilter, RandomFloatFn randomFn, void* randomContext, float3* result);
+	dtStatus GetRandomPositionAround(float3 center, float3 extent, float radius, const DtQueryFilter* queryFilter, RandomFloatFn randomFn, void* randomContext, float3* result);
+	dtStatus GetLocation(float3 point, float3 extent, const DtQueryFilter* queryFilter, float3* result);
 	int IsValid();
 	void Invalidate();
 };
\ No newline at end of file
@@ -0,0 +1,128 @@
+#include "AiTileCache.hpp"
+#include <DetourCommon.h>
+#include <DetourNavMeshBuilder.h>
+#include <string.h>
+
+CallbackCompressor::CallbackCompressor(const DtTileCacheCompressorCallbacks& callbacks)
+{
+	m_callbacks = callbacks;
+}
+
+int CallbackCompressor::maxCompressedSize(const int bufferSize)
+{
+	return m_callbacks.maxCompressedSize(m_callbacks.context, bufferSize);
+}
+
+dtStatus CallbackCompressor::compress(const unsigned char* buffer, const int bufferSize,
+	unsigned char* compressed, const int maxCompressedSize, int* compressedSize)
+{
+	if (m_callbacks.compress(m_callbacks.context, buffer, bufferSize, compressed, maxCompressedSize, compressedSize) != 1)
+		return DT_FAILURE;
+	return DT_SUCCESS;
+}
+
+dtStatus CallbackCompressor::decompress(const unsigned char* compressed, const int compressedSize,
+	unsigned char* buffer, const int maxBufferSize, int* bufferSize)
+{
+	if (m_callbacks.decompress(m_callbacks.context, compressed, compressedSize, buffer, maxBufferSize, bufferSize) != 1)
+		return DT_FAILURE;
+	return DT_SUCCESS;
+}
+
+void AiTileCacheMeshProcess::process(struct dtNavMeshCreateParams* params, unsigned char* polyAreas, unsigned short* polyFlags)
+{
+	for (int i = 0; i < params->polyCount; ++i)
+	{
+		if (polyAreas[i] != DT_TILECACHE_NULL_AREA)
+			polyFlags[i] = areaFlags[polyAreas[i] & 0x3f];
+	}
+}
+
+AiTileCache::AiTileCache()
+{
+}
+
+AiTileCache::~AiTileCache()
+{
+	if (m_tileCache)
+		dtFreeTileCache(m_tileCache);
+	delete m_compressor;
+}
+
+int AiTileCache::Init(NavigationMesh* navmesh, const dtTileCacheParams* params, const DtTileCacheCompressorCallbacks* compressor, const uint16_t* areaFlags)
+{
+	m_navMesh = navmesh->GetDtNavMesh();
+	memcpy(m_meshProcess.areaFlags, areaFlags, sizeof(m_meshProcess.areaFlags));
+	m_compressor = new CallbackCompressor(*compressor);
+	m_tileCache = dtAllocTileCache();
+	if (!m_tileCache)
+		return 0;
+
+	dtStatus status = m_tileCache->init(params, &m_alloc, m_compressor, &m_meshProcess);
+	if (dtStatusFailed(status))
+		return 0;
+	return 1;
+}
+
+dtStatus AiTileCache::AddTileLayer(const uint8_t* data, int dataLength)
+{
+	if (!data || dataLength <= 0)
+		return DT_FAILURE | DT_INVALID_PARAM;
+
+	// The tile cache frees the copy when the layer is removed
+	uint8_t* dataCopy = (uint8_t*)dtAlloc(dataLength, DT_ALLOC_PERM);
+	if (!dataCopy)
+		return DT_FAILURE | DT_OUT_OF_MEMORY;
+	memcpy(dataCopy, data, dataLength);
+
+	dtCompressedTileRef tileRef = 0;
+	dtStatus status = m_tileCache->addTile(dataCopy, dataLength, DT_COMPRESSEDTILE_FREE_DATA, &tileRef);
+	if (dtStatusFailed(status))
+		dtFree(dataCopy);
+	return status;
+}
+
+int AiTileCache::RemoveTileLayers(int2 tileCoordinate)
+{
+	const int maxLayers = 32;
+	dtCompressedTileRef tiles[maxLayers];
+	int count = m_tileCache->getTilesAt(tileCoordinate.x, tileCoordinate.y, tiles, maxLayers);
+	for (int i = 0; i < count; i++)
+	{
+		m_tileCache->removeTile(tiles[i], 0, 0);
+	}
+	return count;
+}
+
+dtStatus AiTileCache::BuildNavMeshTile(int2 tileCoordinate)
+{
+	return m_tileCache->buildNavMeshTilesAt(tileCoordinate.x, tileCoordinate.y, m_navMesh);
+}
+
+dtStatus AiTileCache::AddObstacle(float3 position, float radius, float height, dtObstacleRef* result)
+{
+	return m_tileCache->addObstacle(&position.x, radius, height, result);
+}
+
+dtStatus AiTileCache::AddBoxObstacle(float3 min, float3 max, dtObstacleRef* result)
+{
+	return m_tileCache->addBoxObstacle(&min.x, &max.x, result);
+}
+
+dtStatus AiTileCache::AddOrientedBoxObstacle(float3 center, float3 halfExtents, float yRadians, dtObstacleRef* result)
+{
+	return m_tileCache->addBoxObstacle(&center.x, &halfExtents.x, yRadians, result);
+}
+
+dtStatus AiTileCache::RemoveObstacle(dtObstacleRef obstacle)
+{
+	return m_tileCache->removeObstacle(obstacle);
+}
+
+dtStatus AiTileCache::Update(float dt, int* upToDate)
+{
+	bool done = false;
+	dtStatus status = m_tileCache->update(dt, m_navMesh, &done);
+	*upToDate = done ? 1 : 0;
+	return status;
+}
@@ -0,0 +1,51 @@
+#pragma once
+#include <DetourNavMesh.h>
+#include <DetourTileCache.h>
+#include <DetourTileCacheBuilder.h>
+#include "Navigation.hpp"
+#include "NavigationMesh.hpp"
+
+// Forwards tile cache compression to callbacks supplied by the caller
+class CallbackCompressor : public dtTileCacheCompressor
+{
+private:
+	DtTileCacheCompressorCallbacks m_callbacks;
+public:
+	CallbackCompressor(const DtTileCacheCompressorCallbacks& callbacks);
+	int maxCompressedSize(const int bufferSize) override;
+	dtStatus compress(const unsigned char* buffer, const int bufferSize,
+		unsigned char* compressed, const int maxCompressedSize, int* compressedSize) override;
+	dtStatus decompress(const unsigned char* compressed, const int compressedSize,
+		unsigned char* buffer, const int maxBufferSize, int* bufferSize) override;
+};
+
+// Sets navmesh poly flags on tiles the tile cache builds, the same way NavigationBuilder does
+class AiTileCacheMeshProcess : public dtTileCacheMeshProcess
+{
+public:
+	// Poly flags for each area id
+	uint16_t areaFlags[DT_MAX_AREAS];
+	void process(struct dtNavMeshCreateParams* params, unsigned char* polyAreas, unsigned short* polyFlags) override;
+};
+
+class AiTileCache {
+private:
+	dtNavMesh* m_navMesh = nullptr;
+	dtTileCache* m_tileCache = nullptr;
+	dtTileCacheAlloc m_alloc;
+	CallbackCompressor* m_compressor = nullptr;
+	AiTileCacheMeshProcess m_meshProcess;
+public:
+	AiTileCache();
+	~AiTileCache();
+	int Init(NavigationMesh* navmesh, const dtTileCacheParams* params, const DtTileCacheCompressorCallbacks* compressor, const uint16_t* areaFlags);
+	// Data is copied
+	dtStatus AddTileLayer(const uint8_t* data, int dataLength);
+	int RemoveTileLayers(int2 tileCoordinate);
+	dtStatus BuildNavMeshTile(int2 tileCoordinate);
+	dtStatus AddObstacle(float3 position, float radius, float height, dtObstacleRef* result);
+	dtStatus AddBoxObstacle(float3 min, float3 max, dtObstacleRef* result);
+	dtStatus AddOrientedBoxObstacle(float3 center, float3 halfExtents, float yRadians, dtObstacleRef* result);
+	dtStatus RemoveObstacle(dtObstacleRef obstacle);
+	dtStatus Update(float dt, int* upToDate);
+};
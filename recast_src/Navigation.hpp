@@ -22,6 +22,13 @@ typedef struct DtBoundingBox
 	float3 max;
 } DtBoundingBox;
 
+enum DtPartitionType
+{
+	DT_PARTITION_WATERSHED = 0,
+	DT_PARTITION_MONOTONE = 1,
+	DT_PARTITION_LAYERS = 2,
+};
+
 struct DtBuildSettings
 {
 	// Bounding box for the generated navigation mesh
@@ -40,6 +47,72 @@ struct DtBuildSettings
 	float agentRadius;
 	float agentMaxClimb;
 	float agentMaxSlope;
+	int partitionType;
+	int filterLowHangingObstacles;
+	int filterLedgeSpans;
+	int filterWalkableLowHeightSpans;
+	int maxVertsPerPoly;
+	// Poly flags for each area id, DT_MAX_AREAS entries
+	uint16_t areaFlags[64];
+};
+
+struct DtOffMeshConnection
+{
+	float3 start;
+	float3 end;
+	float radius;
+	int bidirectional;
+	uint8_t area;
+	uint16_t flags;
+	uint32_t userId;
+};
+
+#define DT_MAX_CONVEX_VOLUME_VERTS 12
+
+enum DtConvexVolumeType
+{
+	DT_CONVEX_VOLUME_POLYGON = 0,
+	// verts[0] is the bottom center, hmax - hmin the height
+	DT_CONVEX_VOLUME_CYLINDER = 1,
+	// verts[0] and verts[1] are min and max
+	DT_CONVEX_VOLUME_BOX = 2,
+};
+
+// Marks the area of the walkable surface inside the volume, applied after erosion
+struct DtConvexVolume
+{
+	float3 verts[DT_MAX_CONVEX_VOLUME_VERTS];
+	int numVerts;
+	float hmin;
+	float hmax;
+	float radius;
+	int type;
+	uint8_t area;
+};
+
+struct DtBuildLogMessage
+{
+	// rcLogCategory
+	int category;
+	const char* message;
+};
+
+// Stage durations in microseconds and the recast log of the last build
+struct DtBuildStats
+{
+	int rasterizeTime;
+	int filterTime;
+	int compactTime;
+	int erodeTime;
+	int markAreasTime;
+	int regionsTime;
+	int contoursTime;
+	int polyMeshTime;
+	int detailMeshTime;
+	int detourTime;
+	int totalTime;
+	DtBuildLogMessage* logs;
+	int numLogs;
 };
 
 struct DtGeneratedData
@@ -51,6 +124,24 @@ struct DtGeneratedData
 };
 
 
+struct DtTileCacheLayersResult
+{
+	bool success = false;
+	int error = 0;
+	uint8_t** layers = nullptr;
+	int* layerSizes = nullptr;
+	int numLayers = 0;
+};
+
+// Compression callbacks for tile cache layers.  Return 1 on success
+struct DtTileCacheCompressorCallbacks
+{
+	void* context;
+	int (*maxCompressedSize)(void* context, int bufferSize);
+	int (*compress)(void* context, const uint8_t* buffer, int bufferSize, uint8_t* compressed, int maxCompressedSize, int* compressedSize);
+	int (*decompress)(void* context, const uint8_t* compressed, int compressedSize, uint8_t* buffer, int maxBufferSize, int* bufferSize);
+};
+
 struct DtCrowdAgent
 {
 	int index;
@@ -62,12 +153,47 @@ struct DtCrowdAgent
 	float3 velocity;
 };
 
+// Extended agent state.  Array sizes match DT_CROWDAGENT_MAX_CORNERS, DT_CROWDAGENT_MAX_NEIGHBOURS and dtLocalBoundary::MAX_LOCAL_SEGS
+struct DtCrowdAgentInfo
+{
+	DtCrowdAgent agent;
+	float3 desiredVelocity;
+	// Desired velocity adjusted by obstacle avoidance
+	float3 steeringVelocity;
+	uint64_t currentPoly;
+	uint8_t targetState;
+	uint64_t targetPoly;
+	float3 targetPosition;
+	int numCorners;
+	float3 cornerVerts[4];
+	uint8_t cornerFlags[4];
+	uint64_t cornerPolys[4];
+	int numNeighbours;
+	int neighbourIndices[6];
+	float neighbourDistances[6];
+	int numBoundarySegments;
+	// Start and end of each segment
+	float3 boundarySegments[16];
+	int corridorLength;
+};
+
 struct DtCrowdAgentsResult
 {
 	DtCrowdAgent* agents = nullptr;
 	int agentCount = 0;
 };
 
+// Active agents as parallel arrays, each with room for maxAgents entries
+struct DtCrowdAgentSnapshot
+{
+	int* indices = nullptr;
+	float3* positions = nullptr;
+	float3* velocities = nullptr;
+	uint8_t* states = nullptr;
+	int maxAgents = 0;
+	int agentCount = 0;
+};
+
 struct DtAgentParams {
 	float radius;						///< Agent radius. [Limit: >= 0]
 	float height;						///< Agent height. [Limit: > 0]
@@ -85,4 +211,18 @@ struct DtAgentParams {
 	int queryFilterType;
 };
 
+// Mirrors dtObstacleAvoidanceParams
+struct DtObstacleAvoidanceParams {
+	float velBias;
+	float weightDesVel;
+	float weightCurVel;
+	float weightSide;
+	float weightToi;
+	float horizTime;
+	uint8_t gridSize;
+	uint8_t adaptiveDivs;
+	uint8_t adaptiveRings;
+	uint8_t adaptiveDepth;
+};
+
 //#pragma pack(pop)
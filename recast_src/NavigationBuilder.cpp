@@ -21,16 +21,93 @@
 // 3. This notice may not be removed or altered from any source distribution.
 //
 
+#include "DetourNavMesh.h"
 #include "DetourNavMeshBuilder.h"
+#include "DetourCommon.h"
 
 
 #include "Navigation.hpp"
 #include "NavigationBuilder.hpp"
 
 
+BuildContext::BuildContext() : rcContext(true)
+{
+	doResetTimers();
+}
+
+void BuildContext::doResetLog()
+{
+	m_messages.clear();
+	m_categories.clear();
+	m_logs.clear();
+}
+
+void BuildContext::doLog(const rcLogCategory category, const char* msg, const int len)
+{
+	m_messages.push_back(std::string(msg, len));
+	m_categories.push_back(category);
+}
+
+void BuildContext::doResetTimers()
+{
+	for (int i = 0; i < RC_MAX_TIMERS; ++i)
+		m_accTime[i] = -1;
+}
+
+void BuildContext::doStartTimer(const rcTimerLabel label)
+{
+	m_startTime[label] = std::chrono::steady_clock::now();
+}
+
+void BuildContext::doStopTimer(const rcTimerLabel label)
+{
+	auto elapsed = std::chrono::steady_clock::now() - m_startTime[label];
+	int deltaTime = (int)std::chrono::duration_cast<std::chrono::microseconds>(elapsed).count();
+	if (m_accTime[label] == -1)
+		m_accTime[label] = deltaTime;
+	else
+		m_accTime[label] += deltaTime;
+}
+
+int BuildContext::doGetAccumulatedTime(const rcTimerLabel label) const
+{
+	return m_accTime[label];
+}
+
+void BuildContext::GetStats(DtBuildStats* stats)
+{
+	// Stages that didn't run report 0
+	auto time = [this](rcTimerLabel label) { return rcMax(getAccumulatedTime(label), 0); };
+
+	stats->rasterizeTime = time(RC_TIMER_RASTERIZE_TRIANGLES);
+	stats->filterTime = time(RC_TIMER_FILTER_LOW_OBSTACLES) + time(RC_TIMER_FILTER_BORDER) + time(RC_TIMER_FILTER_WALKABLE);
+	stats->compactTime = time(RC_TIMER_BUILD_COMPACTHEIGHTFIELD);
+	stats->erodeTime = time(RC_TIMER_ERODE_AREA);
+	stats->markAreasTime = time(RC_TIMER_MARK_CONVEXPOLY_AREA) + time(RC_TIMER_MARK_BOX_AREA) + time(RC_TIMER_MARK_CYLINDER_AREA);
+	stats->regionsTime = time(RC_TIMER_BUILD_DISTANCEFIELD) + time(RC_TIMER_BUILD_REGIONS) + time(RC_TIMER_BUILD_LAYERS);
+	stats->contoursTime = time(RC_TIMER_BUILD_CONTOURS);
+	stats->polyMeshTime = time(RC_TIMER_BUILD_POLYMESH);
+	stats->detailMeshTime = time(RC_TIMER_BUILD_POLYMESHDETAIL);
+	// Detour tile creation is timed with the user defined timer
+	stats->detourTime = time(RC_TIMER_TEMP);
+	stats->totalTime = time(RC_TIMER_TOTAL);
+
+	m_logs.clear();
+	for (size_t i = 0; i < m_messages.size(); ++i)
+	{
+		DtBuildLogMessage log;
+		log.category = m_categories[i];
+		log.message = m_messages[i].c_str();
+		m_logs.push_back(log);
+	}
+	stats->logs = m_logs.data();
+	stats->numLogs = (int)m_logs.size();
+}
+
+
 NavigationBuilder::NavigationBuilder()
 {
-	m_context = new rcContext(false);
+	m_context = new BuildContext();
 }
 NavigationBuilder::~NavigationBuilder()
 {
@@ -70,37 +147,43 @@ void NavigationBuilder::Cleanup()
 		rcFreePolyMeshDetail(m_dmesh);
 		m_dmesh = nullptr;
 	}
+	if (m_lset)
+	{
+		rcFreeHeightfieldLayerSet(m_lset);
+		m_lset = nullptr;
+	}
+	for (auto layer : m_layers)
+		dtFree(layer);
+	m_layers.clear();
+	m_layerSizes.clear();
 }
-DtGeneratedData* NavigationBuilder::BuildNavmesh(float3* vertices, int numVertices, int* indices, int numIndices, uint8_t* areas)
+bool NavigationBuilder::InitConfig(BuildConfig& cfg)
 {
-	DtGeneratedData* ret = &m_result;
-	ret->success = false;
-
-	float bmin[3];
-	memcpy(bmin, &m_buildSettings.boundingBox.min.x, sizeof(float) * 3);
-	float bmax[3];
-	memcpy(bmax, &m_buildSettings.boundingBox.max.x, sizeof(float) * 3);
+	memcpy(cfg.bmin, &m_buildSettings.boundingBox.min.x, sizeof(float) * 3);
+	memcpy(cfg.bmax, &m_buildSettings.boundingBox.max.x, sizeof(float) * 3);
 
 	float bbSize[3];
-	rcVsub(bbSize, bmax, bmin);
+	rcVsub(bbSize, cfg.bmax, cfg.bmin);
 	if (bbSize[0] <= 0.0f || bbSize[1] <= 0.0f || bbSize[2] <= 0.0f)
-		return ret; // Negative or empty bounding box
+		return false; // Negative or empty bounding box
 
 	// Check input parameters
 	if (m_buildSettings.detailSampleDistInput < 1.0f)
-		return ret;
+		return false;
 	if (m_buildSettings.detailSampleMaxErrorInput <= 0.0f)
-		return ret;
+		return false;
 	if (m_buildSettings.edgeMaxError < 0.1f)
-		return ret;
+		return false;
 	if (m_buildSettings.edgeMaxLen < 0.0f)
-		return ret;
+		return false;
 	if (m_buildSettings.regionMinArea < 0.0f)
-		return ret;
+		return false;
 	if (m_buildSettings.regionMergeArea < 0.0f)
-		return ret;
+		return false;
 	if (m_buildSettings.tileSize <= 0)
-		return ret;
+		return false;
+	if (m_buildSettings.maxVertsPerPoly < 3 || m_buildSettings.maxVertsPerPoly > DT_VERTS_PER_POLYGON)
+		return false;
 
 	// Limit cell size to not freeze the process with calculating a huge amount of cells
 	if (m_buildSettings.cellSize < 0.01f)
@@ -108,75 +191,72 @@ DtGeneratedData* NavigationBuilder::BuildNavmesh(float3* vertices, int numVertic
 	if (m_buildSettings.cellHeight < 0.01f)
 		m_buildSettings.cellHeight = 0.01f;
 
-	int maxEdgeLen = (int)(m_buildSettings.edgeMaxLen / m_buildSettings.cellSize);
-	float maxSimplificationError = m_buildSettings.edgeMaxError;
-	int maxVertsPerPoly = 6;
-	float detailSampleDist = m_buildSettings.cellSize * m_buildSettings.detailSampleDistInput;
-	float detailSampleMaxError = m_buildSettings.cellHeight * m_buildSettings.detailSampleMaxErrorInput;
-
-	int walkableHeight = (int)ceilf(m_buildSettings.agentHeight / m_buildSettings.cellHeight);
-	int walkableClimb = (int)floorf(m_buildSettings.agentMaxClimb / m_buildSettings.cellHeight);
-	int walkableRadius = (int)ceilf(m_buildSettings.agentRadius / m_buildSettings.cellSize);
+	cfg.walkableHeight = (int)ceilf(m_buildSettings.agentHeight / m_buildSettings.cellHeight);
+	cfg.walkableClimb = (int)floorf(m_buildSettings.agentMaxClimb / m_buildSettings.cellHeight);
+	cfg.walkableRadius = (int)ceilf(m_buildSettings.agentRadius / m_buildSettings.cellSize);
 
 	// Size of the tile border
-	int borderSize = walkableRadius + 3;
+	cfg.borderSize = cfg.walkableRadius + 3;
 	int tileSize = m_buildSettings.tileSize;
 
 	// Expand bounding box by border size so that all required geometry is included
-	bmin[0] -= borderSize * m_buildSettings.cellSize;
-	bmin[2] -= borderSize * m_buildSettings.cellSize;
-	bmax[0] += borderSize * m_buildSettings.cellSize;
-	bmax[2] += borderSize * m_buildSettings.cellSize;
-
-	int width = tileSize + borderSize * 2;
-	int height = tileSize + borderSize * 2;
-
-
-	// Make sure state is clean
-	Cleanup();
+	cfg.bmin[0] -= cfg.borderSize * m_buildSettings.cellSize;
+	cfg.bmin[2] -= cfg.borderSize * m_buildSettings.cellSize;
+	cfg.bmax[0] += cfg.borderSize * m_buildSettings.cellSize;
+	cfg.bmax[2] += cfg.borderSize * m_buildSettings.cellSize;
+
+	cfg.width = tileSize + cfg.borderSize * 2;
+	cfg.height = tileSize + cfg.borderSize * 2;
+	return true;
+}
 
+int NavigationBuilder::BuildCompactHeightfield(const BuildConfig& cfg, float3* vertices, int numVertices, int* indices, int numIndices, uint8_t* areas)
+{
 	if (numIndices == 0 || numVertices == 0)
-		return ret;
+		return -1;
 
-	if (walkableClimb < 0)
-		return ret;
+	if (cfg.walkableClimb < 0)
+		return -1;
 
 	m_solid = rcAllocHeightfield();
-	if (!rcCreateHeightfield(m_context, *m_solid, width, height, bmin, bmax, m_buildSettings.cellSize, m_buildSettings.cellHeight))
+	if (!rcCreateHeightfield(m_context, *m_solid, cfg.width, cfg.height, cfg.bmin, cfg.bmax, m_buildSettings.cellSize, m_buildSettings.cellHeight))
 	{
-		return ret;
+		return -1;
 	}
 
 	int numTriangles = numIndices / 3;
 	m_triareas = new uint8_t[numTriangles];
 	if (!m_triareas)
 	{
-		return ret;
+		return -1;
 	}
 
 	// Find walkable triangles and rasterize into heightfield
 	memset(m_triareas, 0, numTriangles * sizeof(unsigned char));
 	rcMarkWalkableTriangles(m_context, m_buildSettings.agentMaxSlope, (float*)vertices, numVertices, indices, numTriangles, m_triareas);
 
-	// set areas to RC_NULL_AREA here that we want to be unwalkable.  Although it's probably easier to just exclude the geometry.
+	// Walkable triangles take the area given as input, so it ends up on the navmesh polygons.
+	// Set areas to RC_NULL_AREA that we want to be unwalkable.  Although it's probably easier to just exclude the geometry.
 	// But it's supported here just in case.
 	for (int i = 0; i < numTriangles; i++)
 	{
-		if (areas[i] == RC_NULL_AREA) {
-			m_triareas[i] = RC_NULL_AREA;
+		if (m_triareas[i] != RC_NULL_AREA) {
+			m_triareas[i] = areas[i];
 		}
 	}
 
-	if (!rcRasterizeTriangles(m_context, (float*)vertices, numVertices, indices, m_triareas, numTriangles, *m_solid, walkableClimb))
+	if (!rcRasterizeTriangles(m_context, (float*)vertices, numVertices, indices, m_triareas, numTriangles, *m_solid, cfg.walkableClimb))
 	{
-		ret->error = 10;
-		return ret;
+		return 10;
 	}
 
 	// Filter walkables surfaces.
-	rcFilterLowHangingWalkableObstacles(m_context, walkableClimb, *m_solid);
-	rcFilterLedgeSpans(m_context, walkableHeight, walkableClimb, *m_solid);
-	rcFilterWalkableLowHeightSpans(m_context, walkableHeight, *m_solid);
+	if (m_buildSettings.filterLowHangingObstacles)
+		rcFilterLowHangingWalkableObstacles(m_context, cfg.walkableClimb, *m_solid);
+	if (m_buildSettings.filterLedgeSpans)
+		rcFilterLedgeSpans(m_context, cfg.walkableHeight, cfg.walkableClimb, *m_solid);
+	if (m_buildSettings.filterWalkableLowHeightSpans)
+		rcFilterWalkableLowHeightSpans(m_context, cfg.walkableHeight, *m_solid);
 
 	// Compact the heightfield so that it is faster to handle from now on.
 	// This will result more cache coherent data as well as the neighbours
@@ -184,13 +264,11 @@ DtGeneratedData* NavigationBuilder::BuildNavmesh(float3* vertices, int numVertic
 	m_chf = rcAllocCompactHeightfield();
 	if (!m_chf)
 	{
-		ret->error = 20;
-		return ret;
+		return 20;
 	}
-	if (!rcBuildCompactHeightfield(m_context, walkableHeight, walkableClimb, *m_solid, *m_chf))
+	if (!rcBuildCompactHeightfield(m_context, cfg.walkableHeight, cfg.walkableClimb, *m_solid, *m_chf))
 	{
-		ret->error = 30;
-		return ret;
+		return 30;
 	}
 
 	// No longer need solid heightfield after compacting it
@@ -198,23 +276,97 @@ DtGeneratedData* NavigationBuilder::BuildNavmesh(float3* vertices, int numVertic
 	m_solid = 0;
 
 	// Erode the walkable area by agent radius.
-	if (!rcErodeWalkableArea(m_context, walkableRadius, *m_chf))
+	if (!rcErodeWalkableArea(m_context, cfg.walkableRadius, *m_chf))
+	{
+		return 40;
+	}
+
+	// Mark areas from volumes
+	for (const DtConvexVolume& volume : m_convexVolumes)
+	{
+		switch (volume.type)
+		{
+		case DT_CONVEX_VOLUME_CYLINDER:
+			rcMarkCylinderArea(m_context, &volume.verts[0].x, volume.radius, volume.hmax - volume.hmin, volume.area, *m_chf);
+			break;
+		case DT_CONVEX_VOLUME_BOX:
+			rcMarkBoxArea(m_context, &volume.verts[0].x, &volume.verts[1].x, volume.area, *m_chf);
+			break;
+		default:
+			rcMarkConvexPolyArea(m_context, &volume.verts[0].x, volume.numVerts, volume.hmin, volume.hmax, volume.area, *m_chf);
+			break;
+		}
+	}
+	return 0;
+}
+
+DtGeneratedData* NavigationBuilder::BuildNavmesh(float3* vertices, int numVertices, int* indices, int numIndices, uint8_t* areas)
+{
+	DtGeneratedData* ret = &m_result;
+	ret->success = false;
+	ret->error = 0;
+
+	m_context->resetLog();
+	m_context->resetTimers();
+	rcScopedTimer totalTimer(m_context, RC_TIMER_TOTAL);
+
+	BuildConfig cfg;
+	if (!InitConfig(cfg))
 	{
-		ret->error = 40;
+		m_context->log(RC_LOG_ERROR, "Invalid build settings or empty tile bounds");
+		ret->error = 1;
 		return ret;
 	}
 
-	// Prepare for region partitioning, by calculating distance field along the walkable surface.
-	if (!rcBuildDistanceField(m_context, *m_chf))
+	int maxEdgeLen = (int)(m_buildSettings.edgeMaxLen / m_buildSettings.cellSize);
+	float maxSimplificationError = m_buildSettings.edgeMaxError;
+	int maxVertsPerPoly = m_buildSettings.maxVertsPerPoly;
+	float detailSampleDist = m_buildSettings.cellSize * m_buildSettings.detailSampleDistInput;
+	float detailSampleMaxError = m_buildSettings.cellHeight * m_buildSettings.detailSampleMaxErrorInput;
+
+	// Make sure state is clean
+	Cleanup();
+
+	int error = BuildCompactHeightfield(cfg, vertices, numVertices, indices, numIndices, areas);
+	if (error != 0)
 	{
-		ret->error = 50;
+		if (error > 0)
+			ret->error = error;
 		return ret;
 	}
+
 	// Partition the walkable surface into simple regions without holes.
-	if (!rcBuildRegions(m_context, *m_chf, borderSize, m_buildSettings.regionMinArea, m_buildSettings.regionMergeArea))
+	switch (m_buildSettings.partitionType)
 	{
-		ret->error = 60;
-		return ret;
+	case DT_PARTITION_MONOTONE:
+		// Fastest, no distance field needed but can give long thin polygons
+		if (!rcBuildRegionsMonotone(m_context, *m_chf, cfg.borderSize, m_buildSettings.regionMinArea, m_buildSettings.regionMergeArea))
+		{
+			ret->error = 60;
+			return ret;
+		}
+		break;
+	case DT_PARTITION_LAYERS:
+		// Keeps overlapping floors apart, merge area is not used
+		if (!rcBuildLayerRegions(m_context, *m_chf, cfg.borderSize, m_buildSettings.regionMinArea))
+		{
+			ret->error = 60;
+			return ret;
+		}
+		break;
+	default:
+		// Prepare for region partitioning, by calculating distance field along the walkable surface.
+		if (!rcBuildDistanceField(m_context, *m_chf))
+		{
+			ret->error = 50;
+			return ret;
+		}
+		if (!rcBuildRegions(m_context, *m_chf, cfg.borderSize, m_buildSettings.regionMinArea, m_buildSettings.regionMergeArea))
+		{
+			ret->error = 60;
+			return ret;
+		}
+		break;
 	}
 
 	// Create contours.
@@ -278,22 +430,16 @@ DtGeneratedData* NavigationBuilder::BuildNavmesh(float3* vertices, int numVertic
 	// Update poly flags from areas.
 	for (int i = 0; i < m_pmesh->npolys; ++i)
 	{
-		if (m_pmesh->areas[i] == RC_WALKABLE_AREA)
-			m_pmesh->areas[i] = 0;
-
-		if (m_pmesh->areas[i] == 0)
-		{
-			if (areas[i] != RC_NULL_AREA) {
-				m_pmesh->areas[i] = areas[i];
-			}
-			m_pmesh->flags[i] = 1;
-		}
+		if (m_pmesh->areas[i] != RC_NULL_AREA)
+			m_pmesh->flags[i] = m_buildSettings.areaFlags[m_pmesh->areas[i] & 0x3f];
 	}
 
 
 	// Generate native navmesh format and store the data pointers in the return structure
 
+	m_context->startTimer(RC_TIMER_TEMP);
 	int navCreateRes = CreateDetourMesh();
+	m_context->stopTimer(RC_TIMER_TEMP);
 	if (navCreateRes > 0) {
 		ret->error = 1000 + navCreateRes;
 		return ret;
@@ -306,15 +452,118 @@ DtGeneratedData* NavigationBuilder::BuildNavmesh(float3* vertices, int numVertic
 	return ret;
 }
 
+DtTileCacheLayersResult* NavigationBuilder::BuildTileCacheLayers(float3* vertices, int numVertices, int* indices, int numIndices, uint8_t* areas, dtTileCacheCompressor* compressor)
+{
+	DtTileCacheLayersResult* ret = &m_layersResult;
+	ret->success = false;
+	ret->error = 0;
+	ret->numLayers = 0;
+
+	m_context->resetLog();
+	m_context->resetTimers();
+	rcScopedTimer totalTimer(m_context, RC_TIMER_TOTAL);
+
+	BuildConfig cfg;
+	if (!InitConfig(cfg))
+	{
+		m_context->log(RC_LOG_ERROR, "Invalid build settings or empty tile bounds");
+		ret->error = 1;
+		return ret;
+	}
+
+	// Make sure state is clean
+	Cleanup();
+
+	int error = BuildCompactHeightfield(cfg, vertices, numVertices, indices, numIndices, areas);
+	if (error != 0)
+	{
+		if (error > 0)
+			ret->error = error;
+		return ret;
+	}
+
+	m_lset = rcAllocHeightfieldLayerSet();
+	if (!m_lset)
+	{
+		ret->error = 200;
+		return ret;
+	}
+	if (!rcBuildHeightfieldLayers(m_context, *m_chf, cfg.borderSize, cfg.walkableHeight, *m_lset))
+	{
+		ret->error = 210;
+		return ret;
+	}
+
+	// Each layer is stored compressed, the tile cache builds navmesh tiles from them on demand
+	for (int i = 0; i < m_lset->nlayers; ++i)
+	{
+		const rcHeightfieldLayer* layer = &m_lset->layers[i];
+
+		dtTileCacheLayerHeader header;
+		header.magic = DT_TILECACHE_MAGIC;
+		header.version = DT_TILECACHE_VERSION;
+		header.tx = m_buildSettings.tilePosition.x;
+		header.ty = m_buildSettings.tilePosition.y;
+		header.tlayer = i;
+		rcVcopy(header.bmin, layer->bmin);
+		rcVcopy(header.bmax, layer->bmax);
+		header.width = (unsigned char)layer->width;
+		header.height = (unsigned char)layer->height;
+		header.minx = (unsigned char)layer->minx;
+		header.maxx = (unsigned char)layer->maxx;
+		header.miny = (unsigned char)layer->miny;
+		header.maxy = (unsigned char)layer->maxy;
+		header.hmin = (unsigned short)layer->hmin;
+		header.hmax = (unsigned short)layer->hmax;
+
+		uint8_t* data = nullptr;
+		int dataSize = 0;
+		dtStatus status = dtBuildTileCacheLayer(compressor, &header, layer->heights, layer->areas, layer->cons, &data, &dataSize);
+		if (dtStatusFailed(status))
+		{
+			ret->error = 220;
+			return ret;
+		}
+		m_layers.push_back(data);
+		m_layerSizes.push_back(dataSize);
+	}
+
+	ret->numLayers = (int)m_layers.size();
+	ret->layers = m_layers.data();
+	ret->layerSizes = m_layerSizes.data();
+	ret->success = true;
+	return ret;
+}
+
 void NavigationBuilder::SetSettings(DtBuildSettings buildSettings)
 {
 	// Copy this to have access to original settings
 	m_buildSettings = buildSettings;
 }
 
+void NavigationBuilder::SetOffMeshConnections(DtOffMeshConnection* connections, int numConnections)
+{
+	m_offMeshConnections.clear();
+	if (connections && numConnections > 0)
+		m_offMeshConnections.assign(connections, connections + numConnections);
+}
+
+void NavigationBuilder::SetConvexVolumes(DtConvexVolume* volumes, int numVolumes)
+{
+	m_convexVolumes.clear();
+	if (volumes && numVolumes > 0)
+		m_convexVolumes.assign(volumes, volumes + numVolumes);
+}
+
+DtBuildStats* NavigationBuilder::GetBuildStats()
+{
+	m_context->GetStats(&m_stats);
+	return &m_stats;
+}
+
 int NavigationBuilder::CreateDetourMesh()
 {
-	if (m_pmesh->nvp > 6)
+	if (m_pmesh->nvp > DT_VERTS_PER_POLYGON)
 		return 10;
 	if (m_pmesh->nverts >= 0xffff)
 		return 11;
@@ -340,14 +589,33 @@ int NavigationBuilder::CreateDetourMesh()
 	params.detailVertsCount = m_dmesh->nverts;
 	params.detailTris = m_dmesh->tris;
 	params.detailTriCount = m_dmesh->ntris;
-	// TODO: Support off-mesh connections
-	params.offMeshConVerts = nullptr;
-	params.offMeshConRad = nullptr;
-	params.offMeshConDir = nullptr;
-	params.offMeshConAreas = nullptr;
-	params.offMeshConFlags = nullptr;
-	params.offMeshConUserID = nullptr;
-	params.offMeshConCount = 0;
+
+	// Off-mesh connections are passed to detour as separate arrays
+	int offMeshConCount = (int)m_offMeshConnections.size();
+	std::vector<float> offMeshConVerts(offMeshConCount * 6);
+	std::vector<float> offMeshConRad(offMeshConCount);
+	std::vector<uint8_t> offMeshConDir(offMeshConCount);
+	std::vector<uint8_t> offMeshConAreas(offMeshConCount);
+	std::vector<uint16_t> offMeshConFlags(offMeshConCount);
+	std::vector<uint32_t> offMeshConUserID(offMeshConCount);
+	for (int i = 0; i < offMeshConCount; i++)
+	{
+		const DtOffMeshConnection& con = m_offMeshConnections[i];
+		memcpy(&offMeshConVerts[i * 6], &con.start.x, sizeof(float) * 3);
+		memcpy(&offMeshConVerts[i * 6 + 3], &con.end.x, sizeof(float) * 3);
+		offMeshConRad[i] = con.radius;
+		offMeshConDir[i] = con.bidirectional ? DT_OFFMESH_CON_BIDIR : 0;
+		offMeshConAreas[i] = con.area;
+		offMeshConFlags[i] = con.flags;
+		offMeshConUserID[i] = con.userId;
+	}
+	params.offMeshConVerts = offMeshConCount > 0 ? offMeshConVerts.data() : nullptr;
+	params.offMeshConRad = offMeshConCount > 0 ? offMeshConRad.data() : nullptr;
+	params.offMeshConDir = offMeshConCount > 0 ? offMeshConDir.data() : nullptr;
+	params.offMeshConAreas = offMeshConCount > 0 ? offMeshConAreas.data() : nullptr;
+	params.offMeshConFlags = offMeshConCount > 0 ? offMeshConFlags.data() : nullptr;
+	params.offMeshConUserID = offMeshConCount > 0 ? offMeshConUserID.data() : nullptr;
+	params.offMeshConCount = offMeshConCount;
 	params.walkableHeight = m_buildSettings.agentHeight;
 	params.walkableClimb = m_buildSettings.agentMaxClimb;
 	params.walkableRadius = m_buildSettings.agentRadius;
@@ -1,10 +1,47 @@
 #pragma once
 
 #include "Recast.h"
+#include "DetourTileCacheBuilder.h"
 #include "Navigation.hpp"
 #include <math.h>
+#include <chrono>
 #include <cstdint>
 #include <cstring>
+#include <string>
+#include <vector>
+
+// Values derived from the build settings, shared by navmesh and tile cache layer builds
+struct BuildConfig
+{
+	float bmin[3];
+	float bmax[3];
+	int walkableHeight;
+	int walkableClimb;
+	int walkableRadius;
+	int borderSize;
+	int width;
+	int height;
+};
+
+// Recast context with timers and log capture, so build stats can be handed back with the result
+class BuildContext : public rcContext
+{
+	std::chrono::steady_clock::time_point m_startTime[RC_MAX_TIMERS];
+	int m_accTime[RC_MAX_TIMERS];
+	std::vector<std::string> m_messages;
+	std::vector<rcLogCategory> m_categories;
+	std::vector<DtBuildLogMessage> m_logs;
+public:
+	BuildContext();
+	void GetStats(DtBuildStats* stats);
+protected:
+	void doResetLog() override;
+	void doLog(const rcLogCategory category, const char* msg, const int len) override;
+	void doResetTimers() override;
+	void doStartTimer(const rcTimerLabel label) override;
+	void doStopTimer(const rcTimerLabel label) override;
+	int doGetAccumulatedTime(const rcTimerLabel label) const override;
+};
 
 class NavigationBuilder
 {
@@ -14,8 +51,12 @@ class NavigationBuilder
 	rcContourSet* m_cset = nullptr;
 	rcPolyMesh* m_pmesh = nullptr;
 	rcPolyMeshDetail* m_dmesh = nullptr;
+	rcHeightfieldLayerSet* m_lset = nullptr;
 	DtBuildSettings m_buildSettings;
-	rcContext* m_context;
+	std::vector<DtOffMeshConnection> m_offMeshConnections;
+	std::vector<DtConvexVolume> m_convexVolumes;
+	BuildContext* m_context;
+	DtBuildStats m_stats;
 
 	// Detour returned navigation mesh data
 	// free with dtFree()
@@ -23,13 +64,26 @@ class NavigationBuilder
 	int m_navmeshDataLength = 0;
 
 	DtGeneratedData m_result;
+
+	// Compressed tile cache layers, free with dtFree()
+	std::vector<uint8_t*> m_layers;
+	std::vector<int> m_layerSizes;
+	DtTileCacheLayersResult m_layersResult;
 public:
 	NavigationBuilder();
 	~NavigationBuilder();
 	void Cleanup();
 	DtGeneratedData* BuildNavmesh(float3* vertices, int numVertices, int* indices, int numIndices, uint8_t* areas);
+	DtTileCacheLayersResult* BuildTileCacheLayers(float3* vertices, int numVertices, int* indices, int numIndices, uint8_t* areas, dtTileCacheCompressor* compressor);
 	void SetSettings(DtBuildSettings buildSettings);
+	void SetOffMeshConnections(DtOffMeshConnection* connections, int numConnections);
+	void SetConvexVolumes(DtConvexVolume* volumes, int numVolumes);
+	// Stats of the last build, valid until the next build
+	DtBuildStats* GetBuildStats();
 
 private:
+	bool InitConfig(BuildConfig& cfg);
+	// Rasterize, filter and erode the input into m_chf.  Returns 0 on success, an error code, or -1 for failures without a code
+	int BuildCompactHeightfield(const BuildConfig& cfg, float3* vertices, int numVertices, int* indices, int numIndices, uint8_t* areas);
 	int CreateDetourMesh();
 };
\ No newline at end of file
@@ -10,6 +10,19 @@ static float frand()
 }
 
 
+void ToDetourFilter(const DtQueryFilter* input, dtQueryFilter* filter)
+{
+	if (!input)
+		return;
+
+	for (int i = 0; i < DT_MAX_AREAS; i++)
+	{
+		filter->setAreaCost(i, input->areaCost[i]);
+	}
+	filter->setIncludeFlags(input->includeFlags);
+	filter->setExcludeFlags(input->excludeFlags);
+}
+
 NavigationMesh::NavigationMesh()
 {
 }
@@ -44,14 +57,6 @@ NavigationMesh::~NavigationMesh()
 
 int NavigationMesh::Init(float cellTileSize, int tileBits, int polyBits)
 {
-	// Allocate objects
-	m_navMesh = dtAllocNavMesh();
-	m_navQuery = dtAllocNavMeshQuery();
-
-	if (!m_navMesh || !m_navQuery)
-		return 0;
-
-	
 	dtNavMeshParams params = { 0 };
 	params.orig[0] = 0.0f;
 	params.orig[1] = 0.0f;
@@ -65,7 +70,19 @@ int NavigationMesh::Init(float cellTileSize, int tileBits, int polyBits)
 	params.maxTiles = 1 << tileBits;
 	params.maxPolys = 1 << polyBits;
 
-	dtStatus status = m_navMesh->init(&params);
+	return Init(&params);
+}
+
+int NavigationMesh::Init(const dtNavMeshParams* params)
+{
+	// Allocate objects
+	m_navMesh = dtAllocNavMesh();
+	m_navQuery = dtAllocNavMeshQuery();
+
+	if (!m_navMesh || !m_navQuery)
+		return 0;
+
+	dtStatus status = m_navMesh->init(params);
 	if (dtStatusFailed(status))
 		return 0;
 
@@ -76,29 +93,55 @@ int NavigationMesh::Init(float cellTileSize, int tileBits, int polyBits)
 	return 1;
 }
 
-int NavigationMesh::LoadTile(uint8_t* navData, int navDataLength)
+void NavigationMesh::GetParams(dtNavMeshParams* params)
+{
+	memcpy(params, m_navMesh->getParams(), sizeof(dtNavMeshParams));
+}
+
+dtStatus NavigationMesh::LoadTile(uint8_t* navData, int navDataLength, dtTileRef lastRef)
 {
 	if (!m_navMesh || !m_navQuery)
-		return 0;
-	if (!navData)
-		return 0;
+		return DT_FAILURE;
+	if (!navData || navDataLength <= 0)
+		return DT_FAILURE | DT_INVALID_PARAM;
 
 	// Copy data
 	uint8_t* dataCopy = new uint8_t[navDataLength];
 	memcpy(dataCopy, navData, navDataLength);
 
 	dtTileRef tileRef = 0;
-	if (dtStatusSucceed(m_navMesh->addTile(dataCopy, navDataLength, 0, 0, &tileRef)))
+	dtStatus status = m_navMesh->addTile(dataCopy, navDataLength, 0, lastRef, &tileRef);
+	if (dtStatusSucceed(status))
 	{
 		m_tileRefs.insert(tileRef);
-		return 1;
+		return status;
 	}
 
 	delete[] dataCopy;
-	return 0;
+	return status;
+}
+
+int NavigationMesh::GetTileData(int2 tileCoordinate, const uint8_t** navData, int* navDataLength, dtTileRef* tileRef)
+{
+	const dtMeshTile* tile = m_navMesh->getTileAt(tileCoordinate.x, tileCoordinate.y, 0);
+	if (!tile || !tile->header || !tile->dataSize)
+		return 0;
+
+	*navData = tile->data;
+	*navDataLength = tile->dataSize;
+	*tileRef = m_navMesh->getTileRef(tile);
+	return 1;
+}
+
+dtPolyRef NavigationMesh::GetTilePolyRefBase(int2 tileCoordinate)
+{
+	const dtMeshTile* tile = m_navMesh->getTileAt(tileCoordinate.x, tileCoordinate.y, 0);
+	if (!tile)
+		return 0;
+	return m_navMesh->getPolyRefBase(tile);
 }
 
-int NavigationMesh::RemoveTile(int2 tileCoordinate)
+dtStatus NavigationMesh::RemoveTile(int2 tileCoordinate)
 {
 	dtTileRef tileRef = m_navMesh->getTileRefAt(tileCoordinate.x, tileCoordinate.y, 0);
 
@@ -110,10 +153,9 @@ int NavigationMesh::RemoveTile(int2 tileCoordinate)
 		if (deletedData)
 			delete[] deletedData;
 		m_tileRefs.erase(tileRef);
-		return 1;
 	}
 
-	return 0;
+	return status;
 }
 
 int NavigationMesh::GetRandomPosition(float3* result)
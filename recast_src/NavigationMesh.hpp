@@ -16,13 +16,29 @@ struct NavMeshPathfindQuery
 	float3 target;
 	float3 findNearestPolyExtent;
 	int maxPathPoints;
+	int straightPathOptions;
 };
 struct NavMeshPathfindResult
 {
 	bool pathFound = false;
 	float3* pathPoints = nullptr;
+	uint8_t* pathFlags = nullptr;
+	dtPolyRef* pathPolys = nullptr;
 	int numPathPoints = 0;
 };
+struct NavMeshPolyPathResult
+{
+	bool pathFound = false;
+	dtPolyRef* polys = nullptr;
+	int numPolys = 0;
+};
+
+struct DtQueryFilter
+{
+	float areaCost[DT_MAX_AREAS];
+	uint16_t includeFlags;
+	uint16_t excludeFlags;
+};
 
 struct NavMeshRaycastQuery
 {
@@ -33,13 +49,31 @@ struct NavMeshRaycastQuery
 };
 struct NavMeshRaycastResult
 {
+	// False if no start poly was found or the raycast failed
+	bool found = false;
+	// True if a wall was hit before reaching the end of the segment
 	bool hit = false;
+	// Fraction along the segment where the wall was hit, 1 if no hit
+	float t = 1.0f;
 	float3 position;
 	float3 normal;
+	// Buffer of maxPathPoints poly refs to receive the visited polygons
+	dtPolyRef* polys = nullptr;
+	int numPolys = 0;
+	float pathCost = 0.0f;
 };
 
 //#pragma pack(pop)
 
+// Detail bit set when no polygon was found within the search extent.  Above the detail bits detour uses
+static const dtStatus AINAV_NO_NEAREST_POLY = 1 << 16;
+
+// Returns a random float in [0, 1).  Context is passed back unchanged
+typedef float (*RandomFloatFn)(void* context);
+
+// Copy our filter settings into a detour filter.  A null input leaves the detour defaults
+void ToDetourFilter(const DtQueryFilter* input, dtQueryFilter* filter);
+
 class NavigationMesh
 {
 private:
@@ -51,8 +85,15 @@ public:
 	NavigationMesh();
 	~NavigationMesh();
 	int Init(float cellTileSize, int tileBits, int polyBits);
-	int LoadTile(uint8_t* navData, int navDataLength);
-	int RemoveTile(int2 tileCoordinate);
+	int Init(const dtNavMeshParams* params);
+	void GetParams(dtNavMeshParams* params);
+	// lastRef restores a previously saved tile ref, 0 to allocate a new one
+	dtStatus LoadTile(uint8_t* navData, int navDataLength, dtTileRef lastRef = 0);
+	// Data is owned by the navmesh and only valid until the tile is removed
+	int GetTileData(int2 tileCoordinate, const uint8_t** navData, int* navDataLength, dtTileRef* tileRef);
+	dtStatus RemoveTile(int2 tileCoordinate);
+	// 0 if there is no tile at the coordinate
+	dtPolyRef GetTilePolyRefBase(int2 tileCoordinate);
 	void FindPath(NavMeshPathfindQuery query, NavMeshPathfindResult* result);
 	void Raycast(NavMeshRaycastQuery query, NavMeshRaycastResult* result);
 	int SamplePosition(float3 point, float3 extent, float3* result);
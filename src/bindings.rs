@@ -1,12 +1,23 @@
 use crate::building::*;
 use crate::common::*;
+use crate::common::nav_error::DtStatus;
 use crate::crowd::DtAgentParams;
 use crate::crowd::DtCrowdAgent;
+use crate::crowd::DtCrowdAgentInfo;
+use crate::crowd::DtCrowdAgentSnapshot;
 use crate::crowd::DtCrowdAgentsResult;
+use crate::crowd::ObstacleAvoidanceParams;
 use crate::queries::DtPathFindQuery;
 use crate::queries::DtPathFindResult;
+use crate::queries::DtPolyPathResult;
 use crate::queries::DtRaycastQuery;
 use crate::queries::DtRaycastResult;
+use crate::queries::NavQueryFilter;
+use crate::tile_cache::DtTileCacheCompressorCallbacks;
+use crate::tile_cache::DtTileCacheLayersResult;
+use crate::tile_cache::DtTileCacheParams;
+use crate::tile_cache::ObstacleRef;
+use std::ffi::c_void;
 
 /// Opaque types - https://anssi-fr.github.io/rust-guide/07_ffi.html
 #[repr(C)]
@@ -21,29 +32,73 @@ pub struct RawNavqueryPtr {_private: [u8; 0]}
 #[repr(C)]
 pub struct RawCrowdPtr {_private: [u8; 0]}
 
+#[repr(C)]
+pub struct RawTileCachePtr {_private: [u8; 0]}
+
+/// Returns a random float in [0, 1).  Context is passed back unchanged
+pub type RandomFloatFn = extern "C" fn(context: *mut c_void) -> f32;
+
 #[link(name = "AiNav")]
 extern "C" {
     pub fn CreateBuilder() -> *mut RawBuilderPtr;
     pub fn DestroyBuilder(builder: *mut RawBuilderPtr);
 
     pub fn SetSettings(builder: *mut RawBuilderPtr, buildSettings: *mut DtBuildSettings);
+    pub fn SetOffMeshConnections(builder: *mut RawBuilderPtr, connections: *const DtOffMeshConnection, numConnections: i32);
+    pub fn SetConvexVolumes(builder: *mut RawBuilderPtr, volumes: *const DtConvexVolume, numVolumes: i32);
     pub fn BuildNavmesh(builder: *mut RawBuilderPtr, vertices: *mut DtVector, numVertices: i32, indices: *mut i32, numIndices: i32, areas: *mut u8) -> *const DtGeneratedData;
+    /// Stats of the last build, valid until the next build or the builder is destroyed
+    pub fn GetBuildStats(builder: *mut RawBuilderPtr) -> *const DtBuildStats;
+    /// layers are owned by the builder, valid until the next build or the builder is destroyed
+    pub fn BuildTileCacheLayers(builder: *mut RawBuilderPtr, vertices: *mut DtVector, numVertices: i32, indices: *mut i32, numIndices: i32, areas: *mut u8, compressor: *mut DtTileCacheCompressorCallbacks) -> *const DtTileCacheLayersResult;
     
     pub fn CreateNavmesh(cellTileSize: f32, tileBits: i32, polyBits: i32) -> *mut RawNavmeshPtr;
+    pub fn CreateNavmeshWithParams(params: *const DtNavMeshParams) -> *mut RawNavmeshPtr;
     pub fn DestroyNavmesh(navmesh: *mut RawNavmeshPtr);
+    pub fn GetNavmeshParams(navmesh: *mut RawNavmeshPtr, params: *mut DtNavMeshParams);
+
+    pub fn AddTile(navmesh: *mut RawNavmeshPtr, data: *mut u8, dataLength: i32) -> DtStatus;
+    /// tile_ref restores a saved tile ref, 0 allocates a new one
+    pub fn AddTileWithRef(navmesh: *mut RawNavmeshPtr, data: *mut u8, dataLength: i32, tileRef: u64) -> DtStatus;
+    /// returns 1 if the tile exists.  data is owned by the navmesh
+    pub fn GetTileData(navmesh: *mut RawNavmeshPtr, tileCoordinate: *const DtInt2, data: *mut *const u8, dataLength: *mut i32, tileRef: *mut u64) -> i32;
+    pub fn RemoveTile(navmesh: *mut RawNavmeshPtr, tileCoordinate: *const DtInt2) -> DtStatus;
+    /// returns 0 if there is no tile at the coordinate
+    pub fn GetTilePolyRefBase(navmesh: *mut RawNavmeshPtr, tileCoordinate: *const DtInt2) -> u64;
 
-    pub fn AddTile(navmesh: *mut RawNavmeshPtr, data: *mut u8, dataLength: i32) -> i32;
-    pub fn RemoveTile(navmesh: *mut RawNavmeshPtr, tileCoordinate: *const DtInt2) -> i32;
+    /// compressor.context has to outlive the tile cache.  area_flags is DtArea::MAX_AREAS long
+    pub fn TileCacheCreate(navmesh: *mut RawNavmeshPtr, params: *const DtTileCacheParams, compressor: *const DtTileCacheCompressorCallbacks, areaFlags: *const u16) -> *mut RawTileCachePtr;
+    pub fn TileCacheDestroy(tile_cache: *mut RawTileCachePtr);
+    /// data is copied. returns 1 on success
+    pub fn TileCacheAddTileLayer(tile_cache: *mut RawTileCachePtr, data: *mut u8, dataLength: i32) -> DtStatus;
+    /// returns the number of layers removed
+    pub fn TileCacheRemoveTileLayers(tile_cache: *mut RawTileCachePtr, tileCoordinate: *const DtInt2) -> i32;
+    /// builds the navmesh tile from the layers at the coordinate. returns 1 on success
+    pub fn TileCacheBuildNavMeshTile(tile_cache: *mut RawTileCachePtr, tileCoordinate: *const DtInt2) -> DtStatus;
+    /// Obstacle functions return 1 on success.  They fail when the obstacle request queue is full
+    pub fn TileCacheAddObstacle(tile_cache: *mut RawTileCachePtr, position: *const DtVector, radius: f32, height: f32, result: *mut ObstacleRef) -> DtStatus;
+    pub fn TileCacheAddBoxObstacle(tile_cache: *mut RawTileCachePtr, min: *const DtVector, max: *const DtVector, result: *mut ObstacleRef) -> DtStatus;
+    pub fn TileCacheAddOrientedBoxObstacle(tile_cache: *mut RawTileCachePtr, center: *const DtVector, halfExtents: *const DtVector, yRadians: f32, result: *mut ObstacleRef) -> DtStatus;
+    pub fn TileCacheRemoveObstacle(tile_cache: *mut RawTileCachePtr, obstacle: ObstacleRef) -> DtStatus;
+    /// returns 1 on success.  up_to_date is 1 once all obstacle requests are processed and affected tiles rebuilt
+    pub fn TileCacheUpdate(tile_cache: *mut RawTileCachePtr, dt: f32, upToDate: *mut i32) -> DtStatus;
 
     pub fn QueryCreate(navmesh: *mut RawNavmeshPtr, maxNodes: i32) -> *mut RawNavqueryPtr;
     pub fn QueryDestroy(query: *mut RawNavqueryPtr);
     pub fn QueryInvalidate(query: *mut RawNavqueryPtr);
 
-    pub fn QueryFindStraightPath(query: *mut RawNavqueryPtr, query: *const DtPathFindQuery, result: *mut DtPathFindResult);
-    pub fn QuerySamplePosition(query: *mut RawNavqueryPtr, point: *const DtVector, extent: *const DtVector, result: *mut DtVector) -> i32;
-    pub fn QueryGetLocation(query: *mut RawNavqueryPtr, point: *const DtVector, extent: *const DtVector, result: *mut DtVector) -> i32;
-    pub fn QueryRaycast(query: *mut RawNavqueryPtr, query: *const DtRaycastQuery, result: *mut DtRaycastResult);
-    pub fn QueryHasPath(query: *mut RawNavqueryPtr, query: *const DtPathFindQuery) -> i32;
+    pub fn QueryFindStraightPath(query: *mut RawNavqueryPtr, query: *const DtPathFindQuery, filter: *const NavQueryFilter, result: *mut DtPathFindResult) -> DtStatus;
+    pub fn QuerySamplePosition(query: *mut RawNavqueryPtr, point: *const DtVector, extent: *const DtVector, filter: *const NavQueryFilter, result: *mut DtVector) -> DtStatus;
+    pub fn QueryGetLocation(query: *mut RawNavqueryPtr, point: *const DtVector, extent: *const DtVector, filter: *const NavQueryFilter, result: *mut DtVector) -> DtStatus;
+    pub fn QueryGetRandomPosition(query: *mut RawNavqueryPtr, filter: *const NavQueryFilter, random_fn: RandomFloatFn, random_context: *mut c_void, result: *mut DtVector) -> DtStatus;
+    pub fn QueryGetRandomPositionAround(query: *mut RawNavqueryPtr, center: *const DtVector, extent: *const DtVector, radius: f32, filter: *const NavQueryFilter, random_fn: RandomFloatFn, random_context: *mut c_void, result: *mut DtVector) -> DtStatus;
+    pub fn QueryRaycast(query: *mut RawNavqueryPtr, query: *const DtRaycastQuery, filter: *const NavQueryFilter, result: *mut DtRaycastResult) -> DtStatus;
+    pub fn QueryFindPolyPath(query: *mut RawNavqueryPtr, query: *const DtPathFindQuery, filter: *const NavQueryFilter, result: *mut DtPolyPathResult) -> DtStatus;
+    pub fn QueryHasPath(query: *mut RawNavqueryPtr, query: *const DtPathFindQuery, filter: *const NavQueryFilter) -> DtStatus;
+    pub fn QueryInitSlicedPath(query: *mut RawNavqueryPtr, query: *const DtPathFindQuery, filter: *const NavQueryFilter) -> DtStatus;
+    pub fn QueryUpdateSlicedPath(query: *mut RawNavqueryPtr, max_iters: i32, done_iters: *mut i32) -> DtStatus;
+    /// existing_polys can be null, otherwise finalizeSlicedFindPathPartial keeps as much of the existing corridor as possible
+    pub fn QueryFinalizeSlicedPath(query: *mut RawNavqueryPtr, existing_polys: *const PolyRef, num_existing: i32, result: *mut DtPathFindResult) -> DtStatus;
 
     pub fn CrowdCreate(navmesh: *mut RawNavmeshPtr, max_agents: i32, max_agent_radius: f32) -> *mut RawCrowdPtr;
     pub fn CrowdDestroy(crowd: *mut RawCrowdPtr);
@@ -53,13 +108,28 @@ extern "C" {
     pub fn CrowdGetAgentCount(crowd: *mut RawCrowdPtr) -> i32;
     pub fn CrowdSetAgentParams(crowd: *mut RawCrowdPtr, idx: i32,agent_params: *const DtAgentParams);
     pub fn CrowdGetAgentParams(crowd: *mut RawCrowdPtr, idx: i32,agent_params: *mut DtAgentParams);
+    /// returns 1 on success
+    pub fn CrowdSetFilter(crowd: *mut RawCrowdPtr, idx: i32, filter: *const NavQueryFilter) -> i32;
+    /// returns 1 on success
+    pub fn CrowdGetFilter(crowd: *mut RawCrowdPtr, idx: i32, filter: *mut NavQueryFilter) -> i32;
+    /// returns 1 on success
+    pub fn CrowdSetObstacleAvoidanceParams(crowd: *mut RawCrowdPtr, idx: i32, params: *const ObstacleAvoidanceParams) -> i32;
+    /// returns 1 on success
+    pub fn CrowdGetObstacleAvoidanceParams(crowd: *mut RawCrowdPtr, idx: i32, params: *mut ObstacleAvoidanceParams) -> i32;
     // returns 1 on success
-    pub fn CrowdRequestMoveAgent(crowd: *mut RawCrowdPtr, idx: i32,position: *const DtVector) -> i32;
+    pub fn CrowdRequestMoveAgent(crowd: *mut RawCrowdPtr, idx: i32,position: *const DtVector) -> DtStatus;
+    pub fn CrowdRequestMoveAgentByPoly(crowd: *mut RawCrowdPtr, idx: i32, poly_ref: *const PolyRef, position: *const DtVector) -> DtStatus;
+    pub fn CrowdRequestMoveVelocity(crowd: *mut RawCrowdPtr, idx: i32, velocity: *const DtVector) -> DtStatus;
+    pub fn CrowdResetMoveTarget(crowd: *mut RawCrowdPtr, idx: i32) -> DtStatus;
     pub fn CrowdUpdate(crowd: *mut RawCrowdPtr, delta_time: f32);
 
     /// returns 1 on success
     pub fn CrowdGetAgent(crowd: *mut RawCrowdPtr, idx: i32, agent: *mut DtCrowdAgent) -> i32;
 
     // returns active agents
+    pub fn CrowdGetAgentInfo(crowd: *mut RawCrowdPtr, idx: i32, result: *mut DtCrowdAgentInfo) -> i32;
+    /// Returns the number of polys written, -1 if idx is out of range
+    pub fn CrowdGetAgentCorridor(crowd: *mut RawCrowdPtr, idx: i32, polys: *mut PolyRef, max_polys: i32) -> i32;
     pub fn CrowdGetAgents(crowd: *mut RawCrowdPtr, agents: *mut DtCrowdAgentsResult);
+    pub fn CrowdGetAgentSnapshot(crowd: *mut RawCrowdPtr, snapshot: *mut DtCrowdAgentSnapshot);
 }
\ No newline at end of file
@@ -1,7 +1,10 @@
-use crate::common::{*};
+use std::ffi::c_char;
+
+use crate::common::{*, nav_error::NavError};
 
 pub mod navmesh_build_utils;
 pub mod navmesh_builder;
+pub mod navmesh_build_job;
 pub mod tile_input_builder;
 pub mod shape_to_mesh;
 
@@ -15,6 +18,148 @@ pub struct DtGeneratedData
 	pub navmesh_data_length: i32
 }
 
+/// A user defined link between two points on the navmesh, such as a ladder, jump down or teleporter.
+/// The start point must be inside the tile the connection is built into, the end point can be in a neighbor tile.
+/// Endpoints need to be within agent max climb of the navmesh surface or the connection is dropped.
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct DtOffMeshConnection
+{
+	pub start: DtVector,
+	pub end: DtVector,
+	/// The radius of the endpoints, the endpoints are connected to the closest polygon within this radius.
+	pub radius: f32,
+	/// 1 if the connection can be traversed in both directions, 0 for start to end only.
+	pub bidirectional: i32,
+	pub area: u8,
+	/// Polygon flags used by query filters.  Connections with zero flags are excluded by the default filter.
+	pub flags: u16,
+	pub user_id: u32
+}
+
+impl DtOffMeshConnection {
+    pub fn new(start: DtVector, end: DtVector, radius: f32, bidirectional: bool) -> Self {
+        DtOffMeshConnection {
+            start,
+            end,
+            radius,
+            bidirectional: bidirectional as i32,
+            area: DtArea::WALKABLE,
+            flags: 1,
+            user_id: 0
+        }
+    }
+
+    pub fn is_bidirectional(&self) -> bool {
+        self.bidirectional != 0
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct DtBuildLogMessage
+{
+	/// rcLogCategory, 1 progress, 2 warning, 3 error
+	pub category: i32,
+	pub message: *const c_char
+}
+
+/// Stage durations in microseconds and the recast log of the last build.  logs is owned by the builder
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct DtBuildStats
+{
+	pub rasterize_time: i32,
+	pub filter_time: i32,
+	pub compact_time: i32,
+	pub erode_time: i32,
+	pub mark_areas_time: i32,
+	pub regions_time: i32,
+	pub contours_time: i32,
+	pub poly_mesh_time: i32,
+	pub detail_mesh_time: i32,
+	pub detour_time: i32,
+	pub total_time: i32,
+	pub logs: *const DtBuildLogMessage,
+	pub num_logs: i32
+}
+
+pub const MAX_CONVEX_VOLUME_VERTS: usize = 12;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(i32)]
+pub enum DtConvexVolumeType {
+    Polygon = 0,
+    Cylinder = 1,
+    Box = 2
+}
+
+/// Marks the area of the walkable surface inside a volume, for designer placed water, grass or no-go zones.
+/// Applied after erosion so it doesn't change where agents fit.  Area NULL removes the surface.
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct DtConvexVolume
+{
+	/// Polygon points, or the cylinder bottom center, or box min and max
+	pub verts: [DtVector; MAX_CONVEX_VOLUME_VERTS],
+	pub num_verts: i32,
+	pub hmin: f32,
+	pub hmax: f32,
+	pub radius: f32,
+	pub volume_type: DtConvexVolumeType,
+	pub area: u8
+}
+
+impl DtConvexVolume {
+    /// Convex polygon in xz extruded from hmin to hmax.  Fails if there are less than 3 or more than MAX_CONVEX_VOLUME_VERTS points
+    pub fn polygon(points: &[DtVector], hmin: f32, hmax: f32, area: u8) -> Result<Self, NavError> {
+        if points.len() < 3 || points.len() > MAX_CONVEX_VOLUME_VERTS {
+            return Err(NavError::InvalidParam("convex volumes need 3 to 12 points"));
+        }
+        let mut verts = [DtVector::default(); MAX_CONVEX_VOLUME_VERTS];
+        verts[..points.len()].copy_from_slice(points);
+        Ok(DtConvexVolume {
+            verts,
+            num_verts: points.len() as i32,
+            hmin,
+            hmax,
+            radius: 0.0,
+            volume_type: DtConvexVolumeType::Polygon,
+            area
+        })
+    }
+
+    /// position is the bottom center
+    pub fn cylinder(position: DtVector, radius: f32, height: f32, area: u8) -> Self {
+        let mut verts = [DtVector::default(); MAX_CONVEX_VOLUME_VERTS];
+        verts[0] = position;
+        DtConvexVolume {
+            verts,
+            num_verts: 1,
+            hmin: position.y,
+            hmax: position.y + height,
+            radius,
+            volume_type: DtConvexVolumeType::Cylinder,
+            area
+        }
+    }
+
+    pub fn aabb(min: DtVector, max: DtVector, area: u8) -> Self {
+        let mut verts = [DtVector::default(); MAX_CONVEX_VOLUME_VERTS];
+        verts[0] = min;
+        verts[1] = max;
+        DtConvexVolume {
+            verts,
+            num_verts: 2,
+            hmin: min.y,
+            hmax: max.y,
+            radius: 0.0,
+            volume_type: DtConvexVolumeType::Box,
+            area
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 #[repr(C)]
 pub struct DtBuildSettings
@@ -35,6 +180,24 @@ pub struct DtBuildSettings
 	pub agent_radius: f32,
 	pub agent_max_climb: f32,
 	pub agent_max_slope: f32,
+	pub partition_type: PartitionType,
+	pub filter_low_hanging_obstacles: i32,
+	pub filter_ledge_spans: i32,
+	pub filter_walkable_low_height_spans: i32,
+	pub max_verts_per_poly: i32,
+	pub area_flags: [u16; DtArea::MAX_AREAS],
+}
+
+/// How the walkable surface is split into regions before contours are traced.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(i32)]
+pub enum PartitionType {
+    /// Best quality tessellation, slowest.  The usual choice when tiles are prebuilt
+    Watershed = 0,
+    /// Fastest and never produces holes or overlaps, but can give long thin polygons.  Good for large open tiles built at runtime
+    Monotone = 1,
+    /// Keeps overlapping floors apart without the cost of watershed.  Good for multi story interiors
+    Layers = 2
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -67,11 +230,29 @@ pub struct NavBuildSettings {
     pub detail_sampling_distance: f32,
 
     /// The maximum distance the detail mesh surface should deviate from heightfield data. (For height detail only.)
-    pub max_detail_sampling_error: f32
+    pub max_detail_sampling_error: f32,
+
+    /// The region partitioning algorithm
+    pub partition_type: PartitionType,
+
+    /// Lets agents step up onto walkable spans within max climb of a span below, like curbs and stairs.
+    pub filter_low_hanging_obstacles: bool,
+
+    /// Removes walkable spans next to drops deeper than max climb.
+    pub filter_ledge_spans: bool,
+
+    /// Removes walkable spans with less than agent height of clearance above them.
+    pub filter_walkable_low_height_spans: bool,
+
+    /// The maximum number of vertices per navmesh polygon, 3 to DT_VERTS_PER_POLYGON
+    pub max_verts_per_poly: i32,
+
+    /// Navmesh poly flags for each area id.  Query filters include/exclude polys by these flags
+    pub area_flags: [u16; DtArea::MAX_AREAS]
 }
 
-impl NavBuildSettings {
-    pub fn default() -> Self {
+impl Default for NavBuildSettings {
+    fn default() -> Self {
         NavBuildSettings {
             cell_height: 0.2,
             cell_size: 0.3,
@@ -81,9 +262,35 @@ impl NavBuildSettings {
             max_edge_len: 12.0,
             max_edge_error: 1.3,
             detail_sampling_distance: 6.0,
-            max_detail_sampling_error: 1.0
+            max_detail_sampling_error: 1.0,
+            partition_type: PartitionType::Watershed,
+            filter_low_hanging_obstacles: true,
+            filter_ledge_spans: true,
+            filter_walkable_low_height_spans: true,
+            max_verts_per_poly: DT_VERTS_PER_POLYGON as i32,
+            area_flags: Self::default_area_flags()
         }
     }
+}
+
+impl NavBuildSettings {
+
+    /// Flags 1 for every walkable area, which the default NavQueryFilter includes
+    pub fn default_area_flags() -> [u16; DtArea::MAX_AREAS] {
+        let mut area_flags = [1; DtArea::MAX_AREAS];
+        area_flags[DtArea::NULL as usize] = 0;
+        area_flags
+    }
+
+    /// area is 0-63, higher values are masked to 6 bits like detour does
+    pub fn set_area_flags(&mut self, area: u8, flags: u16) {
+        self.area_flags[(area & DtArea::MASK) as usize] = flags;
+    }
+
+    /// area is masked to 0-63 like detour does
+    pub fn get_area_flags(&self, area: u8) -> u16 {
+        self.area_flags[(area & DtArea::MASK) as usize]
+    }
 
     pub fn high_quality() -> Self {
         NavBuildSettings {
@@ -95,7 +302,13 @@ impl NavBuildSettings {
             max_edge_len: 12.0,
             max_edge_error: 1.3,
             detail_sampling_distance: 6.0,
-            max_detail_sampling_error: 0.5
+            max_detail_sampling_error: 0.5,
+            partition_type: PartitionType::Watershed,
+            filter_low_hanging_obstacles: true,
+            filter_ledge_spans: true,
+            filter_walkable_low_height_spans: true,
+            max_verts_per_poly: DT_VERTS_PER_POLYGON as i32,
+            area_flags: Self::default_area_flags()
         }
     }
 }
\ No newline at end of file
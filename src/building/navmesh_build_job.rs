@@ -0,0 +1,341 @@
+use std::{any::Any, panic::{self, AssertUnwindSafe}, sync::{Arc, atomic::{AtomicBool, AtomicUsize, Ordering}}, thread::{self, JoinHandle}};
+
+use crossbeam::channel::{self, Receiver};
+
+use crate::common::{nav_error::NavError, DtBoundingBox, DtInt2, DtVector, NavAgentSettings};
+
+use super::{navmesh_build_utils::NavmeshBuildUtils, navmesh_builder::{NavmeshBuildResult, NavmeshBuilder}, tile_input_builder::TileInputBuilder, NavBuildSettings};
+
+/// World geometry for a NavmeshBuildJob.  gather is called from the job's worker threads, once per tile.
+pub trait GeometrySource: Send + Sync {
+    /// Append every triangle that overlaps bounds to input.  Triangles fully outside bounds can be skipped but don't have to be.
+    /// A panic fails just this tile, its result comes back as NavError::BuildPanicked.
+    fn gather(&self, bounds: &DtBoundingBox, input: &mut TileInputBuilder);
+}
+
+/// A single indexed triangle mesh as a GeometrySource.  Only triangles overlapping the tile bounds are handed to each tile.
+/// Triangles with an index outside vertices are skipped.
+#[derive(Clone, Debug, Default)]
+pub struct MeshGeometrySource {
+    pub vertices: Vec<DtVector>,
+    /// 3 indices per triangle
+    pub indices: Vec<i32>,
+    /// Area per triangle
+    pub areas: Vec<u8>
+}
+
+impl MeshGeometrySource {
+    pub fn new(vertices: Vec<DtVector>, indices: Vec<i32>, areas: Vec<u8>) -> Self {
+        MeshGeometrySource {
+            vertices,
+            indices,
+            areas
+        }
+    }
+
+    pub fn bounds(&self) -> DtBoundingBox {
+        let mut bounds = DtBoundingBox::new(DtVector::new(f32::MAX, f32::MAX, f32::MAX), DtVector::new(f32::MIN, f32::MIN, f32::MIN));
+        for &vertice in self.vertices.iter() {
+            bounds = bounds.merge(vertice);
+        }
+        bounds
+    }
+}
+
+impl GeometrySource for MeshGeometrySource {
+    fn gather(&self, bounds: &DtBoundingBox, input: &mut TileInputBuilder) {
+        let vertex = |index: i32| usize::try_from(index).ok().and_then(|index| self.vertices.get(index).copied());
+        for (triangle, &area) in self.indices.chunks_exact(3).zip(self.areas.iter()) {
+            let (Some(a), Some(b), Some(c)) = (vertex(triangle[0]), vertex(triangle[1]), vertex(triangle[2])) else {
+                continue;
+            };
+            let vertices = [a, b, c];
+            let triangle_bounds = DtBoundingBox::new(vertices[0].min(vertices[1]).min(vertices[2]), vertices[0].max(vertices[1]).max(vertices[2]));
+
+            // Only xz matters, height is open ended
+            if triangle_bounds.min.x > bounds.max.x || triangle_bounds.max.x < bounds.min.x
+                || triangle_bounds.min.z > bounds.max.z || triangle_bounds.max.z < bounds.min.z {
+                continue;
+            }
+            input.append_triangle(&vertices, area);
+        }
+    }
+}
+
+/// Builds every tile overlapping a bounds on a pool of threads.  Does the get_overlapping_tiles -> calculate_tile_bounding_box ->
+/// TileInputBuilder -> build_tile loop described in the README, with a NavmeshBuilder per thread.
+///
+/// Results are streamed back as each tile finishes, in no particular order.  Adding the tiles to a Navmesh is left to the caller.
+#[derive(Clone, Copy, Debug)]
+pub struct NavmeshBuildJob {
+    pub build_settings: NavBuildSettings,
+    pub agent_settings: NavAgentSettings,
+    pub bounds: DtBoundingBox,
+    pub thread_count: usize
+}
+
+impl NavmeshBuildJob {
+    pub fn new(build_settings: NavBuildSettings, agent_settings: NavAgentSettings, bounds: DtBoundingBox) -> Self {
+        NavmeshBuildJob {
+            build_settings,
+            agent_settings,
+            bounds,
+            thread_count: thread::available_parallelism().map(|count| count.get()).unwrap_or(4)
+        }
+    }
+
+    /// Tile coords overlapping bounds, ordered by row
+    pub fn tiles(&self) -> Vec<DtInt2> {
+        let mut tiles: Vec<DtInt2> = NavmeshBuildUtils::get_overlapping_tiles_from_settings(self.build_settings, self.bounds).into_iter().collect();
+        tiles.sort_by_key(|coord| (coord.y, coord.x));
+        tiles
+    }
+
+    /// The bounds geometry is gathered from for a tile.  The tile plus the border the C side rasterizes, see calculate_tile_input_bounds.
+    /// Height comes from the job bounds plus agent height so flat geometry doesn't end up with an empty heightfield
+    pub fn tile_gather_bounds(&self, coord: DtInt2) -> DtBoundingBox {
+        let mut bounds = NavmeshBuildUtils::calculate_tile_input_bounds(self.build_settings, self.agent_settings, coord);
+        bounds.min.y = self.bounds.min.y;
+        bounds.max.y = self.bounds.max.y + self.agent_settings.height;
+        bounds
+    }
+
+    pub fn start<G: GeometrySource + 'static>(self, source: Arc<G>) -> NavmeshBuildHandle {
+        let tiles = self.tiles();
+        let total = tiles.len();
+
+        let (work_sender, work_receiver) = channel::unbounded::<DtInt2>();
+        for coord in tiles {
+            // The receiver is alive, this can't fail
+            let _ = work_sender.send(coord);
+        }
+        drop(work_sender);
+
+        let (result_sender, result_receiver) = channel::unbounded();
+        let completed = Arc::new(AtomicUsize::new(0));
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        let thread_count = self.thread_count.clamp(1, total.max(1));
+        let mut threads = Vec::with_capacity(thread_count);
+        for _ in 0..thread_count {
+            let work_receiver = work_receiver.clone();
+            let result_sender = result_sender.clone();
+            let completed = completed.clone();
+            let cancelled = cancelled.clone();
+            let source = source.clone();
+
+            threads.push(thread::spawn(move || {
+                let mut builder = NavmeshBuilder::new(self.build_settings, self.agent_settings);
+                while let Ok(coord) = work_receiver.recv() {
+                    if cancelled.load(Ordering::Relaxed) {
+                        break;
+                    }
+
+                    // A panicking tile is reported as an error instead of silently taking the worker down
+                    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                        let bounds = self.tile_gather_bounds(coord);
+                        let mut input = TileInputBuilder::new(coord, bounds);
+                        source.gather(&bounds, &mut input);
+                        builder.build_tile(input)
+                    })).unwrap_or_else(|payload| Err(NavError::BuildPanicked(panic_message(payload.as_ref()))));
+
+                    completed.fetch_add(1, Ordering::Relaxed);
+                    if result_sender.send((coord, result)).is_err() {
+                        break;
+                    }
+                }
+            }));
+        }
+
+        NavmeshBuildHandle {
+            results: result_receiver,
+            total,
+            completed,
+            cancelled,
+            threads
+        }
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        String::from("unknown panic")
+    }
+}
+
+/// Handle to a running NavmeshBuildJob.  Dropping the handle cancels the job and waits for the threads to exit.
+pub struct NavmeshBuildHandle {
+    results: Receiver<(DtInt2, Result<NavmeshBuildResult, NavError>)>,
+    total: usize,
+    completed: Arc<AtomicUsize>,
+    cancelled: Arc<AtomicBool>,
+    threads: Vec<JoinHandle<()>>
+}
+
+impl NavmeshBuildHandle {
+    /// Finished tiles.  The channel disconnects once every tile is done or the job is cancelled
+    pub fn results(&self) -> &Receiver<(DtInt2, Result<NavmeshBuildResult, NavError>)> {
+        &self.results
+    }
+
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    pub fn completed(&self) -> usize {
+        self.completed.load(Ordering::Relaxed)
+    }
+
+    /// 0.0 - 1.0
+    pub fn progress(&self) -> f32 {
+        if self.total == 0 {
+            return 1.0;
+        }
+        self.completed() as f32 / self.total as f32
+    }
+
+    /// Tiles already being built still finish and are sent, no new tiles are started
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.threads.iter().all(|thread| thread.is_finished())
+    }
+
+    /// Wait for the worker threads to exit.  Tile panics are caught and sent as results, anything else panicking in a worker is re-raised here
+    pub fn join(mut self) {
+        if let Some(payload) = self.join_threads() {
+            panic::resume_unwind(payload);
+        }
+    }
+
+    /// Returns the first worker panic
+    fn join_threads(&mut self) -> Option<Box<dyn Any + Send>> {
+        let mut first_panic = None;
+        for thread in self.threads.drain(..) {
+            if let Err(payload) = thread.join() {
+                first_panic.get_or_insert(payload);
+            }
+        }
+        first_panic
+    }
+}
+
+impl Drop for NavmeshBuildHandle {
+    fn drop(&mut self) {
+        self.cancel();
+        // Don't panic in drop, join re-raises worker panics
+        let _ = self.join_threads();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::{building::{tile_input_builder::TileInputBuilder, NavBuildSettings}, common::{nav_error::NavError, navmesh::{Navmesh, NavmeshSettings}, DtArea, DtBoundingBox, DtInt2, DtVector, NavAgentSettings}, queries::{NavQueryFilter, NavQuerySettings}};
+
+    use super::{GeometrySource, MeshGeometrySource, NavmeshBuildJob};
+
+    fn plane(width: f32) -> MeshGeometrySource {
+        let vertices = vec![DtVector::new(0.0, 1.0, 0.0), DtVector::new(0.0, 1.0, width), DtVector::new(width, 1.0, width), DtVector::new(width, 1.0, 0.0)];
+        MeshGeometrySource::new(vertices, vec![0, 1, 2, 0, 2, 3], vec![DtArea::WALKABLE; 2])
+    }
+
+    #[test]
+    fn build_job() {
+        let source = Arc::new(plane(50.0));
+        let build_settings = NavBuildSettings::default();
+        let mut job = NavmeshBuildJob::new(build_settings, NavAgentSettings::default(), source.bounds());
+        job.thread_count = 3;
+
+        let tiles = job.tiles();
+        assert_eq!(9, tiles.len());
+
+        let handle = job.start(source);
+        let mut navmesh = Navmesh::new(NavmeshSettings::default(build_settings, 2048.0, 10)).unwrap();
+        let mut built = 0;
+        for (coord, result) in handle.results().iter() {
+            assert!(tiles.contains(&coord));
+            if let Some(tile) = result.unwrap().tile {
+                assert_eq!(Some(coord), tile.coord());
+                navmesh.add_or_replace_tile(tile).unwrap();
+                built += 1;
+            }
+        }
+        assert_eq!(9, built);
+        assert_eq!(9, handle.completed());
+        assert_eq!(1.0, handle.progress());
+        handle.join();
+
+        // Crossing the tile seams shouldn't need a detour
+        let query = navmesh.query_pool.pop().unwrap();
+        let start = DtVector::new(2.0, 1.0, 25.0);
+        let end = DtVector::new(48.0, 1.0, 25.0);
+        let hit = query.raycast(NavQuerySettings::default(), start, end, &NavQueryFilter::default()).unwrap();
+        assert!(!hit.hit);
+        navmesh.query_pool.push(query);
+    }
+
+    struct PanickingSource;
+
+    impl GeometrySource for PanickingSource {
+        fn gather(&self, bounds: &DtBoundingBox, input: &mut TileInputBuilder) {
+            if input.coord.x == 1 {
+                panic!("bad geometry");
+            }
+            plane(50.0).gather(bounds, input);
+        }
+    }
+
+    #[test]
+    fn build_job_panics() {
+        let bounds = DtBoundingBox::new(DtVector::new(0.0, 0.0, 0.0), DtVector::new(50.0, 2.0, 50.0));
+        let mut job = NavmeshBuildJob::new(NavBuildSettings::default(), NavAgentSettings::default(), bounds);
+        job.thread_count = 2;
+
+        let handle = job.start(Arc::new(PanickingSource));
+        let results: Vec<_> = handle.results().iter().collect();
+        assert_eq!(handle.total(), results.len());
+        assert_eq!(handle.total(), handle.completed());
+        for (coord, result) in results {
+            if coord.x == 1 {
+                assert_eq!(Err(NavError::BuildPanicked(String::from("bad geometry"))), result.map(|result| result.tile.is_some()));
+            } else {
+                assert!(result.unwrap().tile.is_some());
+            }
+        }
+        handle.join();
+
+        // Out of range indices are skipped instead of panicking
+        let mut source = plane(50.0);
+        source.indices.extend([0, 1, 100]);
+        source.areas.push(DtArea::WALKABLE);
+        let mut input = TileInputBuilder::new(DtInt2::new(0, 0), bounds);
+        source.gather(&bounds, &mut input);
+        assert_eq!(2, input.areas.len());
+    }
+
+    #[test]
+    fn cancel_build_job() {
+        let source = Arc::new(plane(100.0));
+        let bounds = DtBoundingBox::new(DtVector::new(0.0, 0.0, 0.0), DtVector::new(100.0, 2.0, 100.0));
+        let mut job = NavmeshBuildJob::new(NavBuildSettings::default(), NavAgentSettings::default(), bounds);
+        job.thread_count = 1;
+
+        let handle = job.start(source);
+        handle.cancel();
+        let received = handle.results().iter().count();
+        assert!(handle.is_cancelled());
+        assert!(received < handle.total());
+    }
+}
@@ -1,7 +1,7 @@
 use rustc_hash::FxHashSet;
 
 
-use crate::common::{DtInt2, DtVector2, DtBoundingBox};
+use crate::common::{DtInt2, DtVector2, DtBoundingBox, NavAgentSettings};
 
 use super::NavBuildSettings;
 
@@ -40,9 +40,7 @@ impl NavmeshBuildUtils {
     }
 
     /// This does not factor in tile border size, which you need to include in the bounds given to TileInputBuilder.  Or you will have gaps between tiles due to no border data
-    /// on the C side the border size is calculated as (agent radius / cell size). bounds is then expanded by border size * cell size.
-    /// Or you can just expand the bounds by a set amount of 2-3 which seems to work well.
-    /// See NavigationBuilder::BuildNavmesh if you want to try and tighten this up with an exact formula.  I never took the time to do that.
+    /// Use calculate_tile_input_bounds for the bounds to gather tile geometry from.
     pub fn calculate_tile_bounding_box(settings: NavBuildSettings, tile_coord: DtInt2) -> DtBoundingBox {
 
         let tcs = settings.tile_size as f32 * settings.cell_size;
@@ -59,4 +57,49 @@ impl NavmeshBuildUtils {
         bounding_box
     }
 
-}
\ No newline at end of file
+    /// Border size in cells that NavigationBuilder::BuildNavmesh adds around a tile.  ceil(agent radius / cell size) + 3
+    pub fn calculate_border_size(settings: NavBuildSettings, agent: NavAgentSettings) -> i32 {
+        let walkable_radius = (agent.radius / settings.cell_size).ceil() as i32;
+        walkable_radius + 3
+    }
+
+    /// The tile bounds expanded on xz by the border the C side rasterizes.  Gather the tile's input geometry from these bounds
+    /// so neighboring tiles see the same geometry along their seams.  Height is left at 0, TileInputBuilder grows it to fit the geometry.
+    pub fn calculate_tile_input_bounds(settings: NavBuildSettings, agent: NavAgentSettings, tile_coord: DtInt2) -> DtBoundingBox {
+        let mut bounding_box = Self::calculate_tile_bounding_box(settings, tile_coord);
+        let border = Self::calculate_border_size(settings, agent) as f32 * settings.cell_size;
+        bounding_box.min.x -= border;
+        bounding_box.min.z -= border;
+        bounding_box.max.x += border;
+        bounding_box.max.z += border;
+        bounding_box
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{building::NavBuildSettings, common::{DtInt2, NavAgentSettings}};
+
+    use super::NavmeshBuildUtils;
+
+    #[test]
+    fn tile_input_bounds() {
+        let settings = NavBuildSettings::default();
+        let agent = NavAgentSettings::default();
+
+        // radius 0.5 / cell size 0.3 rounds up to 2 cells
+        assert_eq!(5, NavmeshBuildUtils::calculate_border_size(settings, agent));
+
+        let coord = DtInt2::new(1, -2);
+        let tile = NavmeshBuildUtils::calculate_tile_bounding_box(settings, coord);
+        let input = NavmeshBuildUtils::calculate_tile_input_bounds(settings, agent, coord);
+        let border = 5.0 * settings.cell_size;
+        assert!((tile.min.x - border - input.min.x).abs() < 0.0001);
+        assert!((tile.min.z - border - input.min.z).abs() < 0.0001);
+        assert!((tile.max.x + border - input.max.x).abs() < 0.0001);
+        assert!((tile.max.z + border - input.max.z).abs() < 0.0001);
+        assert_eq!(tile.min.y, input.min.y);
+        assert_eq!(tile.max.y, input.max.y);
+    }
+}
@@ -1,14 +1,20 @@
-use crate::{common::{navmesh_tile::NavmeshTile, DtVector, DtInt2, DtArea, NavAgentSettings}, bindings::{CreateBuilder, BuildNavmesh, SetSettings, DestroyBuilder}};
+use std::{ffi::CStr, time::Duration};
 
-use super::{navmesh_build_utils::NavmeshBuildUtils, DtBuildSettings, tile_input_builder::TileInputBuilder, NavBuildSettings};
+use crate::{common::{navmesh_tile::NavmeshTile, nav_error::NavError, DtVector, DtInt2, DtArea, NavAgentSettings, DT_VERTS_PER_POLYGON}, bindings::{CreateBuilder, BuildNavmesh, GetBuildStats, BuildTileCacheLayers, SetSettings, SetOffMeshConnections, SetConvexVolumes, DestroyBuilder}, tile_cache::{DtTileCacheCompressorCallbacks, TileCacheCompressor}};
+
+use super::{navmesh_build_utils::NavmeshBuildUtils, DtBuildSettings, DtBuildStats, tile_input_builder::TileInputBuilder, NavBuildSettings, PartitionType};
 
 /// build result codes. Some originate locally some from C
-/// Note: ZeroVertCount is fairly common and normal.  It means we didn't pass recast input data that resulted in navmesh geometry.
+/// Note: ZeroVertCount is fairly common and normal.  It means we didn't pass recast input data that resulted in navmesh geometry,
+/// build_tile returns it as a result without a tile rather than an error.
 /// Other errors from the C side shouldn't happen in normal circumstances.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum BuildResultCode {
     None = 10000,
     Success = 0,
+    /// Settings recast rejects, or tile bounds with no height
+    InvalidConfig = 1,
+    MaxVertsPerPoly = -1002,
     AreaInput = -1001,
     VerticesInput = -1000,
     CreateBuilderFailed = -100,
@@ -29,6 +35,11 @@ pub enum BuildResultCode {
     RcAllocPolyMeshDetail = 130,
     RcBuildPolyMeshDetail = 140,
 
+    /// tile cache layer builds
+    RcAllocHeightfieldLayerSet = 200,
+    RcBuildHeightfieldLayers = 210,
+    DtBuildTileCacheLayer = 220,
+
     /// should never see these, see the C source for definitions if needed
     CreateDetourMesh10 = 1010,
     CreateDetourMesh11 = 1011,
@@ -45,6 +56,8 @@ impl From<i32> for BuildResultCode {
     fn from(item: i32) -> Self {
         match item {
             0 => BuildResultCode::Success,
+            1 => BuildResultCode::InvalidConfig,
+            -1002 => BuildResultCode::MaxVertsPerPoly,
             -1001 => BuildResultCode::AreaInput,
             -1000 => BuildResultCode::VerticesInput,
             -100 => BuildResultCode::CreateBuilderFailed,
@@ -64,6 +77,10 @@ impl From<i32> for BuildResultCode {
             130 => BuildResultCode::RcAllocPolyMeshDetail,
             140 => BuildResultCode::RcBuildPolyMeshDetail,
 
+            200 => BuildResultCode::RcAllocHeightfieldLayerSet,
+            210 => BuildResultCode::RcBuildHeightfieldLayers,
+            220 => BuildResultCode::DtBuildTileCacheLayer,
+
             1010 => BuildResultCode::CreateDetourMesh10,
             1011 => BuildResultCode::CreateDetourMesh11,
             1012 => BuildResultCode::CreateDetourMesh12,
@@ -78,26 +95,102 @@ impl From<i32> for BuildResultCode {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BuildLogLevel {
+    Progress,
+    Warning,
+    Error
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BuildLogMessage {
+    pub level: BuildLogLevel,
+    pub message: String
+}
+
+/// Time spent in each build stage and the recast log.  Stages that didn't run are zero
+#[derive(Clone, Debug, Default)]
+pub struct NavmeshBuildStats {
+    pub rasterize: Duration,
+    /// Low hanging obstacle, ledge and low height span filters
+    pub filter: Duration,
+    pub compact: Duration,
+    pub erode: Duration,
+    /// Convex volumes
+    pub mark_areas: Duration,
+    /// Distance field and region partitioning
+    pub regions: Duration,
+    pub contours: Duration,
+    pub poly_mesh: Duration,
+    pub detail_mesh: Duration,
+    /// Creating the detour tile data
+    pub detour: Duration,
+    pub total: Duration,
+    pub logs: Vec<BuildLogMessage>
+}
+
+impl NavmeshBuildStats {
+    fn from_raw(stats: &DtBuildStats) -> Self {
+        let time = |micros: i32| Duration::from_micros(micros.max(0) as u64);
+
+        let mut logs = Vec::with_capacity(stats.num_logs.max(0) as usize);
+        if !stats.logs.is_null() {
+            let raw_logs = unsafe { std::slice::from_raw_parts(stats.logs, stats.num_logs as usize) };
+            for log in raw_logs {
+                let level = match log.category {
+                    1 => BuildLogLevel::Progress,
+                    2 => BuildLogLevel::Warning,
+                    _ => BuildLogLevel::Error
+                };
+                let message = unsafe { CStr::from_ptr(log.message) }.to_string_lossy().into_owned();
+                logs.push(BuildLogMessage { level, message });
+            }
+        }
+
+        NavmeshBuildStats {
+            rasterize: time(stats.rasterize_time),
+            filter: time(stats.filter_time),
+            compact: time(stats.compact_time),
+            erode: time(stats.erode_time),
+            mark_areas: time(stats.mark_areas_time),
+            regions: time(stats.regions_time),
+            contours: time(stats.contours_time),
+            poly_mesh: time(stats.poly_mesh_time),
+            detail_mesh: time(stats.detail_mesh_time),
+            detour: time(stats.detour_time),
+            total: time(stats.total_time),
+            logs
+        }
+    }
+
+    /// Warnings and errors
+    pub fn problems(&self) -> impl Iterator<Item = &BuildLogMessage> {
+        self.logs.iter().filter(|log| log.level != BuildLogLevel::Progress)
+    }
+}
+
 /// Result of building a navmesh tile
 #[derive(Clone, Debug)]
 pub struct NavmeshBuildResult {
-    pub success: bool,
-	pub result_code: BuildResultCode,
     pub tiles_built: i32,
     pub vertice_count: i32,
     pub triangle_count: i32,
+    /// None if the input didn't produce any navmesh geometry
     pub tile: Option<NavmeshTile>,
+    /// The partitioning the tile was built with
+    pub partition_type: PartitionType,
+    pub stats: NavmeshBuildStats,
 }
 
-impl NavmeshBuildResult {
-    pub fn default() -> Self {
+impl Default for NavmeshBuildResult {
+    fn default() -> Self {
         NavmeshBuildResult {
-            success: false,
-            result_code: BuildResultCode::None,
             tiles_built: 0,
             vertice_count: 0,
             triangle_count: 0,
-            tile: None
+            tile: None,
+            partition_type: PartitionType::Watershed,
+            stats: NavmeshBuildStats::default()
         }
     }
 }
@@ -115,20 +208,129 @@ impl NavmeshBuilder {
         }
     }
 
-    pub fn build_tile(&mut self, mut input: TileInputBuilder) -> NavmeshBuildResult {
+    /// Fails with NavError::Build when recast fails or the input is invalid.  The error carries the recast log
+    pub fn build_tile(&mut self, mut input: TileInputBuilder) -> Result<NavmeshBuildResult, NavError> {
         let mut result = NavmeshBuildResult::default();
 
+        self.validate(&input)?;
+        
+        let mut dt_build_settings = self.create_build_settings(&mut input);
+        result.partition_type = dt_build_settings.partition_type;
+
+        let code: BuildResultCode;
+        unsafe {
+            let ptr = CreateBuilder();
+            if ptr.is_null() {
+                return Err(BuildResultCode::CreateBuilderFailed.into());
+            }
+
+            SetSettings(ptr, &mut dt_build_settings as *mut DtBuildSettings);
+            SetOffMeshConnections(ptr, input.off_mesh_connections.as_ptr(), input.off_mesh_connections.len() as i32);
+            SetConvexVolumes(ptr, input.convex_volumes.as_ptr(), input.convex_volumes.len() as i32);
+
+            let generated_data_ptr = BuildNavmesh(
+                ptr,
+                input.vertices.as_mut_ptr(),
+                input.vertices.len() as i32,
+                input.indices.as_mut_ptr(),
+                input.indices.len() as i32,
+                input.areas.as_mut_ptr(),
+            );
+            let generated_data = *generated_data_ptr;
+            code = generated_data.error.into();
+
+            if generated_data.error == 0
+                && generated_data.success
+                && generated_data.navmesh_data_length > 0
+            {
+                let mut data: Vec<u8> = vec![0; generated_data.navmesh_data_length as usize];
+                std::ptr::copy_nonoverlapping(
+                    generated_data.navmesh_data,
+                    data.as_mut_ptr(),
+                    generated_data.navmesh_data_length as usize,
+                );
+
+                let tile = NavmeshTile { data };
+                result.tile = Some(tile);
+            }
+
+            result.stats = NavmeshBuildStats::from_raw(&*GetBuildStats(ptr));
+            DestroyBuilder(ptr);
+        }
+
+        match code {
+            BuildResultCode::Success | BuildResultCode::ZeroVertCount => Ok(result),
+            code => Err(NavError::Build { code, logs: result.stats.logs })
+        }
+    }
+
+    /// Builds the compressed tile cache layers for a tile instead of a navmesh tile.  Add them with TileCache::add_tile_layers.
+    /// Off-mesh connections aren't carried over into tile cache built tiles.
+    /// One compressed layer per walkable floor in the tile, empty if the input has no walkable surface.
+    pub fn build_tile_cache_layers<C: TileCacheCompressor>(&mut self, mut input: TileInputBuilder, compressor: &C) -> Result<Vec<Vec<u8>>, NavError> {
+        let mut layers = Vec::new();
+
+        self.validate(&input)?;
+
+        let mut dt_build_settings = self.create_build_settings(&mut input);
+        let mut callbacks = DtTileCacheCompressorCallbacks::new(compressor);
+
+        unsafe {
+            let ptr = CreateBuilder();
+            if ptr.is_null() {
+                return Err(BuildResultCode::CreateBuilderFailed.into());
+            }
+
+            SetSettings(ptr, &mut dt_build_settings as *mut DtBuildSettings);
+            SetConvexVolumes(ptr, input.convex_volumes.as_ptr(), input.convex_volumes.len() as i32);
+
+            let layers_result = *BuildTileCacheLayers(
+                ptr,
+                input.vertices.as_mut_ptr(),
+                input.vertices.len() as i32,
+                input.indices.as_mut_ptr(),
+                input.indices.len() as i32,
+                input.areas.as_mut_ptr(),
+                &mut callbacks as *mut DtTileCacheCompressorCallbacks,
+            );
+            let code: BuildResultCode = layers_result.error.into();
+
+            if layers_result.success {
+                let raw_layers = std::slice::from_raw_parts(layers_result.layers, layers_result.num_layers as usize);
+                let layer_sizes = std::slice::from_raw_parts(layers_result.layer_sizes, layers_result.num_layers as usize);
+                for (&layer, &size) in raw_layers.iter().zip(layer_sizes.iter()) {
+                    layers.push(std::slice::from_raw_parts(layer, size as usize).to_vec());
+                }
+            } else if code != BuildResultCode::Success {
+                let logs = NavmeshBuildStats::from_raw(&*GetBuildStats(ptr)).logs;
+                DestroyBuilder(ptr);
+                return Err(NavError::Build { code, logs });
+            }
+
+            DestroyBuilder(ptr);
+        }
+
+        Ok(layers)
+    }
+
+    pub(crate) fn validate(&self, input: &TileInputBuilder) -> Result<(), BuildResultCode> {
+        if !(3..=DT_VERTS_PER_POLYGON as i32).contains(&self.build_settings.max_verts_per_poly) {
+            return Err(BuildResultCode::MaxVertsPerPoly);
+        }
+
         if input.areas.len() != input.indices.len() / 3 {
-            result.result_code = BuildResultCode::AreaInput;
-            return result;
+            return Err(BuildResultCode::AreaInput);
         }
 
         if input.vertices.len() != input.indices.len() {
-            result.result_code = BuildResultCode::VerticesInput;
-            return result;
+            return Err(BuildResultCode::VerticesInput);
         }
-        
-        Self::normalize_input_heights(&mut input);
+        Ok(())
+    }
+
+    /// The settings the C side builds a tile with.  Shared with the tile cache layer build
+    pub(crate) fn create_build_settings(&self, input: &mut TileInputBuilder) -> DtBuildSettings {
+        Self::normalize_input_heights(input);
         
         let mut tile_bounding_box = NavmeshBuildUtils::calculate_tile_bounding_box(
             self.build_settings,
@@ -145,7 +347,7 @@ impl NavmeshBuilder {
         );
 
 
-        let mut dt_build_settings = DtBuildSettings {
+        DtBuildSettings {
             bounding_box: tile_bounding_box,
             tile_position: input.coord,
             tile_size: self.build_settings.tile_size,
@@ -163,48 +365,13 @@ impl NavmeshBuilder {
             agent_radius: self.agent_settings.radius,
             agent_max_climb: self.agent_settings.max_climb,
             agent_max_slope: self.agent_settings.max_slope,
-        };
-
-        unsafe {
-            let ptr = CreateBuilder();
-            if ptr.is_null() {
-                result.result_code = BuildResultCode::CreateBuilderFailed;
-                return result;
-            }
-
-            SetSettings(ptr, &mut dt_build_settings as *mut DtBuildSettings);
-
-            let generated_data_ptr = BuildNavmesh(
-                ptr,
-                input.vertices.as_mut_ptr(),
-                input.vertices.len() as i32,
-                input.indices.as_mut_ptr(),
-                input.indices.len() as i32,
-                input.areas.as_mut_ptr(),
-            );
-            let generated_data = *generated_data_ptr;
-            result.result_code = generated_data.error.into();
-            result.success = generated_data.success;
-
-            if generated_data.error == 0
-                && generated_data.success
-                && generated_data.navmesh_data_length > 0
-            {
-                let mut data: Vec<u8> = vec![0; generated_data.navmesh_data_length as usize];
-                std::ptr::copy_nonoverlapping(
-                    generated_data.navmesh_data,
-                    data.as_mut_ptr(),
-                    generated_data.navmesh_data_length as usize,
-                );
-
-                let tile = NavmeshTile { data };
-                result.tile = Some(tile);
-
-                DestroyBuilder(ptr);
-            }
+            partition_type: self.build_settings.partition_type,
+            filter_low_hanging_obstacles: self.build_settings.filter_low_hanging_obstacles as i32,
+            filter_ledge_spans: self.build_settings.filter_ledge_spans as i32,
+            filter_walkable_low_height_spans: self.build_settings.filter_walkable_low_height_spans as i32,
+            max_verts_per_poly: self.build_settings.max_verts_per_poly,
+            area_flags: self.build_settings.area_flags,
         }
-
-        result
     }
 
     fn normalize_input_heights(input: &mut TileInputBuilder) {
@@ -212,7 +379,7 @@ impl NavmeshBuilder {
         input.bounds.min.y = f32::MAX.min(input.bounds.min.y);
     }
 
-    pub fn build_test_tile(width: f32) -> NavmeshBuildResult {
+    pub fn build_test_tile(width: f32) -> Result<NavmeshBuildResult, NavError> {
         let mut builder = NavmeshBuilder::new(NavBuildSettings::default(), NavAgentSettings::default());
         let coord = DtInt2::new(0, 0);
         let bounds = NavmeshBuildUtils::calculate_tile_bounding_box(builder.build_settings, coord);
@@ -234,12 +401,14 @@ mod tests {
 
     use crate::{
         building::{
-            navmesh_build_utils::NavmeshBuildUtils, tile_input_builder::TileInputBuilder, NavBuildSettings,
+            navmesh_build_utils::NavmeshBuildUtils, tile_input_builder::TileInputBuilder, NavBuildSettings, PartitionType,
         },
-        common::{DtArea, DtInt2, DtVector, NavAgentSettings},
+        common::{nav_error::NavError, DtArea, DtInt2, DtVector, NavAgentSettings},
     };
 
-    use super::NavmeshBuilder;
+    use std::time::Duration;
+
+    use super::{BuildLogLevel, BuildResultCode, NavmeshBuilder};
 
     #[test]
     fn create_tile() {
@@ -261,8 +430,8 @@ mod tests {
 
         println!("input bounds {:?}", input.bounds);
         let result = builder.build_tile(input);
-        println!("build.success {:?} build.error:{:?}", result.success, result.result_code);
-        if result.success {
+        println!("build result {:?}", result.as_ref().map(|result| result.tile.is_some()));
+        if let Ok(result) = result {
             if let Some(tile) = &result.tile {
                 if let Some((vertices, _indices)) = tile.get_tile_vertices() {
                     for vert in vertices.iter() {
@@ -272,4 +441,110 @@ mod tests {
             }
         }
     }
+
+    fn plane_input(builder: &NavmeshBuilder) -> TileInputBuilder {
+        let coord = DtInt2::new(0, 0);
+        let bounds = NavmeshBuildUtils::calculate_tile_input_bounds(builder.build_settings, builder.agent_settings, coord);
+        let mut input = TileInputBuilder::new(coord, bounds);
+
+        let (min, max) = (bounds.min, bounds.max);
+        input.append_triangle(&[DtVector::new(min.x, 1.0, min.z), DtVector::new(min.x, 1.0, max.z), DtVector::new(max.x, 1.0, max.z)], DtArea::WALKABLE);
+        input.append_triangle(&[DtVector::new(min.x, 1.0, min.z), DtVector::new(max.x, 1.0, max.z), DtVector::new(max.x, 1.0, min.z)], DtArea::WALKABLE);
+        input
+    }
+
+    #[test]
+    fn convex_volumes() {
+        let mut builder = NavmeshBuilder::new(NavBuildSettings::default(), NavAgentSettings::default());
+        let mut input = plane_input(&builder);
+
+        let water = [DtVector::new(2.0, 0.0, 2.0), DtVector::new(2.0, 0.0, 8.0), DtVector::new(8.0, 0.0, 8.0), DtVector::new(8.0, 0.0, 2.0)];
+        input.add_convex_volume(&water, 0.0, 2.0, 5).unwrap();
+        assert!(input.add_convex_volume(&water[..2], 0.0, 2.0, 5).is_err());
+        input.add_cylinder_volume(DtVector::new(14.0, 0.0, 14.0), 2.0, 2.0, 10);
+        input.add_box_volume(DtVector::new(12.0, 0.0, 2.0), DtVector::new(16.0, 2.0, 6.0), DtArea::NULL);
+
+        let result = builder.build_tile(input).unwrap();
+        let tile = result.tile.unwrap();
+        let view = tile.view().unwrap();
+        let areas: Vec<u8> = view.polys().map(|poly| poly.area()).collect();
+        assert!(areas.contains(&DtArea::WALKABLE));
+        assert!(areas.contains(&5));
+        assert!(areas.contains(&10));
+
+        // The null area box is cut out of the navmesh
+        let mesh = tile.get_detail_mesh(0).unwrap();
+        for triangle in mesh.indices.chunks_exact(3) {
+            let center = (mesh.vertices[triangle[0] as usize] + mesh.vertices[triangle[1] as usize] + mesh.vertices[triangle[2] as usize]) * (1.0 / 3.0);
+            assert!(!(center.x > 12.5 && center.x < 15.5 && center.z > 2.5 && center.z < 5.5));
+        }
+    }
+
+    #[test]
+    fn partition_types() {
+        for partition_type in [PartitionType::Watershed, PartitionType::Monotone, PartitionType::Layers] {
+            let build_settings = NavBuildSettings { partition_type, ..Default::default() };
+            let mut builder = NavmeshBuilder::new(build_settings, NavAgentSettings::default());
+            let input = plane_input(&builder);
+
+            let result = builder.build_tile(input).unwrap();
+            assert_eq!(partition_type, result.partition_type);
+            assert!(result.tile.unwrap().view().unwrap().poly_count() > 0);
+        }
+    }
+
+    #[test]
+    fn poly_settings() {
+        let mut build_settings = NavBuildSettings { max_verts_per_poly: 7, ..Default::default() };
+        let mut builder = NavmeshBuilder::new(build_settings, NavAgentSettings::default());
+        let error = builder.build_tile(plane_input(&builder)).unwrap_err();
+        assert_eq!(NavError::Build { code: BuildResultCode::MaxVertsPerPoly, logs: Vec::new() }, error);
+
+        build_settings.max_verts_per_poly = 3;
+        build_settings.filter_ledge_spans = false;
+        build_settings.set_area_flags(5, 2);
+        let mut builder = NavmeshBuilder::new(build_settings, NavAgentSettings::default());
+        let mut input = plane_input(&builder);
+        input.add_box_volume(DtVector::new(2.0, 0.0, 2.0), DtVector::new(8.0, 2.0, 8.0), 5);
+
+        let tile = builder.build_tile(input).unwrap().tile.unwrap();
+        let view = tile.view().unwrap();
+        for poly in view.polys() {
+            assert!(poly.vertex_count <= 3);
+            assert_eq!(build_settings.get_area_flags(poly.area()), poly.flags);
+        }
+        assert!(view.polys().any(|poly| poly.flags == 2));
+
+        // Area ids past 63 are masked like the C side does
+        build_settings.set_area_flags(0x40 | 7, 4);
+        assert_eq!(4, build_settings.get_area_flags(7));
+        assert_eq!(4, build_settings.get_area_flags(0xc7));
+    }
+
+    #[test]
+    fn build_stats() {
+        let mut builder = NavmeshBuilder::new(NavBuildSettings::default(), NavAgentSettings::default());
+        let result = builder.build_tile(plane_input(&builder)).unwrap();
+        let stats = &result.stats;
+        assert!(stats.total > Duration::ZERO);
+        assert!(stats.rasterize <= stats.total && stats.detour <= stats.total);
+        assert_eq!(0, stats.problems().count());
+
+        // Flat bounds fail before recast runs, the log says why
+        let coord = DtInt2::new(0, 0);
+        let mut bounds = NavmeshBuildUtils::calculate_tile_bounding_box(builder.build_settings, coord);
+        bounds.max.y = bounds.min.y;
+        let error = builder.build_tile(TileInputBuilder::new(coord, bounds)).unwrap_err();
+        assert!(error.to_string().contains("Invalid build settings"));
+        let NavError::Build { code, logs } = error else { panic!("expected a build error") };
+        assert_eq!(BuildResultCode::InvalidConfig, code);
+        let errors: Vec<_> = logs.iter().filter(|log| log.level != BuildLogLevel::Progress).collect();
+        assert_eq!(1, errors.len());
+        assert_eq!(BuildLogLevel::Error, errors[0].level);
+
+        // No geometry isn't an error, there's just no tile
+        let bounds = plane_input(&builder).bounds;
+        let result = builder.build_tile(TileInputBuilder::new(coord, bounds)).unwrap();
+        assert!(result.tile.is_none());
+    }
 }
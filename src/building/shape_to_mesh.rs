@@ -20,8 +20,7 @@ impl ShapeToMesh {
             },
             ShapeType::Triangle => {
                 let out = shared_shape.as_triangle().unwrap();
-                let mut triangles:  Vec<(usize, Triangle)> = Vec::new();
-                triangles.push((0, *out));
+                let triangles: Vec<(usize, Triangle)> = vec![(0, *out)];
                 Some(Self::triangles_output_to_mesh(triangles))
             },
             ShapeType::TriMesh => {
@@ -46,7 +45,7 @@ impl ShapeToMesh {
         }
     }
 
-    pub fn weld_vertices(vertices: &Vec<DtVector>, indices: &Vec<u32>) -> (Vec<u32>, Vec<DtVector>) {
+    pub fn weld_vertices(vertices: &[DtVector], indices: &[u32]) -> (Vec<u32>, Vec<DtVector>) {
         let remap_table = meshopt::generate_vertex_remap(vertices, Some(indices));
 
         let remapped_indices = meshopt::remap_index_buffer(Some(indices), remap_table.0, &remap_table.1);
@@ -1,4 +1,6 @@
-use crate::common::{DtVector, DtInt2, DtBoundingBox};
+use crate::common::{DtVector, DtInt2, DtBoundingBox, nav_error::NavError};
+
+use super::{DtConvexVolume, DtOffMeshConnection};
 
 /// Input geometry for a tile.
 pub struct TileInputBuilder {
@@ -6,7 +8,9 @@ pub struct TileInputBuilder {
     pub bounds: DtBoundingBox,
     pub vertices: Vec<DtVector>,
     pub indices: Vec<i32>,
-    pub areas: Vec<u8>
+    pub areas: Vec<u8>,
+    pub off_mesh_connections: Vec<DtOffMeshConnection>,
+    pub convex_volumes: Vec<DtConvexVolume>
 }
 
 impl TileInputBuilder {
@@ -16,7 +20,9 @@ impl TileInputBuilder {
             bounds,
             vertices: Vec::new(),
             indices: Vec::new(),
-            areas: Vec::new()
+            areas: Vec::new(),
+            off_mesh_connections: Vec::new(),
+            convex_volumes: Vec::new()
         }
     }
 
@@ -25,15 +31,14 @@ impl TileInputBuilder {
         let vbase: i32 = self.vertices.len() as i32;
 
         // Copy vertices and expand box if needed
-        for i in 0..vertices.len() {
-            let vertice = vertices[i];
+        for &vertice in vertices {
             self.vertices.push(vertice);
             self.bounds = self.bounds.merge(vertice);
         }
 
         // Copy indices with offset applied
-        for i in 0..indices.len()  {
-            self.indices.push(indices[i] + vbase);
+        for index in indices {
+            self.indices.push(index + vbase);
         }
 
         let triangle_count = indices.len() / 3;
@@ -45,12 +50,32 @@ impl TileInputBuilder {
     pub fn append_triangle(&mut self, vertices: &[DtVector], area: u8) {
         let vbase = self.vertices.len();
 
-        for i in 0..vertices.len() {
-            let vertice = vertices[i];
+        for (i, &vertice) in vertices.iter().enumerate() {
             self.vertices.push(vertice);
             self.indices.push((vbase + i) as i32);
             self.bounds = self.bounds.merge(vertice);
         }
         self.areas.push(area);
     }
-}
\ No newline at end of file
+
+    /// Off-mesh connections are only kept by the tile containing the connection start point
+    pub fn add_off_mesh_connection(&mut self, connection: DtOffMeshConnection) {
+        self.off_mesh_connections.push(connection);
+    }
+
+    /// Marks the area of the surface inside a convex polygon, points in xz order, extruded from hmin to hmax.
+    /// Volumes overlapping several tiles need adding to each of them.  Fails if there are less than 3 or more than 12 points
+    pub fn add_convex_volume(&mut self, points: &[DtVector], hmin: f32, hmax: f32, area: u8) -> Result<(), NavError> {
+        self.convex_volumes.push(DtConvexVolume::polygon(points, hmin, hmax, area)?);
+        Ok(())
+    }
+
+    /// position is the bottom center
+    pub fn add_cylinder_volume(&mut self, position: DtVector, radius: f32, height: f32, area: u8) {
+        self.convex_volumes.push(DtConvexVolume::cylinder(position, radius, height, area));
+    }
+
+    pub fn add_box_volume(&mut self, min: DtVector, max: DtVector, area: u8) {
+        self.convex_volumes.push(DtConvexVolume::aabb(min, max, area));
+    }
+}
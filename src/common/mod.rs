@@ -4,6 +4,14 @@ use rapier3d_f64::prelude::{Point, Real};
 
 pub mod navmesh;
 pub mod navmesh_tile;
+pub mod navmesh_set;
+pub mod nav_error;
+
+/// dtPolyRef, recast is built with 64 bit poly refs
+pub type PolyRef = u64;
+
+/// Max vertices per navmesh polygon recast is compiled with
+pub const DT_VERTS_PER_POLYGON: usize = 6;
 
 pub fn dt_align4(size: i32) -> i32 {
     (size + 3) & !3
@@ -53,7 +61,7 @@ impl ops::Add<DtVector2> for DtVector2 {
     }
 }
 
-#[derive(Clone, Copy, Default, Debug)]
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
 #[repr(C)]
 pub struct DtVector {
     pub x: f32,
@@ -120,7 +128,7 @@ impl ops::Sub<DtVector> for DtVector {
     }
 }
 
-#[derive(Clone, Copy, Hash, Eq, Debug)]
+#[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
 #[repr(C)]
 pub struct DtInt2 {
     pub x: i32,
@@ -137,14 +145,7 @@ impl DtInt2 {
     }
 }
 
-impl PartialEq for DtInt2 {
-    fn eq(&self, other: &Self) -> bool {
-        self.x == other.x
-        && self.y == other.y
-    }
-}
-
-#[derive(Clone, Copy, Default, Debug)]
+#[derive(Clone, Copy, Debug)]
 pub struct NavAgentSettings {
     /// The height of the entities in this group. Entities can't enter areas with ceilings lower than this value.
     pub height: f32,
@@ -159,8 +160,8 @@ pub struct NavAgentSettings {
     pub radius: f32
 }
 
-impl NavAgentSettings {
-    pub fn default() -> Self {
+impl Default for NavAgentSettings {
+    fn default() -> Self {
         NavAgentSettings {
             height: 2.0,
             max_climb: 0.4,
@@ -188,19 +189,34 @@ pub struct DtTileHeader {
     pub bv_node_count: i32,
     pub off_mesh_con_count: i32,
     pub off_mesh_base: i32,
-    pub walkable_height: i32,
-    pub walkable_radius: i32,
-    pub walkable_climb: i32,
+    pub walkable_height: f32,
+    pub walkable_radius: f32,
+    pub walkable_climb: f32,
     pub bmin: [f32;3],
     pub bmax: [f32; 3],
     pub bv_quant_factor: f32
 }
 
+/// dtNavMeshParams
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[repr(C)]
+pub struct DtNavMeshParams {
+    pub origin: [f32; 3],
+    pub tile_width: f32,
+    pub tile_height: f32,
+    pub max_tiles: i32,
+    pub max_polys: i32
+}
+
 pub struct DtArea {}
 
 impl DtArea {
     pub const NULL: u8 = 0;
     pub const WALKABLE: u8 = 63;
+    /// Area ids must be less than this
+    pub const MAX_AREAS: usize = 64;
+    /// Detour stores area ids in 6 bits
+    pub const MASK: u8 = 0x3f;
 }
 
 
@@ -222,17 +238,11 @@ impl DtBoundingBox {
     }
 
     pub fn merge(&self, point: DtVector) -> Self {
-        let mut result = Self::default();
-        result.min = self.min.min(point);
-        result.max = self.max.max(point);
-        result
+        DtBoundingBox::new(self.min.min(point), self.max.max(point))
     }
 
     pub fn merge_box(&self, rhs: DtBoundingBox) -> Self {
-        let mut result = Self::default();
-        result.min = self.min.min(rhs.min);
-        result.max = self.max.max(rhs.max);
-        result
+        DtBoundingBox::new(self.min.min(rhs.min), self.max.max(rhs.max))
     }
 
     pub fn intersects(&self, rhs: DtBoundingBox) -> bool {
@@ -0,0 +1,205 @@
+use std::{fmt, io};
+
+use crate::building::navmesh_builder::{BuildLogLevel, BuildLogMessage, BuildResultCode};
+
+use super::{navmesh_tile::TileParseError, DtInt2};
+
+/// dtStatus.  The high bits say whether the call failed, succeeded or is in progress, the low bits detail why
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Debug)]
+#[repr(transparent)]
+pub struct DtStatus(pub u32);
+
+impl DtStatus {
+    pub const FAILURE: u32 = 1 << 31;
+    pub const SUCCESS: u32 = 1 << 30;
+    pub const IN_PROGRESS: u32 = 1 << 29;
+
+    pub const WRONG_MAGIC: u32 = 1 << 0;
+    pub const WRONG_VERSION: u32 = 1 << 1;
+    pub const OUT_OF_MEMORY: u32 = 1 << 2;
+    pub const INVALID_PARAM: u32 = 1 << 3;
+    pub const BUFFER_TOO_SMALL: u32 = 1 << 4;
+    pub const OUT_OF_NODES: u32 = 1 << 5;
+    pub const PARTIAL_RESULT: u32 = 1 << 6;
+    pub const ALREADY_OCCUPIED: u32 = 1 << 7;
+    /// Not a detour detail.  Set by the C side when no polygon was found within the search extent
+    pub const NO_NEAREST_POLY: u32 = 1 << 16;
+
+    pub const DETAIL_MASK: u32 = 0x0ff_ffff;
+
+    const DETAIL_NAMES: [(u32, &'static str); 9] = [
+        (Self::WRONG_MAGIC, "wrong magic"),
+        (Self::WRONG_VERSION, "wrong version"),
+        (Self::OUT_OF_MEMORY, "out of memory"),
+        (Self::INVALID_PARAM, "invalid param"),
+        (Self::BUFFER_TOO_SMALL, "buffer too small"),
+        (Self::OUT_OF_NODES, "out of nodes"),
+        (Self::PARTIAL_RESULT, "partial result"),
+        (Self::ALREADY_OCCUPIED, "already occupied"),
+        (Self::NO_NEAREST_POLY, "no polygon near position")
+    ];
+
+    pub fn failed(&self) -> bool {
+        self.0 & Self::FAILURE != 0
+    }
+
+    pub fn succeeded(&self) -> bool {
+        self.0 & Self::SUCCESS != 0
+    }
+
+    pub fn in_progress(&self) -> bool {
+        self.0 & Self::IN_PROGRESS != 0
+    }
+
+    pub fn detail(&self) -> u32 {
+        self.0 & Self::DETAIL_MASK
+    }
+
+    /// detail is one of the detail constants
+    pub fn has_detail(&self, detail: u32) -> bool {
+        self.0 & detail != 0
+    }
+
+    /// Err(NavError::Status) if the status failed
+    pub fn to_result(self) -> Result<DtStatus, NavError> {
+        if self.failed() {
+            Err(NavError::Status(self))
+        } else {
+            Ok(self)
+        }
+    }
+}
+
+impl fmt::Display for DtStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.failed() {
+            write!(f, "failure")?;
+        } else if self.in_progress() {
+            write!(f, "in progress")?;
+        } else {
+            write!(f, "success")?;
+        }
+
+        let details: Vec<&str> = Self::DETAIL_NAMES.iter().filter(|(detail, _)| self.has_detail(*detail)).map(|(_, name)| *name).collect();
+        if !details.is_empty() {
+            write!(f, " ({})", details.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Errors returned across the crate
+#[derive(Clone, Debug, PartialEq)]
+pub enum NavError {
+    /// A detour call failed, the status has the detail bits
+    Status(DtStatus),
+    /// Mutating the navmesh needs every query back in the pool
+    QueriesInUse,
+    /// Allocating or initializing the named object on the C side failed
+    CreateFailed(&'static str),
+    /// A tile build failed.  logs is recast's log for the build
+    Build { code: BuildResultCode, logs: Vec<BuildLogMessage> },
+    InvalidTile(TileParseError),
+    TileNotFound(DtInt2),
+    /// A tile cache was handed a navmesh other than the one it was created for
+    WrongNavmesh,
+    /// The index is outside the crowd's agent slots
+    InvalidAgent(i32),
+    /// Every crowd agent slot is in use
+    CrowdFull,
+    InvalidParam(&'static str),
+    /// The PathService was dropped before the request ran
+    ServiceStopped,
+    /// Gathering geometry or building a NavmeshBuildJob tile panicked, with the panic message
+    BuildPanicked(String)
+}
+
+impl NavError {
+    /// The detour status if this is a Status error
+    pub fn status(&self) -> Option<DtStatus> {
+        match self {
+            NavError::Status(status) => Some(*status),
+            _ => None
+        }
+    }
+
+    /// True for a failed detour call with the given detail bit, eg DtStatus::OUT_OF_NODES
+    pub fn has_detail(&self, detail: u32) -> bool {
+        matches!(self.status(), Some(status) if status.has_detail(detail))
+    }
+}
+
+impl fmt::Display for NavError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NavError::Status(status) => write!(f, "detour {}", status),
+            NavError::QueriesInUse => write!(f, "navmesh queries are rented out of the pool"),
+            NavError::CreateFailed(name) => write!(f, "failed to create {}", name),
+            NavError::Build { code, logs } => {
+                write!(f, "tile build failed with {:?}", code)?;
+                if let Some(log) = logs.iter().find(|log| log.level == BuildLogLevel::Error) {
+                    write!(f, ": {}", log.message)?;
+                }
+                Ok(())
+            },
+            NavError::InvalidTile(error) => write!(f, "invalid tile data: {}", error),
+            NavError::TileNotFound(coord) => write!(f, "no tile at {}, {}", coord.x, coord.y),
+            NavError::WrongNavmesh => write!(f, "navmesh is not the one the tile cache was created for"),
+            NavError::InvalidAgent(idx) => write!(f, "no crowd agent slot at index {}", idx),
+            NavError::CrowdFull => write!(f, "crowd has no free agent slots"),
+            NavError::InvalidParam(message) => write!(f, "invalid param: {}", message),
+            NavError::ServiceStopped => write!(f, "path service stopped before the request ran"),
+            NavError::BuildPanicked(message) => write!(f, "tile build panicked: {}", message)
+        }
+    }
+}
+
+impl std::error::Error for NavError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            NavError::InvalidTile(error) => Some(error),
+            _ => None
+        }
+    }
+}
+
+impl From<TileParseError> for NavError {
+    fn from(error: TileParseError) -> Self {
+        NavError::InvalidTile(error)
+    }
+}
+
+impl From<BuildResultCode> for NavError {
+    fn from(code: BuildResultCode) -> Self {
+        NavError::Build { code, logs: Vec::new() }
+    }
+}
+
+impl From<NavError> for io::Error {
+    fn from(error: NavError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DtStatus, NavError};
+
+    #[test]
+    fn status_details() {
+        let status = DtStatus(DtStatus::FAILURE | DtStatus::OUT_OF_MEMORY | DtStatus::INVALID_PARAM);
+        assert!(status.failed() && !status.succeeded());
+        assert!(status.has_detail(DtStatus::OUT_OF_MEMORY));
+        assert!(!status.has_detail(DtStatus::PARTIAL_RESULT));
+        assert_eq!("failure (out of memory, invalid param)", status.to_string());
+
+        let error = status.to_result().unwrap_err();
+        assert!(error.has_detail(DtStatus::INVALID_PARAM));
+        assert_eq!("detour failure (out of memory, invalid param)", error.to_string());
+
+        let status = DtStatus(DtStatus::SUCCESS | DtStatus::BUFFER_TOO_SMALL);
+        assert_eq!(Ok(status), status.to_result());
+        assert_eq!("success (buffer too small)", status.to_string());
+        assert!(!NavError::QueriesInUse.has_detail(DtStatus::BUFFER_TOO_SMALL));
+    }
+}
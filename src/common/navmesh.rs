@@ -1,42 +1,67 @@
 
-use std::{sync::Arc};
+use std::{mem::ManuallyDrop, ops::{Deref, DerefMut}, sync::{Arc, Condvar, Mutex, atomic::{self, AtomicUsize, Ordering}}, io::{self, Read, Write}, ptr, slice, time::{Duration, Instant}};
 
 use crossbeam::queue::ArrayQueue;
 use rustc_hash::{FxHashSet};
 
-use crate::{bindings::{CreateNavmesh, DestroyNavmesh, RemoveTile, AddTile, RawNavmeshPtr}, queries::nav_query::NavQuery, building::NavBuildSettings};
+use crate::{bindings::{CreateNavmesh, CreateNavmeshWithParams, DestroyNavmesh, GetNavmeshParams, RemoveTile, GetTilePolyRefBase, AddTile, AddTileWithRef, GetTileData, RawNavmeshPtr}, queries::nav_query::NavQuery, building::NavBuildSettings, crowd::crowd::Crowd};
 
-use super::{DtInt2, navmesh_tile::NavmeshTile};
+use super::{DtInt2, DtNavMeshParams, PolyRef, navmesh_tile::NavmeshTile, navmesh_set::{NavMeshSetHeader, NavMeshTileHeader, invalid_data}, nav_error::NavError};
 
 
-struct NavmeshPtr(*mut RawNavmeshPtr);
+/// Owns the C navmesh.  Queries, crowds and tile caches hold a clone so the navmesh is destroyed after the last of them
+pub(crate) struct NavmeshPtr(*mut RawNavmeshPtr);
 unsafe impl Send for NavmeshPtr {}
+unsafe impl Sync for NavmeshPtr {}
+
+impl NavmeshPtr {
+    pub(crate) fn raw(&self) -> *mut RawNavmeshPtr {
+        self.0
+    }
+}
+
+impl Drop for NavmeshPtr {
+    fn drop(&mut self) {
+        unsafe {DestroyNavmesh(self.0)};
+    }
+}
 
 pub struct NavQueryPool {
     queries: ArrayQueue<NavQuery>,
-    initialized_size: usize
+    initialized_size: usize,
+    /// Drains waiting for or holding every query, see drain
+    drains: AtomicUsize,
+    /// Crowd updates in progress, drains wait for these like rented queries
+    crowd_updates: AtomicUsize,
+    returned: Mutex<()>,
+    returned_signal: Condvar
 }
 
 impl NavQueryPool {
-    fn new(size: usize, navmesh_ptr: *mut RawNavmeshPtr, max_nodes: i32, max_path_points: i32) -> Option<Self> {
+    fn new(size: usize, navmesh_ptr: &Arc<NavmeshPtr>, max_nodes: i32, max_path_points: i32) -> Result<Self, NavError> {
         let pool = NavQueryPool {
             queries: ArrayQueue::new(size),
-            initialized_size: size
+            initialized_size: size,
+            drains: AtomicUsize::new(0),
+            crowd_updates: AtomicUsize::new(0),
+            returned: Mutex::new(()),
+            returned_signal: Condvar::new()
         };
         for _ in 0..size {
-            if let Some(query) = NavQuery::new(navmesh_ptr, max_nodes, max_path_points) {
-                pool.queries.push(query).unwrap_or_default();
-            } else {
-                return None;
-            }
-            
+            let query = NavQuery::new(navmesh_ptr.clone(), max_nodes, max_path_points)?;
+            pool.queries.push(query).unwrap_or_default();
         }
-        Some(pool)
+        Ok(pool)
     }
 
     pub fn is_full(&self) -> bool {
         self.queries.len() == self.initialized_size
     }
+
+    /// Queries rented out of the pool, leased or popped
+    pub fn outstanding(&self) -> usize {
+        self.initialized_size - self.queries.len()
+    }
    
     pub fn clear(&self) {
         while let Some(query) = self.queries.pop() {
@@ -44,12 +69,147 @@ impl NavQueryPool {
         }
     }
 
+    /// A query that goes back to the pool when the guard is dropped, including on panic.  None if the pool is empty or draining
+    /// The guard can outlive the Navmesh, the query keeps the C navmesh alive
+    pub fn lease(self: &Arc<Self>) -> Option<NavQueryGuard> {
+        self.pop().map(|query| NavQueryGuard { query: ManuallyDrop::new(query), pool: self.clone() })
+    }
+
+    /// Same as lease but waits up to timeout for a query to be returned
+    pub fn lease_timeout(self: &Arc<Self>, timeout: Duration) -> Option<NavQueryGuard> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            // Not under the returned lock, pop pushes the query back and locks it when it loses a race with a drain
+            if let Some(guard) = self.lease() {
+                return Some(guard);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return None;
+            }
+            // Pushes and drain ends notify while holding the lock, so checking again under it can't miss a wakeup
+            let lock = self.returned.lock().unwrap_or_else(|error| error.into_inner());
+            if self.queries.is_empty() || self.is_draining() {
+                drop(self.returned_signal.wait_timeout(lock, deadline - now).unwrap_or_else(|error| error.into_inner()));
+            }
+        }
+    }
+
+    /// None if the pool is empty or draining.  Prefer lease, a popped query has to be pushed back by hand
     pub fn pop(&self) -> Option<NavQuery> {
-        self.queries.pop()
+        if self.is_draining() {
+            return None;
+        }
+        let query = self.queries.pop()?;
+        // A drain may have started between the check and the pop, and seen the pool full.  Take the query first and check again,
+        // drain counts itself before checking the pool so one of them always sees the other
+        atomic::fence(Ordering::SeqCst);
+        if self.is_draining() {
+            self.push(query);
+            return None;
+        }
+        Some(query)
     }
 
     pub fn push(&self, query: NavQuery) {
         self.queries.push(query).unwrap_or_default();
+        let _lock = self.returned.lock().unwrap_or_else(|error| error.into_inner());
+        self.returned_signal.notify_all();
+    }
+
+    /// True while a drain is waiting for or holding the queries.  Long lived query holders like PathService
+    /// check this and push their queries back so the navmesh can be mutated
+    pub fn is_draining(&self) -> bool {
+        self.drains.load(Ordering::SeqCst) > 0
+    }
+
+    /// Crowds hold this for the duration of an update.  None while draining, the crowd skips the update
+    pub(crate) fn begin_crowd_update(&self) -> Option<CrowdUpdateGuard<'_>> {
+        // Counted before checking for a drain, and drain counts itself before checking for updates, so one of them always backs off
+        self.crowd_updates.fetch_add(1, Ordering::SeqCst);
+        let guard = CrowdUpdateGuard { pool: self };
+        if self.is_draining() {
+            return None;
+        }
+        Some(guard)
+    }
+
+    fn is_idle(&self) -> bool {
+        self.is_full() && self.crowd_updates.load(Ordering::SeqCst) == 0
+    }
+
+    /// Stops handing out queries and waits up to timeout for every query to be pushed back and running crowd updates to finish.
+    /// Fails with QueriesInUse on timeout.  pop returns None and crowds skip updates until the returned drain is dropped
+    pub fn drain(self: &Arc<Self>, timeout: Duration) -> Result<QueryPoolDrain, NavError> {
+        self.drains.fetch_add(1, Ordering::SeqCst);
+        let drain = QueryPoolDrain { pool: self.clone() };
+        // Pairs with the fence in pop
+        atomic::fence(Ordering::SeqCst);
+
+        let deadline = Instant::now() + timeout;
+        let mut lock = self.returned.lock().unwrap_or_else(|error| error.into_inner());
+        while !self.is_idle() {
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(NavError::QueriesInUse);
+            }
+            lock = self.returned_signal.wait_timeout(lock, deadline - now).unwrap_or_else(|error| error.into_inner()).0;
+        }
+        Ok(drain)
+    }
+}
+
+/// Returned by NavQueryPool::drain.  Queries are handed out again once every drain is dropped
+pub struct QueryPoolDrain {
+    pool: Arc<NavQueryPool>
+}
+
+impl Drop for QueryPoolDrain {
+    fn drop(&mut self) {
+        self.pool.drains.fetch_sub(1, Ordering::SeqCst);
+        // Wake lease_timeout waiters
+        let _lock = self.pool.returned.lock().unwrap_or_else(|error| error.into_inner());
+        self.pool.returned_signal.notify_all();
+    }
+}
+
+pub(crate) struct CrowdUpdateGuard<'a> {
+    pool: &'a NavQueryPool
+}
+
+impl<'a> Drop for CrowdUpdateGuard<'a> {
+    fn drop(&mut self) {
+        self.pool.crowd_updates.fetch_sub(1, Ordering::SeqCst);
+        let _lock = self.pool.returned.lock().unwrap_or_else(|error| error.into_inner());
+        self.pool.returned_signal.notify_all();
+    }
+}
+
+/// A NavQuery leased from a NavQueryPool, see NavQueryPool::lease
+pub struct NavQueryGuard {
+    /// Moved back into the pool on drop
+    query: ManuallyDrop<NavQuery>,
+    pool: Arc<NavQueryPool>
+}
+
+impl Deref for NavQueryGuard {
+    type Target = NavQuery;
+
+    fn deref(&self) -> &NavQuery {
+        &self.query
+    }
+}
+
+impl DerefMut for NavQueryGuard {
+    fn deref_mut(&mut self) -> &mut NavQuery {
+        &mut self.query
+    }
+}
+
+impl Drop for NavQueryGuard {
+    fn drop(&mut self) {
+        let query = unsafe { ManuallyDrop::take(&mut self.query) };
+        self.pool.push(query);
     }
 }
 
@@ -60,8 +220,9 @@ pub struct NavmeshSettings {
     pub map_size: f32,
     pub query_pool_size: usize,
     pub query_max_nodes: i32,
-    pub query_max_path_points: i32
-    
+    pub query_max_path_points: i32,
+    /// How long tile changes wait for queries to be pushed back to the pool before failing with QueriesInUse
+    pub query_drain_timeout: Duration
 }
 
 impl NavmeshSettings {
@@ -72,7 +233,8 @@ impl NavmeshSettings {
             map_size,
             query_pool_size,
             query_max_nodes,
-            query_max_path_points
+            query_max_path_points,
+            query_drain_timeout: Duration::from_millis(100)
         }
     }
 
@@ -83,29 +245,33 @@ impl NavmeshSettings {
             map_size,
             query_pool_size,
             query_max_nodes: 4096,
-            query_max_path_points: 512
+            query_max_path_points: 512,
+            query_drain_timeout: Duration::from_millis(100)
         }
     }
 }
 
 /// https://groups.google.com/g/recastnavigation/c/irmJ5uonNnM  - The gist of thread safety in recast
-
+///
 /// navmesh queries have pointers into the navmesh data on the C side.  So it's not safe to run queries while mutating the navmesh (adding/removing tiles)
 /// Building tiles is completely separate and can be done from any/multiple threads.
-
+///
 /// There isn't really a good way to wrap this safely without compromises.  And performance is paramount.
 /// Requiring mutexes is too much of a perf hit.
 /// Taking the result path vector out of the query would result in not needing mutable access.  But that complicates the api forcing you to manage a pool of path result vectors.
 /// Moving the navmesh and it's queries in and out of an Arc and having a notion of an update/query mode works. But I felt that was overly complex here.
 /// So we just do simple reference counting.  Mutating functions on the navmesh require all the allocated queries to be in the pool.  Ie not in use.
+/// They drain the pool first, waiting up to query_drain_timeout for queries to come back.
 /// 
-/// So the usage pattern for parallel access is you clone the arc wrapped query pool, then take/return queries as needed.
-
-/// Queries should be destroyed on the C++ side before the navmesh is destroyed.  Our drop implementation handles that
-
+/// So the usage pattern for parallel access is you clone the arc wrapped query pool, then lease queries as needed.
+/// Or hand the pool to a PathService which gives its queries back whenever the pool drains.
+///
+/// Queries have to be destroyed on the C++ side before the navmesh is destroyed.  Each query holds the C navmesh, so it's
+/// destroyed after the last query, crowd or tile cache using it, even if the pool outlives the Navmesh
 pub struct Navmesh {
-    navmesh_ptr: NavmeshPtr,
+    navmesh_ptr: Arc<NavmeshPtr>,
     tile_coords: FxHashSet<DtInt2>,
+    query_drain_timeout: Duration,
     pub query_pool: Arc<NavQueryPool>
 }
 
@@ -130,94 +296,409 @@ impl Navmesh {
         (Self::ceil_pow2(max_tiles) as f32).log(2.0) as i32
     }
 
-    pub fn new(settings: NavmeshSettings) -> Option<Self> {
+    pub fn new(settings: NavmeshSettings) -> Result<Self, NavError> {
         let tile_bits = Self::calculate_max_tile_bits(settings.tile_size, settings.cell_size, settings.map_size);
         let poly_bits = 8; // 256 polys per tile.  20 max.
 
         let cell_tile_size = settings.tile_size as f32 * settings.cell_size;
 
         let ptr = unsafe {CreateNavmesh(cell_tile_size, tile_bits, poly_bits)};
+        Self::from_raw(ptr, settings)
+    }
+
+    fn from_raw(ptr: *mut RawNavmeshPtr, settings: NavmeshSettings) -> Result<Self, NavError> {
         if ptr.is_null() {
-            return None;
+            return Err(NavError::CreateFailed("navmesh"));
         }
 
-        let navmesh_ptr = NavmeshPtr(ptr);
-        let query_pool = NavQueryPool::new(settings.query_pool_size, navmesh_ptr.0, settings.query_max_nodes, settings.query_max_path_points);
-        
-        let query_pool = Arc::new(query_pool.unwrap());
+        let navmesh_ptr = Arc::new(NavmeshPtr(ptr));
+        let query_pool = Arc::new(NavQueryPool::new(settings.query_pool_size, &navmesh_ptr, settings.query_max_nodes, settings.query_max_path_points)?);
 
         let navmesh = Navmesh {
             navmesh_ptr,
             tile_coords: FxHashSet::default(),
+            query_drain_timeout: settings.query_drain_timeout,
             query_pool
         };
 
-        Some(navmesh)
+        Ok(navmesh)
     }
 
-    pub fn raw_ptr(&self) -> *mut RawNavmeshPtr {
-        self.navmesh_ptr.0
+    pub fn params(&self) -> DtNavMeshParams {
+        let mut params = DtNavMeshParams::default();
+        unsafe {GetNavmeshParams(self.navmesh_ptr.0, &mut params as *mut DtNavMeshParams)};
+        params
     }
 
-    pub fn add_or_replace_tile(&mut self, mut tile: NavmeshTile) -> bool {
-        if !self.query_pool.is_full() {
-            return false;
+    /// The poly ref of the tile's first poly.  Poly refs in the tile are this or'ed with the poly index
+    pub fn get_tile_base_ref(&self, coord: &DtInt2) -> Option<PolyRef> {
+        let base_ref = unsafe {GetTilePolyRefBase(self.navmesh_ptr.0, coord as *const DtInt2)};
+        if base_ref == 0 {
+            None
+        } else {
+            Some(base_ref)
         }
+    }
 
-        let coord = tile.coord();
-        self.remove_tile_internal(&coord);
-        self.tile_coords.insert(coord);
-
+    /// The tile ref and the data detour holds for the tile at coord
+    fn tile_data(&self, coord: &DtInt2) -> Option<(u64, &[u8])> {
+        let mut data: *const u8 = ptr::null();
+        let mut data_length = 0;
+        let mut tile_ref = 0;
         unsafe {
-            AddTile(self.navmesh_ptr.0, tile.data.as_mut_ptr() as *mut u8, tile.data.len() as i32) == 1
+            if GetTileData(self.navmesh_ptr.0, coord as *const DtInt2, &mut data, &mut data_length, &mut tile_ref) == 1 {
+                Some((tile_ref, slice::from_raw_parts(data, data_length as usize)))
+            } else {
+                None
+            }
         }
     }
 
-    pub fn remove_tile(&mut self, coord: &DtInt2) -> bool {
-        if !self.query_pool.is_full() {
-            return false;
+    /// Writes all tiles in the RecastDemo "MSET" format, see navmesh_set
+    pub fn save_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut coords: Vec<DtInt2> = self.tile_coords.iter().copied().collect();
+        coords.sort_by_key(|coord| (coord.y, coord.x));
+
+        let tiles: Vec<(u64, &[u8])> = coords.iter().filter_map(|coord| self.tile_data(coord)).collect();
+
+        NavMeshSetHeader::new(tiles.len() as i32, self.params()).write(writer)?;
+        for (tile_ref, data) in tiles {
+            NavMeshTileHeader { tile_ref, data_size: data.len() as i32 }.write(writer)?;
+            writer.write_all(data)?;
         }
-        self.remove_tile_internal(coord)
+        Ok(())
+    }
+
+    /// Creates a navmesh from data written by save_to or RecastDemo.  The navmesh params come from the file,
+    /// settings supplies the query pool and must have the same tile width (tile_size * cell_size) the file was built with.
+    pub fn load_from<R: Read>(reader: &mut R, settings: NavmeshSettings) -> io::Result<Self> {
+        let header = NavMeshSetHeader::read(reader)?;
+
+        let cell_tile_size = settings.tile_size as f32 * settings.cell_size;
+        if (header.params.tile_width - cell_tile_size).abs() > 0.001 {
+            return Err(invalid_data("navmesh set tile width does not match settings"));
+        }
+
+        let ptr = unsafe {CreateNavmeshWithParams(&header.params as *const DtNavMeshParams)};
+        let mut navmesh = Self::from_raw(ptr, settings)?;
+
+        for _ in 0..header.num_tiles {
+            let tile_header = NavMeshTileHeader::read(reader)?;
+            if tile_header.tile_ref == 0 || tile_header.data_size <= 0 {
+                break;
+            }
+
+            // Grow the buffer as data arrives instead of trusting data_size up front
+            let mut tile = NavmeshTile { data: Vec::new() };
+            reader.by_ref().take(tile_header.data_size as u64).read_to_end(&mut tile.data)?;
+            if tile.data.len() != tile_header.data_size as usize {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "navmesh set tile data truncated"));
+            }
+            let coord = tile.view().map_err(NavError::from)?.coord();
+
+            unsafe {AddTileWithRef(navmesh.navmesh_ptr.0, tile.data.as_mut_ptr(), tile.data.len() as i32, tile_header.tile_ref)}.to_result()?;
+            navmesh.tile_coords.insert(coord);
+        }
+
+        Ok(navmesh)
     }
 
-    fn remove_tile_internal(&mut self, coord: &DtInt2) -> bool {
-        if self.tile_coords.remove(coord) {
-            unsafe {
-                if RemoveTile(self.navmesh_ptr.0, coord as *const DtInt2) == 1 {
-                    self.tile_coords.remove(coord);
-                    return true;
+    pub fn raw_ptr(&self) -> *mut RawNavmeshPtr {
+        self.navmesh_ptr.0
+    }
+
+    /// The owned C navmesh, for objects that point into it and have to keep it alive
+    pub(crate) fn shared_ptr(&self) -> &Arc<NavmeshPtr> {
+        &self.navmesh_ptr
+    }
+
+    /// Creates a crowd on this navmesh.  The crowd keeps the C navmesh alive, so it can outlive the Navmesh.
+    /// Crowd updates count toward the query pool drain, tile changes wait for a running update and updates are skipped while tiles change
+    pub fn create_crowd(&self, max_agents: i32, max_agent_radius: f32) -> Result<Crowd, NavError> {
+        Crowd::new(self.navmesh_ptr.clone(), self.query_pool.clone(), max_agents, max_agent_radius)
+    }
+
+    /// Fails with QueriesInUse if queries are still rented out after query_drain_timeout, InvalidTile if the tile data doesn't parse,
+    /// or the detour status if detour rejects the tile.  A rejected replacement puts the old tile back with its old tile ref
+    pub fn add_or_replace_tile(&mut self, mut tile: NavmeshTile) -> Result<(), NavError> {
+        let _drain = self.drain_queries()?;
+
+        let coord = tile.view()?.coord();
+        let old_tile = self.tile_data(&coord).map(|(tile_ref, data)| (tile_ref, data.to_vec()));
+        if old_tile.is_some() {
+            self.remove_tile_internal(&coord)?;
+        }
+
+        let status = unsafe { AddTile(self.navmesh_ptr.0, tile.data.as_mut_ptr(), tile.data.len() as i32) };
+        if let Err(error) = status.to_result() {
+            if let Some((tile_ref, mut data)) = old_tile {
+                if unsafe { AddTileWithRef(self.navmesh_ptr.0, data.as_mut_ptr(), data.len() as i32, tile_ref) }.to_result().is_ok() {
+                    self.tile_coords.insert(coord);
                 }
             }
+            return Err(error);
         }
-        false
+        self.tile_coords.insert(coord);
+        Ok(())
     }
-    
-}
 
-impl Drop for Navmesh {
-    fn drop(&mut self) {
-        self.query_pool.clear();
-        unsafe {DestroyNavmesh(self.navmesh_ptr.0)};
+    pub fn remove_tile(&mut self, coord: &DtInt2) -> Result<(), NavError> {
+        let _drain = self.drain_queries()?;
+        self.remove_tile_internal(coord)
     }
+
+    /// Hold the drain while changing tiles
+    pub(crate) fn drain_queries(&self) -> Result<QueryPoolDrain, NavError> {
+        self.query_pool.drain(self.query_drain_timeout)
+    }
+
+    /// Tiles the tile cache builds are added on the C side.  Keeps tile_coords matching what's in the navmesh
+    pub(crate) fn sync_tile_coord(&mut self, coord: DtInt2) {
+        if self.get_tile_base_ref(&coord).is_some() {
+            self.tile_coords.insert(coord);
+        } else {
+            self.tile_coords.remove(&coord);
+        }
+    }
+
+    pub(crate) fn remove_tile_internal(&mut self, coord: &DtInt2) -> Result<(), NavError> {
+        if !self.tile_coords.contains(coord) {
+            return Err(NavError::TileNotFound(*coord));
+        }
+        unsafe {
+            RemoveTile(self.navmesh_ptr.0, coord as *const DtInt2).to_result()?;
+        }
+        self.tile_coords.remove(coord);
+        Ok(())
+    }
+    
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{building::{navmesh_builder::NavmeshBuilder, NavBuildSettings}, common::navmesh::NavmeshSettings};
+    use std::{io::{self, Cursor}, mem, panic::{self, AssertUnwindSafe}, sync::{Arc, mpsc, atomic::{AtomicBool, AtomicUsize, Ordering}}, thread, time::Duration};
+
+    use crate::{building::{navmesh_builder::NavmeshBuilder, NavBuildSettings}, common::{nav_error::{DtStatus, NavError}, navmesh::NavmeshSettings, navmesh_set::NAVMESHSET_MAGIC, DtInt2, DtTileHeader, DtVector}, queries::{NavQuerySettings, NavQueryFilter}};
 
     use super::Navmesh;
 
 
     #[test]
     fn create_drop() {
-        let result = NavmeshBuilder::build_test_tile(30.0);
+        let result = NavmeshBuilder::build_test_tile(30.0).unwrap();
         let tile = result.tile.unwrap();
 
         let build_settings = NavBuildSettings::default();
         let navmesh_settings = NavmeshSettings::default(build_settings, 2048.0, 10);
         let mut navmesh = Navmesh::new(navmesh_settings).unwrap();
-        assert!(navmesh.add_or_replace_tile(tile));
+        navmesh.add_or_replace_tile(tile).unwrap();
        
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn failed_replace_keeps_old_tile() {
+        let tile = NavmeshBuilder::build_test_tile(30.0).unwrap().tile.unwrap();
+        let mut layer_one = tile.clone();
+        layer_one.data[mem::offset_of!(DtTileHeader, layer)..][..4].copy_from_slice(&1i32.to_ne_bytes());
+
+        let build_settings = NavBuildSettings::default();
+        let navmesh_settings = NavmeshSettings::default(build_settings, 2048.0, 10);
+        let mut navmesh = Navmesh::new(navmesh_settings).unwrap();
+        navmesh.add_or_replace_tile(layer_one.clone()).unwrap();
+        navmesh.add_or_replace_tile(tile).unwrap();
+        let coord = DtInt2::new(0, 0);
+        let base_ref = navmesh.get_tile_base_ref(&coord).unwrap();
+
+        // Replacing the layer 0 tile with another layer 1 tile removes it, then detour rejects the new one as occupied
+        let error = navmesh.add_or_replace_tile(layer_one).unwrap_err();
+        assert!(error.has_detail(DtStatus::ALREADY_OCCUPIED));
+        assert_eq!(Some(base_ref), navmesh.get_tile_base_ref(&coord));
+
+        let mut query = navmesh.query_pool.lease().unwrap();
+        let (path_len, _) = query.find_path(NavQuerySettings::default(), DtVector::new(1.0, 1.0, 1.0), DtVector::new(15.0, 1.0, 12.0), &NavQueryFilter::default()).unwrap();
+        assert!(path_len > 0);
+        drop(query);
+
+        navmesh.remove_tile(&coord).unwrap();
+        assert_eq!(Err(NavError::TileNotFound(coord)), navmesh.remove_tile(&coord));
+    }
+
+    #[test]
+    fn save_load() {
+        let result = NavmeshBuilder::build_test_tile(30.0).unwrap();
+        let tile = result.tile.unwrap();
+
+        let build_settings = NavBuildSettings::default();
+        let navmesh_settings = NavmeshSettings::default(build_settings, 2048.0, 10);
+        let mut navmesh = Navmesh::new(navmesh_settings).unwrap();
+        navmesh.add_or_replace_tile(tile.clone()).unwrap();
+
+        let mut bytes: Vec<u8> = Vec::new();
+        navmesh.save_to(&mut bytes).unwrap();
+        assert_eq!(NAVMESHSET_MAGIC.to_le_bytes(), bytes[0..4]);
+        assert_eq!(40 + 16 + tile.data.len(), bytes.len());
+
+        let loaded = Navmesh::load_from(&mut Cursor::new(&bytes), navmesh_settings).unwrap();
+        assert_eq!(navmesh.params(), loaded.params());
+
+        // Same tile refs, so saving again gives identical bytes
+        let mut resaved: Vec<u8> = Vec::new();
+        loaded.save_to(&mut resaved).unwrap();
+        assert_eq!(bytes, resaved);
+
+        let mut query = loaded.query_pool.pop().unwrap();
+        let (path_len, _) = query.find_path(NavQuerySettings::default(), DtVector::new(1.0, 1.0, 8.0), DtVector::new(4.0, 1.0, 16.0), &NavQueryFilter::default()).unwrap();
+        assert!(path_len > 0);
+        loaded.query_pool.push(query);
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] = 0;
+        assert!(Navmesh::load_from(&mut Cursor::new(&bad_magic), navmesh_settings).is_err());
+
+        let mut other_settings = navmesh_settings;
+        other_settings.tile_size *= 2;
+        assert!(Navmesh::load_from(&mut Cursor::new(&bytes), other_settings).is_err());
+
+        assert!(Navmesh::load_from(&mut Cursor::new(&bytes[0..bytes.len() - 1]), navmesh_settings).is_err());
+
+        // A huge tile size fails on the missing data instead of allocating it
+        let mut huge_tile = bytes.clone();
+        huge_tile[48..52].copy_from_slice(&i32::MAX.to_le_bytes());
+        assert_eq!(io::ErrorKind::UnexpectedEof, Navmesh::load_from(&mut Cursor::new(&huge_tile), navmesh_settings).err().unwrap().kind());
+
+        // Tile data is parsed before detour sees it
+        let mut bad_tile = bytes.clone();
+        bad_tile[56 + 4] = 99;
+        assert_eq!(io::ErrorKind::InvalidData, Navmesh::load_from(&mut Cursor::new(&bad_tile), navmesh_settings).err().unwrap().kind());
+    }
+
+    #[test]
+    fn drain_race() {
+        let tile = NavmeshBuilder::build_test_tile(30.0).unwrap().tile.unwrap();
+        let build_settings = NavBuildSettings::default();
+        let mut navmesh = Navmesh::new(NavmeshSettings::default(build_settings, 2048.0, 2)).unwrap();
+        navmesh.add_or_replace_tile(tile.clone()).unwrap();
+
+        let pool = navmesh.query_pool.clone();
+        let draining = Arc::new(AtomicBool::new(false));
+        let stop = Arc::new(AtomicBool::new(false));
+        let leased_while_draining = Arc::new(AtomicUsize::new(0));
+        let leasers: Vec<_> = (0..2).map(|_| {
+            let (pool, draining, stop, leased_while_draining) = (pool.clone(), draining.clone(), stop.clone(), leased_while_draining.clone());
+            thread::spawn(move || {
+                while !stop.load(Ordering::SeqCst) {
+                    if let Some(mut query) = pool.lease() {
+                        if draining.load(Ordering::SeqCst) {
+                            leased_while_draining.fetch_add(1, Ordering::SeqCst);
+                        }
+                        let _ = query.find_path(NavQuerySettings::default(), DtVector::new(1.0, 1.0, 8.0), DtVector::new(4.0, 1.0, 16.0), &NavQueryFilter::default());
+                    }
+                }
+            })
+        }).collect();
+
+        for _ in 0..200 {
+            let drain = pool.drain(Duration::from_secs(5)).unwrap();
+            draining.store(true, Ordering::SeqCst);
+            assert_eq!(0, pool.outstanding());
+            thread::yield_now();
+            assert_eq!(0, pool.outstanding());
+            draining.store(false, Ordering::SeqCst);
+            drop(drain);
+
+            navmesh.add_or_replace_tile(tile.clone()).unwrap();
+        }
+
+        stop.store(true, Ordering::SeqCst);
+        for leaser in leasers {
+            leaser.join().unwrap();
+        }
+        assert_eq!(0, leased_while_draining.load(Ordering::SeqCst));
+        assert!(pool.is_full());
+    }
+
+    #[test]
+    fn lease_timeout_drain_race() {
+        let tile = NavmeshBuilder::build_test_tile(30.0).unwrap().tile.unwrap();
+        let build_settings = NavBuildSettings::default();
+        let mut navmesh = Navmesh::new(NavmeshSettings::default(build_settings, 2048.0, 2)).unwrap();
+        navmesh.add_or_replace_tile(tile.clone()).unwrap();
+
+        let pool = navmesh.query_pool.clone();
+        let stop = Arc::new(AtomicBool::new(false));
+        let leasers: Vec<_> = (0..2).map(|_| {
+            let (pool, stop) = (pool.clone(), stop.clone());
+            thread::spawn(move || {
+                while !stop.load(Ordering::SeqCst) {
+                    drop(pool.lease_timeout(Duration::from_millis(20)));
+                }
+            })
+        }).collect();
+
+        // A lease_timeout that pushed its query back while holding the returned lock would hang here
+        let (done_sender, done_receiver) = mpsc::channel();
+        let drainer = thread::spawn(move || {
+            for _ in 0..2000 {
+                navmesh.add_or_replace_tile(tile.clone()).unwrap();
+            }
+            let _ = done_sender.send(());
+        });
+        assert!(done_receiver.recv_timeout(Duration::from_secs(30)).is_ok(), "lease_timeout deadlocked against drains");
+        drainer.join().unwrap();
+
+        stop.store(true, Ordering::SeqCst);
+        for leaser in leasers {
+            leaser.join().unwrap();
+        }
+        assert!(pool.is_full());
+    }
+
+    #[test]
+    fn query_leases() {
+        let tile = NavmeshBuilder::build_test_tile(30.0).unwrap().tile.unwrap();
+        let build_settings = NavBuildSettings::default();
+        let navmesh_settings = NavmeshSettings { query_drain_timeout: Duration::from_millis(10), ..NavmeshSettings::default(build_settings, 2048.0, 2) };
+        let mut navmesh = Navmesh::new(navmesh_settings).unwrap();
+
+        let guard = navmesh.query_pool.lease().unwrap();
+        assert_eq!(1, navmesh.query_pool.outstanding());
+        assert_eq!(Err(NavError::QueriesInUse), navmesh.add_or_replace_tile(tile.clone()));
+        drop(guard);
+        assert_eq!(0, navmesh.query_pool.outstanding());
+        navmesh.add_or_replace_tile(tile.clone()).unwrap();
+
+        // Panicking while holding a lease still returns the query
+        let pool = navmesh.query_pool.clone();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let _guard = pool.lease().unwrap();
+            panic!("lost query");
+        }));
+        assert!(result.is_err());
+        assert!(navmesh.query_pool.is_full());
+
+        let first = navmesh.query_pool.lease().unwrap();
+        let second = navmesh.query_pool.lease().unwrap();
+        assert!(navmesh.query_pool.lease().is_none());
+        assert!(navmesh.query_pool.lease_timeout(Duration::from_millis(1)).is_none());
+
+        let returner = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            drop(second);
+        });
+        let mut third = navmesh.query_pool.lease_timeout(Duration::from_secs(5)).unwrap();
+        let (path_len, _) = third.find_path(NavQuerySettings::default(), DtVector::new(1.0, 1.0, 8.0), DtVector::new(4.0, 1.0, 16.0), &NavQueryFilter::default()).unwrap();
+        assert!(path_len > 0);
+        returner.join().unwrap();
+
+        drop((first, third));
+        assert_eq!(0, navmesh.query_pool.outstanding());
+        navmesh.remove_tile(&tile.coord().unwrap()).unwrap();
+
+        // A guard can outlive the Navmesh, its query keeps the navmesh alive
+        navmesh.add_or_replace_tile(tile).unwrap();
+        let mut guard = navmesh.query_pool.lease().unwrap();
+        drop(navmesh);
+        let (path_len, _) = guard.find_path(NavQuerySettings::default(), DtVector::new(1.0, 1.0, 8.0), DtVector::new(4.0, 1.0, 16.0), &NavQueryFilter::default()).unwrap();
+        assert!(path_len > 0);
+    }
+}
@@ -0,0 +1,121 @@
+//! The RecastDemo multi-tile navmesh file format.  A NavMeshSetHeader followed by a NavMeshTileHeader and the raw tile data for each tile.
+//!
+//! Everything is written little endian.  Tile refs are written as 64 bits because recast is built with 64 bit poly refs,
+//! so files only interoperate with other tools built with DT_POLYREF64.
+
+use std::io::{self, Read, Write};
+
+use super::DtNavMeshParams;
+
+pub const NAVMESHSET_MAGIC: i32 = (b'M' as i32) << 24 | (b'S' as i32) << 16 | (b'E' as i32) << 8 | b'T' as i32;
+pub const NAVMESHSET_VERSION: i32 = 1;
+
+#[derive(Clone, Copy, Debug)]
+pub struct NavMeshSetHeader {
+    pub magic: i32,
+    pub version: i32,
+    pub num_tiles: i32,
+    pub params: DtNavMeshParams
+}
+
+impl NavMeshSetHeader {
+    pub const SIZE: usize = 40;
+
+    pub fn new(num_tiles: i32, params: DtNavMeshParams) -> Self {
+        NavMeshSetHeader {
+            magic: NAVMESHSET_MAGIC,
+            version: NAVMESHSET_VERSION,
+            num_tiles,
+            params
+        }
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut buf = Vec::with_capacity(Self::SIZE);
+        buf.extend_from_slice(&self.magic.to_le_bytes());
+        buf.extend_from_slice(&self.version.to_le_bytes());
+        buf.extend_from_slice(&self.num_tiles.to_le_bytes());
+        for value in self.params.origin {
+            buf.extend_from_slice(&value.to_le_bytes());
+        }
+        buf.extend_from_slice(&self.params.tile_width.to_le_bytes());
+        buf.extend_from_slice(&self.params.tile_height.to_le_bytes());
+        buf.extend_from_slice(&self.params.max_tiles.to_le_bytes());
+        buf.extend_from_slice(&self.params.max_polys.to_le_bytes());
+        writer.write_all(&buf)
+    }
+
+    /// Fails with InvalidData if the magic or version don't match
+    pub fn read<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let mut buf = [0u8; Self::SIZE];
+        reader.read_exact(&mut buf)?;
+
+        let header = NavMeshSetHeader {
+            magic: read_i32(&buf, 0),
+            version: read_i32(&buf, 4),
+            num_tiles: read_i32(&buf, 8),
+            params: DtNavMeshParams {
+                origin: [read_f32(&buf, 12), read_f32(&buf, 16), read_f32(&buf, 20)],
+                tile_width: read_f32(&buf, 24),
+                tile_height: read_f32(&buf, 28),
+                max_tiles: read_i32(&buf, 32),
+                max_polys: read_i32(&buf, 36)
+            }
+        };
+
+        if header.magic != NAVMESHSET_MAGIC {
+            return Err(invalid_data("not a navmesh set, bad magic"));
+        }
+        if header.version != NAVMESHSET_VERSION {
+            return Err(invalid_data("unsupported navmesh set version"));
+        }
+        if header.num_tiles < 0 {
+            return Err(invalid_data("negative tile count"));
+        }
+        Ok(header)
+    }
+}
+
+/// Precedes each tile's data.  Matches the C layout with a 64 bit dtTileRef, including the trailing padding
+#[derive(Clone, Copy, Debug)]
+pub struct NavMeshTileHeader {
+    pub tile_ref: u64,
+    pub data_size: i32
+}
+
+impl NavMeshTileHeader {
+    pub const SIZE: usize = 16;
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut buf = [0u8; Self::SIZE];
+        buf[0..8].copy_from_slice(&self.tile_ref.to_le_bytes());
+        buf[8..12].copy_from_slice(&self.data_size.to_le_bytes());
+        writer.write_all(&buf)
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let mut buf = [0u8; Self::SIZE];
+        reader.read_exact(&mut buf)?;
+
+        let mut tile_ref = [0u8; 8];
+        tile_ref.copy_from_slice(&buf[0..8]);
+        Ok(NavMeshTileHeader {
+            tile_ref: u64::from_le_bytes(tile_ref),
+            data_size: read_i32(&buf, 8)
+        })
+    }
+}
+
+pub(crate) fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_i32(buf: &[u8], offset: usize) -> i32 {
+    let mut bytes = [0u8; 4];
+    bytes.copy_from_slice(&buf[offset..offset + 4]);
+    i32::from_le_bytes(bytes)
+}
+
+fn read_f32(buf: &[u8], offset: usize) -> f32 {
+    f32::from_bits(read_i32(buf, offset) as u32)
+}
@@ -1,77 +1,533 @@
-use std::mem;
+use std::{fmt, mem, ptr};
 
-use crate::{common::{DtInt2, DtTileHeader}};
+use crate::{common::{DtInt2, DtTileHeader}, building::DtOffMeshConnection};
 
-use super::{DtVector, dt_align4};
+use super::{DtVector, PolyRef, DT_VERTS_PER_POLYGON};
 
+/// DT_OFFMESH_CON_BIDIR
+const OFFMESH_CON_BIDIR: u8 = 1;
+/// DT_POLYTYPE_OFFMESH_CONNECTION
+const POLYTYPE_OFFMESH_CONNECTION: u8 = 1;
+
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
 pub struct DtPoly {
     pub first_link: u32,
-    pub vertices: [u16;6],
-    pub neighbors: [u16;6],
+    pub vertices: [u16; DT_VERTS_PER_POLYGON],
+    pub neighbors: [u16; DT_VERTS_PER_POLYGON],
     pub flags: u16,
     pub vertex_count: u8,
     pub area_and_type: u8
 }
 
+impl DtPoly {
+    pub fn area(&self) -> u8 {
+        self.area_and_type & 0x3f
+    }
+
+    /// Off-mesh connection polys are just the two end points, they have no surface
+    pub fn is_off_mesh_connection(&self) -> bool {
+        self.area_and_type >> 6 == POLYTYPE_OFFMESH_CONNECTION
+    }
+}
+
+/// dtLink.  Assumes 64 bit poly refs
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct DtLink {
+    pub poly_ref: PolyRef,
+    pub next: u32,
+    pub edge: u8,
+    pub side: u8,
+    pub bmin: u8,
+    pub bmax: u8
+}
+
+/// dtPolyDetail.  Indexes into the tile's detail verts and tris
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct DtPolyDetail {
+    pub vert_base: u32,
+    pub tri_base: u32,
+    pub vert_count: u8,
+    pub tri_count: u8
+}
+
+/// dtBVNode
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct DtBVNode {
+    pub bmin: [u16; 3],
+    pub bmax: [u16; 3],
+    pub i: i32
+}
+
+/// dtOffMeshConnection as stored in the tile data
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct DtTileOffMeshConnection {
+    pub pos: [f32; 6],
+    pub radius: f32,
+    pub poly: u16,
+    pub flags: u8,
+    pub side: u8,
+    pub user_id: u32
+}
+
+/// DT_NAVMESH_MAGIC
+pub const NAVMESH_MAGIC: i32 = (b'D' as i32) << 24 | (b'N' as i32) << 16 | (b'A' as i32) << 8 | b'V' as i32;
+/// DT_NAVMESH_VERSION
+pub const NAVMESH_VERSION: i32 = 7;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TileParseError {
+    /// Data is smaller than the tile header
+    TooSmall,
+    BadMagic(i32),
+    BadVersion(i32),
+    /// A section count in the header is negative
+    InvalidCount,
+    /// The sections described by the header don't fit in the data
+    Truncated { expected: usize, actual: usize },
+    /// The section sizes in the header overflow
+    Overflow,
+    /// A poly has too many vertices or a vertex index past the tile's vertices
+    InvalidPoly(usize)
+}
+
+impl fmt::Display for TileParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TileParseError::TooSmall => write!(f, "tile data is smaller than the tile header"),
+            TileParseError::BadMagic(magic) => write!(f, "bad tile magic {:#x}", magic),
+            TileParseError::BadVersion(version) => write!(f, "unsupported tile version {}", version),
+            TileParseError::InvalidCount => write!(f, "negative section count in tile header"),
+            TileParseError::Truncated { expected, actual } => write!(f, "tile data truncated, expected {} bytes got {}", expected, actual),
+            TileParseError::Overflow => write!(f, "tile header section sizes overflow"),
+            TileParseError::InvalidPoly(index) => write!(f, "tile poly {} has invalid vertices", index)
+        }
+    }
+}
+
+impl std::error::Error for TileParseError {}
+
+/// Byte offsets of each section in the tile data, in the order detour lays them out
+#[derive(Clone, Copy, Debug)]
+struct TileSections {
+    verts: usize,
+    polys: usize,
+    links: usize,
+    detail_meshes: usize,
+    detail_verts: usize,
+    detail_tris: usize,
+    bv_nodes: usize,
+    off_mesh_cons: usize,
+    end: usize
+}
+
+/// Validated, read only access to tile data.  Sections are read with unaligned reads so the data can come from any buffer
+#[derive(Clone, Copy, Debug)]
+pub struct TileView<'a> {
+    data: &'a [u8],
+    header: DtTileHeader,
+    sections: TileSections
+}
+
+impl<'a> TileView<'a> {
+    pub fn header(&self) -> &DtTileHeader {
+        &self.header
+    }
+
+    pub fn coord(&self) -> DtInt2 {
+        DtInt2::new(self.header.x, self.header.y)
+    }
+
+    /// Size of the tile data described by the header
+    pub fn data_size(&self) -> usize {
+        self.sections.end
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.header.vertcount as usize
+    }
+
+    pub fn vertex(&self, index: usize) -> Option<DtVector> {
+        self.read(self.sections.verts, index, self.vertex_count())
+    }
+
+    pub fn vertices(&self) -> impl Iterator<Item = DtVector> + '_ {
+        (0..self.vertex_count()).filter_map(move |i| self.vertex(i))
+    }
+
+    pub fn poly_count(&self) -> usize {
+        self.header.polycount as usize
+    }
+
+    pub fn poly(&self, index: usize) -> Option<DtPoly> {
+        self.read(self.sections.polys, index, self.poly_count())
+    }
+
+    pub fn polys(&self) -> impl Iterator<Item = DtPoly> + '_ {
+        (0..self.poly_count()).filter_map(move |i| self.poly(i))
+    }
+
+    pub fn link_count(&self) -> usize {
+        self.header.max_linked_count as usize
+    }
+
+    /// Links are only filled in once the tile is added to a navmesh.  Tiles straight from the builder have empty links
+    pub fn link(&self, index: usize) -> Option<DtLink> {
+        self.read(self.sections.links, index, self.link_count())
+    }
+
+    pub fn detail_mesh_count(&self) -> usize {
+        self.header.detail_mesh_count as usize
+    }
+
+    /// One detail mesh per ground poly, off-mesh connection polys have none
+    pub fn detail_mesh(&self, index: usize) -> Option<DtPolyDetail> {
+        self.read(self.sections.detail_meshes, index, self.detail_mesh_count())
+    }
+
+    pub fn detail_vertex_count(&self) -> usize {
+        self.header.detail_vert_count as usize
+    }
+
+    pub fn detail_vertex(&self, index: usize) -> Option<DtVector> {
+        self.read(self.sections.detail_verts, index, self.detail_vertex_count())
+    }
+
+    pub fn detail_vertices(&self) -> impl Iterator<Item = DtVector> + '_ {
+        (0..self.detail_vertex_count()).filter_map(move |i| self.detail_vertex(i))
+    }
+
+    pub fn detail_tri_count(&self) -> usize {
+        self.header.detail_tri_count as usize
+    }
+
+    /// Three vertex indices and the edge flags.  Indices below the poly's vertex_count are poly vertices, the rest are detail vertices
+    pub fn detail_tri(&self, index: usize) -> Option<[u8; 4]> {
+        self.read(self.sections.detail_tris, index, self.detail_tri_count())
+    }
+
+    pub fn bv_node_count(&self) -> usize {
+        self.header.bv_node_count as usize
+    }
+
+    pub fn bv_node(&self, index: usize) -> Option<DtBVNode> {
+        self.read(self.sections.bv_nodes, index, self.bv_node_count())
+    }
+
+    pub fn off_mesh_connection_count(&self) -> usize {
+        self.header.off_mesh_con_count as usize
+    }
+
+    pub fn off_mesh_connection(&self, index: usize) -> Option<DtTileOffMeshConnection> {
+        self.read(self.sections.off_mesh_cons, index, self.off_mesh_connection_count())
+    }
+
+    fn read<T: Copy>(&self, offset: usize, index: usize, count: usize) -> Option<T> {
+        if index >= count {
+            return None;
+        }
+        let start = offset + index * mem::size_of::<T>();
+        let bytes = &self.data[start..start + mem::size_of::<T>()];
+        // Parse checked every section fits in data, and all section types are plain old data
+        Some(unsafe {ptr::read_unaligned(bytes.as_ptr() as *const T)})
+    }
+}
+
+/// The exact surface of a tile built from the detail mesh.  vertices are the tile's poly vertices followed by its detail vertices
+#[derive(Clone, Debug, Default)]
+pub struct NavmeshDetailMesh {
+    pub vertices: Vec<DtVector>,
+    /// Three indices per triangle
+    pub indices: Vec<i32>,
+    /// Area id per triangle
+    pub areas: Vec<u8>,
+    /// Poly ref per triangle.  The tile base ref or'ed with the poly index
+    pub polys: Vec<PolyRef>
+}
+
 #[derive(Clone, Debug)]
 pub struct NavmeshTile {
     pub data: Vec<u8>
 }
 
 impl NavmeshTile {
-    
-    /// The coordinate of the tile is embedded inside the tile data header
-    pub fn coord(&self) -> DtInt2 {
-        let header = Self::read_header(&self.data);
-        DtInt2::new(header.x, header.y)
+
+    /// Validates the magic, version, that every section fits in the data and that poly vertices are in range
+    pub fn parse(data: &[u8]) -> Result<TileView<'_>, TileParseError> {
+        let header = Self::read_header(data)?;
+
+        let counts = [header.vertcount, header.polycount, header.max_linked_count, header.detail_mesh_count, header.detail_vert_count,
+            header.detail_tri_count, header.bv_node_count, header.off_mesh_con_count];
+        if counts.iter().any(|count| *count < 0) {
+            return Err(TileParseError::InvalidCount);
+        }
+
+        // Each section is aligned to 4 bytes, returns the offset of the next section
+        let mut offset = 0usize;
+        let mut next_section = |item_size: usize, count: i32| {
+            let size = item_size.checked_mul(count as usize).and_then(|size| size.checked_add(3)).ok_or(TileParseError::Overflow)? & !3;
+            offset = offset.checked_add(size).ok_or(TileParseError::Overflow)?;
+            Ok::<usize, TileParseError>(offset)
+        };
+        let verts = next_section(mem::size_of::<DtTileHeader>(), 1)?;
+        let polys = next_section(mem::size_of::<DtVector>(), header.vertcount)?;
+        let links = next_section(mem::size_of::<DtPoly>(), header.polycount)?;
+        let detail_meshes = next_section(mem::size_of::<DtLink>(), header.max_linked_count)?;
+        let detail_verts = next_section(mem::size_of::<DtPolyDetail>(), header.detail_mesh_count)?;
+        let detail_tris = next_section(mem::size_of::<DtVector>(), header.detail_vert_count)?;
+        let bv_nodes = next_section(mem::size_of::<[u8; 4]>(), header.detail_tri_count)?;
+        let off_mesh_cons = next_section(mem::size_of::<DtBVNode>(), header.bv_node_count)?;
+        let end = next_section(mem::size_of::<DtTileOffMeshConnection>(), header.off_mesh_con_count)?;
+
+        if end > data.len() {
+            return Err(TileParseError::Truncated { expected: end, actual: data.len() });
+        }
+
+        let view = TileView {
+            data,
+            header,
+            sections: TileSections { verts, polys, links, detail_meshes, detail_verts, detail_tris, bv_nodes, off_mesh_cons, end }
+        };
+
+        // Display and detail mesh helpers index the tile's vertices with these
+        for (index, poly) in view.polys().enumerate() {
+            let vertex_count = poly.vertex_count as usize;
+            if vertex_count > DT_VERTS_PER_POLYGON || poly.vertices[..vertex_count].iter().any(|vertex| *vertex as usize >= view.vertex_count()) {
+                return Err(TileParseError::InvalidPoly(index));
+            }
+        }
+
+        Ok(view)
     }
 
-    pub fn read_header(navmesh_data: &[u8]) -> DtTileHeader {
-        let header = navmesh_data.as_ptr() as *const DtTileHeader;
-        unsafe {*header}
+    pub fn view(&self) -> Result<TileView<'_>, TileParseError> {
+        Self::parse(&self.data)
+    }
+
+    /// The coordinate of the tile is embedded inside the tile data header.  None if the data is not a valid tile
+    pub fn coord(&self) -> Option<DtInt2> {
+        Self::parse(&self.data).ok().map(|view| view.coord())
+    }
+
+    /// Reads and checks the header only, use parse to validate the whole tile
+    pub fn read_header(navmesh_data: &[u8]) -> Result<DtTileHeader, TileParseError> {
+        if navmesh_data.len() < mem::size_of::<DtTileHeader>() {
+            return Err(TileParseError::TooSmall);
+        }
+        let header = unsafe {ptr::read_unaligned(navmesh_data.as_ptr() as *const DtTileHeader)};
+        if header.magic != NAVMESH_MAGIC {
+            return Err(TileParseError::BadMagic(header.magic));
+        }
+        if header.version != NAVMESH_VERSION {
+            return Err(TileParseError::BadVersion(header.version));
+        }
+        Ok(header)
+    }
+
+    /// Off-mesh connections that start in this tile, with area and flags read back from their polygons
+    pub fn get_off_mesh_connections(&self) -> Vec<DtOffMeshConnection> {
+        let view = match self.view() {
+            Ok(view) => view,
+            Err(_) => return Vec::new()
+        };
+
+        (0..view.off_mesh_connection_count())
+            .filter_map(|i| view.off_mesh_connection(i))
+            .filter_map(|con| view.poly(con.poly as usize).map(|poly| (con, poly)))
+            .map(|(con, poly)| DtOffMeshConnection {
+                start: DtVector::new(con.pos[0], con.pos[1], con.pos[2]),
+                end: DtVector::new(con.pos[3], con.pos[4], con.pos[5]),
+                radius: con.radius,
+                bidirectional: (con.flags & OFFMESH_CON_BIDIR != 0) as i32,
+                area: poly.area(),
+                flags: poly.flags,
+                user_id: con.user_id
+            })
+            .collect()
     }
 
     /// return the navigation mesh vertices/indices in this tile.  Useful for visual displays of the navmesh
+    /// These are the coarse polygons, use get_detail_mesh for the actual surface height
     pub fn get_tile_vertices(&self) -> Option<(Vec<DtVector>, Vec<i32>)> {
-        
-        if self.data.is_empty() {
-            return None;
-        }
-        let header = Self::read_header(&self.data);
-        if header.vertcount == 0 {
+        let view = self.view().ok()?;
+        if view.vertex_count() == 0 {
             return None;
         }
 
-        let header_size = dt_align4(mem::size_of::<DtTileHeader>() as i32) as usize;
-        let verts_size = dt_align4(mem::size_of::<f32>() as i32 * 3 * header.vertcount) as usize;
+        let vertices: Vec<DtVector> = view.vertices().collect();
+
+        let mut indices: Vec<i32> = Vec::new();
+        for poly in view.polys().filter(|poly| !poly.is_off_mesh_connection()) {
+            for j in 0..(poly.vertex_count as usize).saturating_sub(2) {
+                indices.push(poly.vertices[0] as i32);
+                indices.push(poly.vertices[j + 1] as i32);
+                indices.push(poly.vertices[j + 2] as i32);
+            }
+        }
         
-        let slice = &self.data[header_size..self.data.len()];
+        Some((vertices, indices))
+    }
+
+    /// Triangulated detail mesh with the area and poly of each triangle.
+    /// base_ref is the tile's poly ref base from Navmesh::get_tile_base_ref, or 0 to get plain poly indices
+    pub fn get_detail_mesh(&self, base_ref: PolyRef) -> Option<NavmeshDetailMesh> {
+        let view = self.view().ok()?;
+        if view.vertex_count() == 0 {
+            return None;
+        }
 
-        let mut vertices: Vec<DtVector> = Vec::new();
-        let verts_ptr = slice.as_ptr() as *const DtVector;
+        let mut mesh = NavmeshDetailMesh {
+            vertices: view.vertices().chain(view.detail_vertices()).collect(),
+            ..Default::default()
+        };
 
-        unsafe {
-            for i in 0..header.vertcount {
-                let vertice = verts_ptr.offset(i as isize);
-                let vertice = *vertice;
-                vertices.push(vertice);
+        let vertex_count = view.vertex_count();
+        for (poly_index, poly) in view.polys().enumerate() {
+            if poly.is_off_mesh_connection() {
+                continue;
+            }
+            let detail = view.detail_mesh(poly_index)?;
+            for i in 0..detail.tri_count as usize {
+                let tri = view.detail_tri(detail.tri_base as usize + i)?;
+                for index in &tri[0..3] {
+                    let index = *index as usize;
+                    // Indices below the poly vertex count are poly vertices, the rest are detail vertices
+                    let vertex = if index < poly.vertex_count as usize {
+                        poly.vertices[index] as usize
+                    } else {
+                        vertex_count + detail.vert_base as usize + index - poly.vertex_count as usize
+                    };
+                    if vertex >= mesh.vertices.len() {
+                        return None;
+                    }
+                    mesh.indices.push(vertex as i32);
+                }
+                mesh.areas.push(poly.area());
+                mesh.polys.push(base_ref | poly_index as PolyRef);
             }
         }
 
-        let mut indices: Vec<i32> = Vec::new();
-        let slice = &self.data[(header_size + verts_size)..self.data.len()];
-        let poly_ptr = slice.as_ptr() as *const DtPoly;
-        unsafe {
-            for i in 0..header.polycount {
-                let poly = poly_ptr.offset(i as isize);
-                for j in 0..((*poly).vertex_count - 3) {
-                    indices.push((*poly).vertices[0] as i32);
-                    indices.push((*poly).vertices[(j + 1) as usize] as i32);
-                    indices.push((*poly).vertices[(j + 2) as usize] as i32);
-                }
+        Some(mesh)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{building::{navmesh_builder::NavmeshBuilder, NavBuildSettings}, common::{navmesh::{Navmesh, NavmeshSettings}, DtArea, DtInt2, NavAgentSettings}};
+
+    use std::mem;
+
+    use super::{DtPoly, NavmeshTile, TileParseError};
+
+    #[test]
+    fn parse_tile() {
+        let result = NavmeshBuilder::build_test_tile(30.0).unwrap();
+        let tile = result.tile.unwrap();
+
+        let view = tile.view().unwrap();
+        assert_eq!(DtInt2::new(0, 0), view.coord());
+        assert_eq!(tile.data.len(), view.data_size());
+        assert!(view.poly_count() > 0 && view.vertex_count() > 0);
+        assert_eq!(view.poly_count(), view.detail_mesh_count());
+        assert_eq!(view.poly_count(), view.polys().count());
+        assert!(view.poly(view.poly_count()).is_none());
+        assert!(view.detail_tri(view.detail_tri_count() - 1).is_some());
+        assert!(view.bv_node(0).is_some());
+        assert!((view.header().walkable_height - NavAgentSettings::default().height).abs() < 0.01);
+
+        for poly in view.polys() {
+            for i in 0..poly.vertex_count as usize {
+                assert!(view.vertex(poly.vertices[i] as usize).is_some());
             }
         }
-        
-        Some((vertices, indices))
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn detail_mesh() {
+        let result = NavmeshBuilder::build_test_tile(30.0).unwrap();
+        let tile = result.tile.unwrap();
+        let view = tile.view().unwrap();
+
+        let (_, indices) = tile.get_tile_vertices().unwrap();
+        let poly_triangles: usize = view.polys().map(|poly| poly.vertex_count as usize - 2).sum();
+        assert_eq!(poly_triangles * 3, indices.len());
+
+        let mesh = tile.get_detail_mesh(0).unwrap();
+        assert_eq!(view.vertex_count() + view.detail_vertex_count(), mesh.vertices.len());
+        assert_eq!(mesh.indices.len(), mesh.areas.len() * 3);
+        assert_eq!(mesh.areas.len(), mesh.polys.len());
+        assert!(mesh.areas.len() >= poly_triangles);
+        assert!(mesh.areas.iter().all(|area| *area == DtArea::WALKABLE));
+        assert!(mesh.polys.iter().all(|poly| (*poly as usize) < view.poly_count()));
+        for index in mesh.indices.iter() {
+            let vertex = mesh.vertices[*index as usize];
+            assert!((vertex.y - 1.0).abs() < 0.5);
+        }
+
+        let build_settings = NavBuildSettings::default();
+        let mut navmesh = Navmesh::new(NavmeshSettings::default(build_settings, 2048.0, 10)).unwrap();
+        assert!(navmesh.get_tile_base_ref(&DtInt2::new(0, 0)).is_none());
+        navmesh.add_or_replace_tile(tile.clone()).unwrap();
+        let base_ref = navmesh.get_tile_base_ref(&DtInt2::new(0, 0)).unwrap();
+        assert_ne!(0, base_ref);
+
+        let mesh_with_refs = tile.get_detail_mesh(base_ref).unwrap();
+        for (poly_ref, poly_index) in mesh_with_refs.polys.iter().zip(mesh.polys.iter()) {
+            assert_eq!(base_ref | poly_index, *poly_ref);
+        }
+    }
+
+    #[test]
+    fn corrupt_polys() {
+        let tile = NavmeshBuilder::build_test_tile(30.0).unwrap().tile.unwrap();
+        let polys = tile.view().unwrap().sections.polys;
+        let vertices = polys + mem::offset_of!(DtPoly, vertices);
+        let vertex_count = polys + mem::offset_of!(DtPoly, vertex_count);
+
+        let mut bad_vertex = tile.clone();
+        bad_vertex.data[vertices..vertices + 2].copy_from_slice(&u16::MAX.to_le_bytes());
+
+        let mut bad_count = tile.clone();
+        bad_count.data[vertex_count] = 200;
+
+        for corrupt in [bad_vertex, bad_count] {
+            assert_eq!(Some(TileParseError::InvalidPoly(0)), corrupt.view().err());
+            assert!(corrupt.get_tile_vertices().is_none());
+            assert!(corrupt.get_detail_mesh(0).is_none());
+        }
+    }
+
+    #[test]
+    fn parse_invalid_tile() {
+        let result = NavmeshBuilder::build_test_tile(30.0).unwrap();
+        let tile = result.tile.unwrap();
+
+        assert_eq!(Some(TileParseError::TooSmall), NavmeshTile::parse(&tile.data[0..10]).err());
+
+        let truncated = &tile.data[0..tile.data.len() - 4];
+        assert!(matches!(NavmeshTile::parse(truncated), Err(TileParseError::Truncated { .. })));
+
+        let mut bad_magic = tile.data.clone();
+        bad_magic[0] ^= 0xff;
+        assert!(matches!(NavmeshTile::parse(&bad_magic), Err(TileParseError::BadMagic(_))));
+
+        let mut bad_version = tile.data.clone();
+        bad_version[4] = 99;
+        assert_eq!(Some(TileParseError::BadVersion(99)), NavmeshTile::parse(&bad_version).err());
+
+        // Counts big enough to overflow a 32 bit section size
+        let mut huge_count = tile.data.clone();
+        huge_count[28..32].copy_from_slice(&i32::MAX.to_le_bytes());
+        assert!(matches!(NavmeshTile::parse(&huge_count), Err(TileParseError::Truncated { .. } | TileParseError::Overflow)));
+
+        let garbage = NavmeshTile { data: vec![0xab; 512] };
+        assert!(garbage.coord().is_none());
+        assert!(garbage.get_tile_vertices().is_none());
+        assert!(garbage.get_detail_mesh(0).is_none());
+        assert!(garbage.get_off_mesh_connections().is_empty());
+    }
+}
@@ -1,92 +1,215 @@
+use std::sync::Arc;
+
 use crate::{
     bindings::{
         CrowdAddAgent, CrowdCreate, CrowdDestroy, CrowdGetAgent, CrowdGetAgentCount,
-        CrowdGetAgentParams, CrowdGetAgents, CrowdRemoveAgent, CrowdRequestMoveAgent,
-        CrowdSetAgentParams, CrowdUpdate, RawCrowdPtr, RawNavmeshPtr,
+        CrowdGetAgentParams, CrowdGetAgents, CrowdRemoveAgent, CrowdRequestMoveAgent, CrowdRequestMoveAgentByPoly, CrowdRequestMoveVelocity, CrowdResetMoveTarget,
+        CrowdSetAgentParams, CrowdUpdate, RawCrowdPtr, CrowdSetFilter, CrowdGetFilter, CrowdGetAgentInfo,
+        CrowdGetAgentCorridor, CrowdGetAgentSnapshot, CrowdSetObstacleAvoidanceParams, CrowdGetObstacleAvoidanceParams,
     },
-    common::DtVector,
+    common::{nav_error::NavError, navmesh::{CrowdUpdateGuard, NavQueryPool, NavmeshPtr}, DtVector, PolyRef},
+    queries::NavQueryFilter,
 };
 
-use super::{DtAgentParams, DtCrowdAgent, DtCrowdAgentsResult};
+use super::{
+    CrowdAgentInfo, CrowdDelta, CrowdSnapshot, DtAgentParams, DtCrowdAgent, DtCrowdAgentInfo, DtCrowdAgentSnapshot,
+    DtCrowdAgentsResult, ObstacleAvoidanceParams,
+};
 
 struct CrowdPtr(*mut RawCrowdPtr);
 unsafe impl Send for CrowdPtr {}
 
+/// Created with Navmesh::create_crowd
 pub struct Crowd {
     crowd_ptr: CrowdPtr,
     max_agents: i32,
+    /// Bumped each time an agent is added to the slot, so CrowdDelta can tell a reused slot from the agent it reported
+    agent_generations: Vec<u32>,
+    query_pool: Arc<NavQueryPool>,
+    /// Dropped after the crowd is destroyed in drop
+    _navmesh: Arc<NavmeshPtr>,
 }
 
 impl Crowd {
-    pub fn new(
-        navmesh_ptr: *mut RawNavmeshPtr,
+    /// Number of query filters a crowd has.  DtAgentParams::query_filter_type indexes into these.
+    pub const MAX_QUERY_FILTER_TYPE: i32 = 16;
+    /// Number of obstacle avoidance param slots.  DtAgentParams::obstacle_avoidance_type indexes into these.
+    pub const MAX_OBSTACLE_AVOIDANCE_TYPE: i32 = 8;
+
+    pub(crate) fn new(
+        navmesh: Arc<NavmeshPtr>,
+        query_pool: Arc<NavQueryPool>,
         max_agents: i32,
         max_agent_radius: f32,
-    ) -> Option<Self> {
+    ) -> Result<Self, NavError> {
         
-        let ptr = unsafe { CrowdCreate(navmesh_ptr, max_agents, max_agent_radius) };
+        let ptr = unsafe { CrowdCreate(navmesh.raw(), max_agents, max_agent_radius) };
         if ptr.is_null() {
-            return None;
+            return Err(NavError::CreateFailed("crowd"));
         }
 
         let crowd_ptr = CrowdPtr(ptr);
         let crowd = Crowd {
             crowd_ptr,
             max_agents,
+            agent_generations: vec![0; max_agents.max(0) as usize],
+            query_pool,
+            _navmesh: navmesh,
         };
-        Some(crowd)
+        Ok(crowd)
     }
 
-    pub fn update(&mut self, delta_time: f32) {
-        unsafe { CrowdUpdate(self.crowd_ptr.0, delta_time) }
+    /// Returns false without updating while the navmesh is changing tiles on another thread, call again next frame
+    pub fn update(&mut self, delta_time: f32) -> bool {
+        let Some(_update) = self.query_pool.begin_crowd_update() else {
+            return false;
+        };
+        unsafe { CrowdUpdate(self.crowd_ptr.0, delta_time) };
+        true
     }
 
-    pub fn move_agent(&mut self, idx: i32, position: DtVector) -> bool {
-        unsafe { CrowdRequestMoveAgent(self.crowd_ptr.0, idx, &position as *const DtVector) == 1 }
+    /// Held while touching the navmesh outside of update.  Fails with QueriesInUse while the navmesh is changing tiles
+    fn navmesh_access(&self) -> Result<CrowdUpdateGuard<'_>, NavError> {
+        self.query_pool.begin_crowd_update().ok_or(NavError::QueriesInUse)
     }
 
-    pub fn get_agent(&mut self, idx: i32) -> Option<DtCrowdAgent> {
+    /// Fails with NO_NEAREST_POLY if position isn't near the navmesh, INVALID_PARAM if idx isn't an active agent,
+    /// QueriesInUse while the navmesh is changing tiles
+    pub fn move_agent(&mut self, idx: i32, position: DtVector) -> Result<(), NavError> {
+        let _access = self.navmesh_access()?;
+        unsafe { CrowdRequestMoveAgent(self.crowd_ptr.0, idx, &position as *const DtVector) }.to_result()?;
+        Ok(())
+    }
+
+    /// Move toward position on a polygon the caller already found, eg the last poly of NavQuery::find_poly_path.
+    /// Skips the nearest poly search move_agent does.  Fails with INVALID_PARAM if idx isn't an active agent,
+    /// QueriesInUse while the navmesh is changing tiles
+    pub fn move_agent_to_poly(&mut self, idx: i32, poly_ref: PolyRef, position: DtVector) -> Result<(), NavError> {
+        let _access = self.navmesh_access()?;
+        unsafe { CrowdRequestMoveAgentByPoly(self.crowd_ptr.0, idx, &poly_ref as *const PolyRef, &position as *const DtVector) }.to_result()?;
+        Ok(())
+    }
+
+    /// Steer the agent at a velocity instead of toward a target, for player controlled agents.
+    /// The agent keeps avoiding obstacles and other agents.  Fails with INVALID_PARAM if idx isn't an active agent,
+    /// QueriesInUse while the navmesh is changing tiles
+    pub fn set_velocity(&mut self, idx: i32, velocity: DtVector) -> Result<(), NavError> {
+        let _access = self.navmesh_access()?;
+        unsafe { CrowdRequestMoveVelocity(self.crowd_ptr.0, idx, &velocity as *const DtVector) }.to_result()?;
+        Ok(())
+    }
+
+    /// Clear the agent's target or velocity request, the agent slows to a stop.  Fails with INVALID_PARAM if idx isn't an active agent,
+    /// QueriesInUse while the navmesh is changing tiles
+    pub fn stop_agent(&mut self, idx: i32) -> Result<(), NavError> {
+        let _access = self.navmesh_access()?;
+        unsafe { CrowdResetMoveTarget(self.crowd_ptr.0, idx) }.to_result()?;
+        Ok(())
+    }
+
+    pub fn get_agent(&mut self, idx: i32) -> Result<DtCrowdAgent, NavError> {
         unsafe {
             let mut agent = DtCrowdAgent::default();
             let res = CrowdGetAgent(self.crowd_ptr.0, idx, &mut agent as *mut DtCrowdAgent);
             if res == 1 {
-                Some(agent)
+                Ok(agent)
             } else {
-                None
+                Err(NavError::InvalidAgent(idx))
             }
         }
     }
 
-    pub fn get_agents(&mut self) -> Option<Vec<DtCrowdAgent>> {
-        unsafe {
-            let mut agents = vec![DtCrowdAgent::default(); self.max_agents as usize];
-            let mut result = DtCrowdAgentsResult {
-                agents: agents.as_mut_ptr() as *mut DtCrowdAgent,
-                agent_count: 0,
-            };
-
-            CrowdGetAgents(self.crowd_ptr.0, &mut result as *mut DtCrowdAgentsResult);
-            if result.agent_count > 0 {
-                agents.truncate(result.agent_count as usize);
-                Some(agents)
-            } else {
-                None
-            }
+    /// Steering and path state of the agent for animation and debugging
+    pub fn get_agent_info(&self, idx: i32) -> Result<CrowdAgentInfo, NavError> {
+        let mut info = DtCrowdAgentInfo::default();
+        if unsafe { CrowdGetAgentInfo(self.crowd_ptr.0, idx, &mut info as *mut DtCrowdAgentInfo) } != 1 {
+            return Err(NavError::InvalidAgent(idx));
+        }
+
+        let mut corridor: Vec<PolyRef> = vec![0; info.corridor_length.max(0) as usize];
+        let count = unsafe { CrowdGetAgentCorridor(self.crowd_ptr.0, idx, corridor.as_mut_ptr(), corridor.len() as i32) };
+        corridor.truncate(count.max(0) as usize);
+        Ok(CrowdAgentInfo::new(&info, corridor))
+    }
+
+    /// Every active agent.  Empty if there are none
+    pub fn get_agents(&mut self) -> Vec<DtCrowdAgent> {
+        let mut agents = Vec::new();
+        self.get_agents_into(&mut agents);
+        agents
+    }
+
+    /// get_agents into a reused buffer.  Doesn't allocate once agents has capacity for max_agents
+    pub fn get_agents_into(&mut self, agents: &mut Vec<DtCrowdAgent>) {
+        agents.resize(self.max_agents as usize, DtCrowdAgent::default());
+        let mut result = DtCrowdAgentsResult {
+            agents: agents.as_mut_ptr(),
+            agent_count: 0,
+        };
+
+        unsafe { CrowdGetAgents(self.crowd_ptr.0, &mut result as *mut DtCrowdAgentsResult) };
+        agents.truncate(result.agent_count.max(0) as usize);
+    }
+
+    /// Positions, velocities and states of every active agent into snapshot's buffers, replacing what was there.
+    /// Doesn't allocate once the buffers have capacity for max_agents
+    pub fn snapshot_into(&self, snapshot: &mut CrowdSnapshot) {
+        let max_agents = self.max_agents.max(0) as usize;
+        snapshot.resize(max_agents);
+        let mut result = DtCrowdAgentSnapshot {
+            indices: snapshot.indices.as_mut_ptr(),
+            positions: snapshot.positions.as_mut_ptr(),
+            velocities: snapshot.velocities.as_mut_ptr(),
+            states: snapshot.states.as_mut_ptr(),
+            max_agents: max_agents as i32,
+            agent_count: 0,
+        };
+
+        unsafe { CrowdGetAgentSnapshot(self.crowd_ptr.0, &mut result as *mut DtCrowdAgentSnapshot) };
+        snapshot.resize(result.agent_count.max(0) as usize);
+    }
+
+    /// Fill delta with the agents added or moved more than epsilon since delta last reported them, and the agents removed.
+    /// Agents that drift slowly are reported once their distance from the last reported position passes epsilon.
+    /// A slot that was removed and added to again in between is reported in both removed and changed.
+    pub fn delta_into(&self, epsilon: f32, delta: &mut CrowdDelta) {
+        self.snapshot_into(&mut delta.current);
+        delta.reported.resize(self.max_agents.max(0) as usize, None);
+        delta.update(epsilon, &self.agent_generations);
+    }
+
+    /// Detour indexes its filter and avoidance param arrays with these unchecked
+    fn check_agent_params(params: &DtAgentParams) -> Result<(), NavError> {
+        if !(0..Self::MAX_QUERY_FILTER_TYPE).contains(&params.query_filter_type) {
+            return Err(NavError::InvalidParam("query_filter_type is out of range"));
+        }
+        if !(0..Self::MAX_OBSTACLE_AVOIDANCE_TYPE).contains(&params.obstacle_avoidance_type) {
+            return Err(NavError::InvalidParam("obstacle_avoidance_type is out of range"));
         }
+        Ok(())
     }
 
-    pub fn add_agent(&mut self, position: DtVector, params: DtAgentParams) -> Option<i32> {
-        let res = unsafe {
-            CrowdAddAgent(
-                self.crowd_ptr.0,
-                &position as *const DtVector,
-                &params as *const DtAgentParams,
-            )
+    /// Fails with CrowdFull if every agent slot is in use, QueriesInUse while the navmesh is changing tiles,
+    /// InvalidParam if query_filter_type or obstacle_avoidance_type is out of range
+    pub fn add_agent(&mut self, position: DtVector, params: DtAgentParams) -> Result<i32, NavError> {
+        Self::check_agent_params(&params)?;
+        let res = {
+            let _access = self.navmesh_access()?;
+            unsafe {
+                CrowdAddAgent(
+                    self.crowd_ptr.0,
+                    &position as *const DtVector,
+                    &params as *const DtAgentParams,
+                )
+            }
         };
         if res >= 0 {
-            Some(res)
+            if let Some(generation) = self.agent_generations.get_mut(res as usize) {
+                *generation = generation.wrapping_add(1);
+            }
+            Ok(res)
         } else {
-            None
+            Err(NavError::CrowdFull)
         }
     }
 
@@ -98,8 +221,11 @@ impl Crowd {
         unsafe { CrowdGetAgentCount(self.crowd_ptr.0) }
     }
 
-    pub fn set_agent_params(&mut self, idx: i32, params: DtAgentParams) {
+    /// Fails with InvalidParam if query_filter_type or obstacle_avoidance_type is out of range
+    pub fn set_agent_params(&mut self, idx: i32, params: DtAgentParams) -> Result<(), NavError> {
+        Self::check_agent_params(&params)?;
         unsafe { CrowdSetAgentParams(self.crowd_ptr.0, idx, &params as *const DtAgentParams) }
+        Ok(())
     }
 
     pub fn get_agent_params(&mut self, idx: i32) -> DtAgentParams {
@@ -109,6 +235,44 @@ impl Crowd {
             params
         }
     }
+
+    /// Set the filter used by agents whose query_filter_type is filter_type.
+    pub fn set_filter(&mut self, filter_type: i32, filter: &NavQueryFilter) -> Result<(), NavError> {
+        if unsafe { CrowdSetFilter(self.crowd_ptr.0, filter_type, filter as *const NavQueryFilter) } == 1 {
+            Ok(())
+        } else {
+            Err(NavError::InvalidParam("filter_type is out of range"))
+        }
+    }
+
+    pub fn get_filter(&self, filter_type: i32) -> Result<NavQueryFilter, NavError> {
+        unsafe {
+            let mut filter = NavQueryFilter::default();
+            if CrowdGetFilter(self.crowd_ptr.0, filter_type, &mut filter as *mut NavQueryFilter) == 1 {
+                Ok(filter)
+            } else {
+                Err(NavError::InvalidParam("filter_type is out of range"))
+            }
+        }
+    }
+
+    /// Set the obstacle avoidance params used by agents whose obstacle_avoidance_type is avoidance_type
+    pub fn set_obstacle_avoidance_params(&mut self, avoidance_type: i32, params: &ObstacleAvoidanceParams) -> Result<(), NavError> {
+        if unsafe { CrowdSetObstacleAvoidanceParams(self.crowd_ptr.0, avoidance_type, params as *const ObstacleAvoidanceParams) } == 1 {
+            Ok(())
+        } else {
+            Err(NavError::InvalidParam("avoidance_type is out of range"))
+        }
+    }
+
+    pub fn get_obstacle_avoidance_params(&self, avoidance_type: i32) -> Result<ObstacleAvoidanceParams, NavError> {
+        let mut params = ObstacleAvoidanceParams::default();
+        if unsafe { CrowdGetObstacleAvoidanceParams(self.crowd_ptr.0, avoidance_type, &mut params as *mut ObstacleAvoidanceParams) } == 1 {
+            Ok(params)
+        } else {
+            Err(NavError::InvalidParam("avoidance_type is out of range"))
+        }
+    }
 }
 
 impl Drop for Crowd {
@@ -119,13 +283,17 @@ impl Drop for Crowd {
 
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
     use crate::{
         building::{navmesh_builder::NavmeshBuilder, NavBuildSettings},
         common::{
+            nav_error::{DtStatus, NavError},
             navmesh::{Navmesh, NavmeshSettings},
             DtVector,
         },
-        crowd::DtAgentParams,
+        crowd::{CrowdDelta, CrowdSnapshot, DtAgentParams, DtMoveRequestState, ObstacleAvoidanceParams},
+        queries::{NavQueryFilter, NavQuerySettings},
     };
 
     use super::Crowd;
@@ -136,11 +304,11 @@ mod tests {
         let navmesh_settings = NavmeshSettings::default(build_settings, 2048.0, 10);
         let mut navmesh = Navmesh::new(navmesh_settings).unwrap();
 
-        let result = NavmeshBuilder::build_test_tile(100.0);
+        let result = NavmeshBuilder::build_test_tile(100.0).unwrap();
         let tile = result.tile.unwrap();
-        navmesh.add_or_replace_tile(tile);
+        navmesh.add_or_replace_tile(tile).unwrap();
 
-        let mut crowd = Crowd::new(navmesh.raw_ptr(), 100, 0.5).unwrap();
+        let mut crowd = navmesh.create_crowd(100, 0.5).unwrap();
 
         let pos = DtVector::new(1.0, 1.0, 1.0);
         let params = DtAgentParams::default();
@@ -152,7 +320,8 @@ mod tests {
         assert_eq!(count, 2);
 
         let move_pos = DtVector::new(10.0, 1.0, 10.0);
-        assert!(crowd.move_agent(idx0, move_pos));
+        crowd.move_agent(idx0, move_pos).unwrap();
+        assert!(crowd.move_agent(idx0, DtVector::new(1000.0, 1.0, 1000.0)).unwrap_err().has_detail(DtStatus::NO_NEAREST_POLY));
 
         for _ in 0..10 {
             crowd.update(0.2);
@@ -161,7 +330,7 @@ mod tests {
         let agent = crowd.get_agent(idx0).unwrap();
         assert_eq!(idx0, agent.index);
 
-        let agents = crowd.get_agents().unwrap();
+        let agents = crowd.get_agents();
         assert_eq!(agents.len(), 2);
 
         crowd.remove_agent(idx0);
@@ -169,5 +338,270 @@ mod tests {
 
         let count = crowd.agent_count();
         assert_eq!(count, 0);
+        assert_eq!(Err(NavError::InvalidAgent(100)), crowd.get_agent(100).map(|agent| agent.index));
     }
-}
+
+    #[test]
+    fn crowd_filters() {
+        let build_settings = NavBuildSettings::default();
+        let navmesh_settings = NavmeshSettings::default(build_settings, 2048.0, 10);
+        let mut navmesh = Navmesh::new(navmesh_settings).unwrap();
+
+        let result = NavmeshBuilder::build_test_tile(100.0).unwrap();
+        navmesh.add_or_replace_tile(result.tile.unwrap()).unwrap();
+
+        let mut crowd = navmesh.create_crowd(100, 0.5).unwrap();
+
+        let mut filter = NavQueryFilter::default();
+        filter.set_area_cost(5, 10.0);
+        filter.exclude_flags = 2;
+        crowd.set_filter(1, &filter).unwrap();
+        assert!(crowd.set_filter(Crowd::MAX_QUERY_FILTER_TYPE, &filter).is_err());
+
+        let mut filter = crowd.get_filter(1).unwrap();
+        assert_eq!(10.0, filter.area_cost(5));
+        assert_eq!(2, filter.exclude_flags);
+
+        // Areas past 63 are masked like detour does
+        filter.set_area_cost(5 | 0x40, 3.0);
+        assert_eq!(3.0, filter.area_cost(5));
+
+        let params = DtAgentParams { query_filter_type: 1, ..Default::default() };
+        let idx = crowd.add_agent(DtVector::new(1.0, 1.0, 1.0), params).unwrap();
+        assert_eq!(1, crowd.get_agent_params(idx).query_filter_type);
+
+        // Filter types past the crowd's filters are rejected before they reach detour
+        let bad_params = DtAgentParams { query_filter_type: Crowd::MAX_QUERY_FILTER_TYPE, ..Default::default() };
+        assert_eq!(Err(NavError::InvalidParam("query_filter_type is out of range")), crowd.add_agent(DtVector::new(1.0, 1.0, 3.0), bad_params));
+        assert!(crowd.set_agent_params(idx, DtAgentParams { query_filter_type: -1, ..Default::default() }).is_err());
+        assert_eq!(1, crowd.get_agent_params(idx).query_filter_type);
+
+        // Targets are snapped with the agent's own filter
+        let other = crowd.add_agent(DtVector::new(1.0, 1.0, 2.0), DtAgentParams::default()).unwrap();
+        let target = DtVector::new(4.0, 1.0, 16.0);
+        crowd.set_filter(1, &NavQueryFilter { exclude_flags: 1, ..Default::default() }).unwrap();
+        assert!(crowd.move_agent(idx, target).unwrap_err().has_detail(DtStatus::NO_NEAREST_POLY));
+        crowd.move_agent(other, target).unwrap();
+    }
+
+    #[test]
+    fn crowd_obstacle_avoidance() {
+        let build_settings = NavBuildSettings::default();
+        let navmesh_settings = NavmeshSettings::default(build_settings, 2048.0, 10);
+        let mut navmesh = Navmesh::new(navmesh_settings).unwrap();
+        navmesh.add_or_replace_tile(NavmeshBuilder::build_test_tile(30.0).unwrap().tile.unwrap()).unwrap();
+
+        let mut crowd = navmesh.create_crowd(10, 0.5).unwrap();
+        assert_eq!(ObstacleAvoidanceParams::low(), crowd.get_obstacle_avoidance_params(0).unwrap());
+        assert_eq!(ObstacleAvoidanceParams::high(), crowd.get_obstacle_avoidance_params(3).unwrap());
+        assert_eq!(ObstacleAvoidanceParams::default(), crowd.get_obstacle_avoidance_params(Crowd::MAX_OBSTACLE_AVOIDANCE_TYPE - 1).unwrap());
+
+        let params = ObstacleAvoidanceParams { weight_side: 3.0, horiz_time: 1.5, adaptive_rings: 3, ..ObstacleAvoidanceParams::good() };
+        crowd.set_obstacle_avoidance_params(5, &params).unwrap();
+        assert_eq!(params, crowd.get_obstacle_avoidance_params(5).unwrap());
+        assert!(crowd.set_obstacle_avoidance_params(Crowd::MAX_OBSTACLE_AVOIDANCE_TYPE, &params).is_err());
+        assert!(crowd.get_obstacle_avoidance_params(-1).is_err());
+
+        let agent_params = DtAgentParams { obstacle_avoidance_type: 5, ..Default::default() };
+        let idx = crowd.add_agent(DtVector::new(2.0, 1.0, 8.0), agent_params).unwrap();
+        crowd.add_agent(DtVector::new(2.0, 1.0, 10.0), DtAgentParams::default()).unwrap();
+        crowd.move_agent(idx, DtVector::new(4.0, 1.0, 16.0)).unwrap();
+        for _ in 0..5 {
+            crowd.update(0.1);
+        }
+        assert_eq!(5, crowd.get_agent_params(idx).obstacle_avoidance_type);
+        assert!(crowd.get_agent(idx).unwrap().velocity.z > 0.0);
+
+        let bad_params = DtAgentParams { obstacle_avoidance_type: Crowd::MAX_OBSTACLE_AVOIDANCE_TYPE, ..Default::default() };
+        assert_eq!(Err(NavError::InvalidParam("obstacle_avoidance_type is out of range")), crowd.add_agent(DtVector::new(2.0, 1.0, 12.0), bad_params));
+        assert!(crowd.set_agent_params(idx, bad_params).is_err());
+        crowd.set_agent_params(idx, DtAgentParams { obstacle_avoidance_type: 2, ..Default::default() }).unwrap();
+        assert_eq!(2, crowd.get_agent_params(idx).obstacle_avoidance_type);
+    }
+
+    #[test]
+    fn crowd_agent_info() {
+        let build_settings = NavBuildSettings::default();
+        let navmesh_settings = NavmeshSettings::default(build_settings, 2048.0, 10);
+        let mut navmesh = Navmesh::new(navmesh_settings).unwrap();
+        navmesh.add_or_replace_tile(NavmeshBuilder::build_test_tile(30.0).unwrap().tile.unwrap()).unwrap();
+
+        let mut crowd = navmesh.create_crowd(10, 0.5).unwrap();
+        let idx = crowd.add_agent(DtVector::new(2.0, 1.0, 8.0), DtAgentParams::default()).unwrap();
+        let other = crowd.add_agent(DtVector::new(2.0, 1.0, 9.5), DtAgentParams::default()).unwrap();
+        let target = DtVector::new(4.0, 1.0, 16.0);
+        crowd.move_agent(idx, target).unwrap();
+        for _ in 0..3 {
+            crowd.update(0.1);
+        }
+
+        let info = crowd.get_agent_info(idx).unwrap();
+        assert_eq!(idx, info.agent.index);
+        assert_eq!(DtMoveRequestState::VALID, info.target_state);
+        assert!((info.target_position.x - target.x).abs() < 0.1 && (info.target_position.z - target.z).abs() < 0.1);
+        assert!(info.current_poly != 0);
+        assert_eq!(Some(&info.current_poly), info.corridor.first());
+        assert!(!info.corners.is_empty());
+        assert!(info.desired_velocity.z > 0.0);
+        assert!(info.neighbours.iter().any(|neighbour| neighbour.index == other));
+        // The walkable half of the test tile ends at the diagonal next to the agent
+        assert!(!info.boundary_segments.is_empty());
+
+        // Bulk readback carries the state and velocity too
+        let agents = crowd.get_agents();
+        let agent = agents.iter().find(|agent| agent.index == idx).unwrap();
+        assert_eq!(1, agent.state);
+        assert!(agent.velocity.z > 0.0);
+
+        assert_eq!(Err(NavError::InvalidAgent(100)), crowd.get_agent_info(100).map(|info| info.agent.index));
+
+        // A removed agent's slot keeps its old state, it isn't reported
+        crowd.remove_agent(idx);
+        assert_eq!(Err(NavError::InvalidAgent(idx)), crowd.get_agent_info(idx).map(|info| info.agent.index));
+    }
+
+    #[test]
+    fn crowd_snapshots() {
+        let build_settings = NavBuildSettings::default();
+        let navmesh_settings = NavmeshSettings::default(build_settings, 2048.0, 10);
+        let mut navmesh = Navmesh::new(navmesh_settings).unwrap();
+        navmesh.add_or_replace_tile(NavmeshBuilder::build_test_tile(30.0).unwrap().tile.unwrap()).unwrap();
+
+        let mut crowd = navmesh.create_crowd(10, 0.5).unwrap();
+        let mut agents = Vec::new();
+        crowd.get_agents_into(&mut agents);
+        assert!(agents.is_empty());
+        assert!(crowd.get_agents().is_empty());
+
+        let moving = crowd.add_agent(DtVector::new(2.0, 1.0, 8.0), DtAgentParams::default()).unwrap();
+        let idle = crowd.add_agent(DtVector::new(8.0, 1.0, 15.0), DtAgentParams::default()).unwrap();
+
+        crowd.get_agents_into(&mut agents);
+        assert_eq!(2, agents.len());
+        let capacity = agents.capacity();
+        crowd.get_agents_into(&mut agents);
+        assert_eq!(capacity, agents.capacity());
+
+        let mut snapshot = CrowdSnapshot::default();
+        crowd.snapshot_into(&mut snapshot);
+        assert_eq!(vec![moving, idle], snapshot.indices);
+        assert_eq!(2, snapshot.positions.len());
+        assert_eq!(vec![1, 1], snapshot.states);
+        assert!((snapshot.positions[1].x - 8.0).abs() < 0.1 && (snapshot.positions[1].z - 15.0).abs() < 0.1);
+
+        // First delta reports everything
+        let mut delta = CrowdDelta::default();
+        crowd.delta_into(0.01, &mut delta);
+        assert_eq!(vec![moving, idle], delta.changed.indices);
+        assert!(delta.removed.is_empty());
+
+        crowd.move_agent(moving, DtVector::new(4.0, 1.0, 16.0)).unwrap();
+        for _ in 0..3 {
+            crowd.update(0.1);
+        }
+        crowd.delta_into(0.01, &mut delta);
+        assert_eq!(vec![moving], delta.changed.indices);
+        assert!(delta.changed.velocities[0].z > 0.0);
+
+        // Nothing moved far enough
+        crowd.delta_into(1000.0, &mut delta);
+        assert!(delta.changed.is_empty());
+
+        crowd.remove_agent(idle);
+        crowd.delta_into(1000.0, &mut delta);
+        assert!(delta.changed.is_empty());
+        assert_eq!(vec![idle], delta.removed);
+        crowd.delta_into(1000.0, &mut delta);
+        assert!(delta.removed.is_empty());
+
+        // A new agent in a reused slot is reported as a removal and an addition, even at the old agent's position
+        let position = crowd.get_agent(moving).unwrap().position;
+        crowd.remove_agent(moving);
+        assert_eq!(moving, crowd.add_agent(position, DtAgentParams::default()).unwrap());
+        crowd.delta_into(1000.0, &mut delta);
+        assert_eq!(vec![moving], delta.removed);
+        assert_eq!(vec![moving], delta.changed.indices);
+        crowd.delta_into(1000.0, &mut delta);
+        assert!(delta.removed.is_empty() && delta.changed.is_empty());
+    }
+
+    #[test]
+    fn crowd_move_requests() {
+        let build_settings = NavBuildSettings::default();
+        let navmesh_settings = NavmeshSettings::default(build_settings, 2048.0, 10);
+        let mut navmesh = Navmesh::new(navmesh_settings).unwrap();
+        navmesh.add_or_replace_tile(NavmeshBuilder::build_test_tile(30.0).unwrap().tile.unwrap()).unwrap();
+
+        let mut crowd = navmesh.create_crowd(10, 0.5).unwrap();
+        let idx = crowd.add_agent(DtVector::new(2.0, 1.0, 8.0), DtAgentParams::default()).unwrap();
+
+        crowd.set_velocity(idx, DtVector::new(0.0, 0.0, 2.0)).unwrap();
+        for _ in 0..5 {
+            crowd.update(0.1);
+        }
+        let info = crowd.get_agent_info(idx).unwrap();
+        assert_eq!(DtMoveRequestState::VELOCITY, info.target_state);
+        assert!(info.agent.velocity.z > 0.0);
+
+        crowd.stop_agent(idx).unwrap();
+        assert_eq!(DtMoveRequestState::NONE, crowd.get_agent_info(idx).unwrap().target_state);
+        for _ in 0..10 {
+            crowd.update(0.1);
+        }
+        assert!(crowd.get_agent(idx).unwrap().velocity.z.abs() < 0.01);
+
+        let target = DtVector::new(4.0, 1.0, 16.0);
+        let query = navmesh.query_pool.lease().unwrap();
+        let (polys, _) = query.find_poly_path(NavQuerySettings::default(), info.agent.position, target, &NavQueryFilter::default()).unwrap();
+        drop(query);
+        let target_poly = *polys.last().unwrap();
+        crowd.move_agent_to_poly(idx, target_poly, target).unwrap();
+        crowd.update(0.1);
+        let info = crowd.get_agent_info(idx).unwrap();
+        assert_eq!(target_poly, info.target_poly);
+        assert_ne!(DtMoveRequestState::FAILED, info.target_state);
+
+        crowd.remove_agent(idx);
+        assert!(crowd.stop_agent(idx).unwrap_err().has_detail(DtStatus::INVALID_PARAM));
+        assert!(crowd.set_velocity(100, DtVector::default()).unwrap_err().has_detail(DtStatus::INVALID_PARAM));
+        assert!(crowd.move_agent_to_poly(idx, target_poly, target).is_err());
+    }
+
+    #[test]
+    fn crowd_navmesh_lifetime() {
+        let build_settings = NavBuildSettings::default();
+        let navmesh_settings = NavmeshSettings::default(build_settings, 2048.0, 10);
+        let mut navmesh = Navmesh::new(navmesh_settings).unwrap();
+
+        let tile = NavmeshBuilder::build_test_tile(30.0).unwrap().tile.unwrap();
+        navmesh.add_or_replace_tile(tile.clone()).unwrap();
+
+        let mut crowd = navmesh.create_crowd(10, 0.5).unwrap();
+        let idx = crowd.add_agent(DtVector::new(2.0, 1.0, 8.0), DtAgentParams::default()).unwrap();
+        crowd.move_agent(idx, DtVector::new(4.0, 1.0, 16.0)).unwrap();
+        assert!(crowd.update(0.1));
+
+        // Updates are skipped while the navmesh drains for a tile change
+        let drain = navmesh.query_pool.drain(Duration::from_millis(10)).unwrap();
+        assert!(!crowd.update(0.1));
+        // So is everything else that touches the navmesh
+        assert_eq!(Err(NavError::QueriesInUse), crowd.add_agent(DtVector::new(2.0, 1.0, 9.0), DtAgentParams::default()));
+        assert_eq!(Err(NavError::QueriesInUse), crowd.move_agent(idx, DtVector::new(4.0, 1.0, 16.0)));
+        assert_eq!(Err(NavError::QueriesInUse), crowd.move_agent_to_poly(idx, 1, DtVector::new(4.0, 1.0, 16.0)));
+        assert_eq!(Err(NavError::QueriesInUse), crowd.set_velocity(idx, DtVector::new(0.0, 0.0, 1.0)));
+        assert_eq!(Err(NavError::QueriesInUse), crowd.stop_agent(idx));
+        drop(drain);
+        assert!(crowd.update(0.1));
+
+        // Tiles can change between updates
+        navmesh.add_or_replace_tile(tile).unwrap();
+        assert!(crowd.update(0.1));
+
+        // The crowd keeps the navmesh alive
+        drop(navmesh);
+        for _ in 0..10 {
+            assert!(crowd.update(0.1));
+        }
+        assert_eq!(idx, crowd.get_agent(idx).unwrap().index);
+    }
+}
\ No newline at end of file
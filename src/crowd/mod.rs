@@ -1,6 +1,7 @@
-use crate::common::DtVector;
+use crate::{common::{DtVector, PolyRef}, queries::StraightPathPoint};
 
 
+#[allow(clippy::module_inception)]
 pub mod crowd;
 
 #[derive(Clone, Copy, Debug)]
@@ -8,11 +9,11 @@ pub mod crowd;
 pub struct DtAgentParams {
     ///< Agent radius. [Limit: >= 0]
 	pub radius: f32,
-    ///< Agent height. [Limit: > 0]			
+    ///< Agent height. [Limit: > 0]
 	pub height: f32,
-    ///< Maximum allowed acceleration. [Limit: >= 0]						
+    ///< Maximum allowed acceleration. [Limit: >= 0]
 	pub max_acceleration: f32,
-    ///< Maximum allowed speed. [Limit: >= 0]			
+    ///< Maximum allowed speed. [Limit: >= 0]
 	pub max_speed: f32,						
 	pub collision_query_range: f32,
     ///< The path visibility optimization range. [Limit: > 0]
@@ -23,13 +24,14 @@ pub struct DtAgentParams {
 	pub optimize_topo: i32,
 	pub obstacle_avoidance: i32,
 	pub crowd_separation: i32,
-    /// Index into one of 4 presets set in AiCrowd.cpp.  Indexes 0-4.
+    /// Index of the obstacle avoidance params this agent uses, see Crowd::set_obstacle_avoidance_params.  Indexes 0-7.
 	pub obstacle_avoidance_type: i32,
+    /// Index of the crowd query filter this agent uses, see Crowd::set_filter.  Indexes 0-15.
 	pub query_filter_type: i32
 }
 
-impl DtAgentParams {
-    pub fn default() -> Self {
+impl Default for DtAgentParams {
+    fn default() -> Self {
         DtAgentParams {
             radius: 0.5,
             height: 2.0,
@@ -49,6 +51,80 @@ impl DtAgentParams {
     }
 }
 
+/// How an agent samples velocities when avoiding other agents and walls, dtObstacleAvoidanceParams.
+/// A crowd has Crowd::MAX_OBSTACLE_AVOIDANCE_TYPE slots, slots 0-3 start as the low, medium, good and high presets
+/// and the rest as default.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(C)]
+pub struct ObstacleAvoidanceParams {
+    /// How much the sampling is biased toward the desired velocity, 0-1
+    pub vel_bias: f32,
+    /// Penalty for deviating from the desired velocity
+    pub weight_des_vel: f32,
+    /// Penalty for deviating from the current velocity
+    pub weight_cur_vel: f32,
+    /// Penalty for passing other agents on the wrong side
+    pub weight_side: f32,
+    /// Penalty for velocities that collide sooner
+    pub weight_toi: f32,
+    /// Seconds ahead to look for collisions
+    pub horiz_time: f32,
+    /// Grid sampling resolution, only used when adaptive_divs is 0
+    pub grid_size: u8,
+    /// Adaptive sampling directions per ring
+    pub adaptive_divs: u8,
+    /// Adaptive sampling rings
+    pub adaptive_rings: u8,
+    /// Adaptive refinement passes
+    pub adaptive_depth: u8
+}
+
+impl ObstacleAvoidanceParams {
+    // The presets the crowd starts with in slots 0-3, the rest of the params are detour's defaults
+    fn preset(adaptive_divs: u8, adaptive_rings: u8, adaptive_depth: u8) -> Self {
+        ObstacleAvoidanceParams {
+            vel_bias: 0.5,
+            adaptive_divs,
+            adaptive_rings,
+            adaptive_depth,
+            ..Default::default()
+        }
+    }
+
+    pub fn low() -> Self {
+        Self::preset(5, 2, 1)
+    }
+
+    pub fn medium() -> Self {
+        Self::preset(5, 2, 2)
+    }
+
+    pub fn good() -> Self {
+        Self::preset(7, 2, 3)
+    }
+
+    pub fn high() -> Self {
+        Self::preset(7, 3, 3)
+    }
+}
+
+impl Default for ObstacleAvoidanceParams {
+    fn default() -> Self {
+        ObstacleAvoidanceParams {
+            vel_bias: 0.4,
+            weight_des_vel: 2.0,
+            weight_cur_vel: 0.75,
+            weight_side: 0.75,
+            weight_toi: 2.5,
+            horiz_time: 2.5,
+            grid_size: 33,
+            adaptive_divs: 7,
+            adaptive_rings: 2,
+            adaptive_depth: 5
+        }
+    }
+}
+
 pub enum CrowdAgentState
 {
     //< The agent is not in a valid state.
@@ -72,10 +148,226 @@ pub struct DtCrowdAgent
 	pub velocity: DtVector
 }
 
+/// Target state of a crowd agent, see MoveRequestState
+pub struct DtMoveRequestState {}
+
+impl DtMoveRequestState {
+    pub const NONE: u8 = 0;
+    pub const FAILED: u8 = 1;
+    pub const VALID: u8 = 2;
+    pub const REQUESTING: u8 = 3;
+    pub const WAITING_FOR_QUEUE: u8 = 4;
+    pub const WAITING_FOR_PATH: u8 = 5;
+    /// Moving by velocity instead of toward a target
+    pub const VELOCITY: u8 = 6;
+}
+
+pub const DT_CROWDAGENT_MAX_CORNERS: usize = 4;
+pub const DT_CROWDAGENT_MAX_NEIGHBOURS: usize = 6;
+/// dtLocalBoundary::MAX_LOCAL_SEGS
+pub const DT_MAX_LOCAL_SEGS: usize = 8;
+
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct DtCrowdAgentInfo
+{
+    pub agent: DtCrowdAgent,
+    pub desired_velocity: DtVector,
+    pub steering_velocity: DtVector,
+    pub current_poly: PolyRef,
+    pub target_state: u8,
+    pub target_poly: PolyRef,
+    pub target_position: DtVector,
+    pub num_corners: i32,
+    pub corner_verts: [DtVector; DT_CROWDAGENT_MAX_CORNERS],
+    pub corner_flags: [u8; DT_CROWDAGENT_MAX_CORNERS],
+    pub corner_polys: [PolyRef; DT_CROWDAGENT_MAX_CORNERS],
+    pub num_neighbours: i32,
+    pub neighbour_indices: [i32; DT_CROWDAGENT_MAX_NEIGHBOURS],
+    pub neighbour_distances: [f32; DT_CROWDAGENT_MAX_NEIGHBOURS],
+    pub num_boundary_segments: i32,
+    /// Start and end of each segment
+    pub boundary_segments: [DtVector; DT_MAX_LOCAL_SEGS * 2],
+    pub corridor_length: i32
+}
+
+/// A nearby agent the crowd steers around
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CrowdNeighbour {
+    pub index: i32,
+    pub distance: f32
+}
+
+/// Everything detour crowd tracks for an agent, see Crowd::get_agent_info
+#[derive(Clone, Debug, Default)]
+pub struct CrowdAgentInfo {
+    pub agent: DtCrowdAgent,
+    /// Velocity toward the next corner before obstacle avoidance
+    pub desired_velocity: DtVector,
+    /// desired_velocity after obstacle avoidance, what the agent accelerates toward
+    pub steering_velocity: DtVector,
+    /// The polygon the agent is on, 0 if it isn't on the navmesh
+    pub current_poly: PolyRef,
+    /// DtMoveRequestState
+    pub target_state: u8,
+    pub target_poly: PolyRef,
+    pub target_position: DtVector,
+    /// The next few corners of the path the agent is steering along
+    pub corners: Vec<StraightPathPoint>,
+    pub neighbours: Vec<CrowdNeighbour>,
+    /// Navmesh wall segments near the agent as start, end
+    pub boundary_segments: Vec<[DtVector; 2]>,
+    /// The polygon corridor from current_poly toward the target
+    pub corridor: Vec<PolyRef>
+}
+
+impl CrowdAgentInfo {
+    pub fn new(info: &DtCrowdAgentInfo, corridor: Vec<PolyRef>) -> Self {
+        let corners = (0..info.num_corners.clamp(0, DT_CROWDAGENT_MAX_CORNERS as i32) as usize)
+            .map(|i| StraightPathPoint { position: info.corner_verts[i], flags: info.corner_flags[i], poly: info.corner_polys[i] })
+            .collect();
+        let neighbours = (0..info.num_neighbours.clamp(0, DT_CROWDAGENT_MAX_NEIGHBOURS as i32) as usize)
+            .map(|i| CrowdNeighbour { index: info.neighbour_indices[i], distance: info.neighbour_distances[i] })
+            .collect();
+        let boundary_segments = (0..info.num_boundary_segments.clamp(0, DT_MAX_LOCAL_SEGS as i32) as usize)
+            .map(|i| [info.boundary_segments[i * 2], info.boundary_segments[i * 2 + 1]])
+            .collect();
+
+        CrowdAgentInfo {
+            agent: info.agent,
+            desired_velocity: info.desired_velocity,
+            steering_velocity: info.steering_velocity,
+            current_poly: info.current_poly,
+            target_state: info.target_state,
+            target_poly: info.target_poly,
+            target_position: info.target_position,
+            corners,
+            neighbours,
+            boundary_segments,
+            corridor
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 #[repr(C)]
 pub struct DtCrowdAgentsResult
 {
 	pub agents: *mut DtCrowdAgent,
 	pub agent_count: i32
-}
\ No newline at end of file
+}
+
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct DtCrowdAgentSnapshot
+{
+    pub indices: *mut i32,
+    pub positions: *mut DtVector,
+    pub velocities: *mut DtVector,
+    pub states: *mut u8,
+    pub max_agents: i32,
+    pub agent_count: i32
+}
+
+/// Active agents as parallel arrays, see Crowd::snapshot_into.  Entry i of each array is the same agent.
+/// Buffers are reused between calls so steady state snapshots don't allocate.
+#[derive(Clone, Debug, Default)]
+pub struct CrowdSnapshot {
+    /// Agent index as used by Crowd::get_agent and friends
+    pub indices: Vec<i32>,
+    pub positions: Vec<DtVector>,
+    pub velocities: Vec<DtVector>,
+    /// CrowdAgentState as u8
+    pub states: Vec<u8>
+}
+
+impl CrowdSnapshot {
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.indices.clear();
+        self.positions.clear();
+        self.velocities.clear();
+        self.states.clear();
+    }
+
+    fn push(&mut self, index: i32, position: DtVector, velocity: DtVector, state: u8) {
+        self.indices.push(index);
+        self.positions.push(position);
+        self.velocities.push(velocity);
+        self.states.push(state);
+    }
+
+    fn resize(&mut self, len: usize) {
+        self.indices.resize(len, 0);
+        self.positions.resize(len, DtVector::default());
+        self.velocities.resize(len, DtVector::default());
+        self.states.resize(len, 0);
+    }
+}
+
+/// Agents that changed since the last Crowd::delta_into call with this delta.  Keep one per consumer,
+/// it remembers the positions it last reported.
+#[derive(Clone, Debug, Default)]
+pub struct CrowdDelta {
+    /// New agents and agents that moved more than the epsilon since they were last reported
+    pub changed: CrowdSnapshot,
+    /// Indices of agents removed since the last call.  A slot reused by a new agent is also in changed, apply removed first
+    pub removed: Vec<i32>,
+    current: CrowdSnapshot,
+    /// Slot generation and last reported position per agent index, None for inactive slots
+    reported: Vec<Option<(u32, DtVector)>>,
+    seen: Vec<bool>
+}
+
+impl CrowdDelta {
+    fn update(&mut self, epsilon: f32, generations: &[u32]) {
+        self.changed.clear();
+        self.removed.clear();
+
+        let max_agents = self.reported.len();
+        self.seen.clear();
+        self.seen.resize(max_agents, false);
+
+        let epsilon_sqr = epsilon * epsilon;
+        for i in 0..self.current.len() {
+            let index = self.current.indices[i];
+            let Some(slot) = usize::try_from(index).ok().filter(|&slot| slot < max_agents) else {
+                continue;
+            };
+            self.seen[slot] = true;
+
+            let position = self.current.positions[i];
+            let generation = generations.get(slot).copied().unwrap_or(0);
+            let moved = match self.reported[slot] {
+                Some((reported_generation, reported)) if reported_generation == generation => {
+                    let offset = position - reported;
+                    offset.x * offset.x + offset.y * offset.y + offset.z * offset.z > epsilon_sqr
+                },
+                Some(_) => {
+                    // The agent that was reported is gone and a new one took its slot
+                    self.removed.push(index);
+                    true
+                },
+                None => true
+            };
+            if moved {
+                self.reported[slot] = Some((generation, position));
+                self.changed.push(index, position, self.current.velocities[i], self.current.states[i]);
+            }
+        }
+
+        for (slot, reported) in self.reported.iter_mut().enumerate() {
+            if reported.is_some() && !self.seen[slot] {
+                *reported = None;
+                self.removed.push(slot as i32);
+            }
+        }
+    }
+}
@@ -4,3 +4,4 @@ pub mod bindings;
 pub mod common;
 pub mod queries;
 pub mod crowd;
+pub mod tile_cache;
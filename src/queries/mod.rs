@@ -1,22 +1,26 @@
-use crate::common::DtVector;
+use crate::common::{DtVector, DtArea, PolyRef, nav_error::{DtStatus, NavError}};
 
 
 pub mod nav_query;
+pub mod path_service;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 #[repr(C)]
 pub struct NavQuerySettings {
     pub find_nearest_poly_extent: DtVector,
     pub max_path_points: i32
 }
 
-impl NavQuerySettings {
-    pub fn default() -> Self {
+impl Default for NavQuerySettings {
+    fn default() -> Self {
         NavQuerySettings {
             find_nearest_poly_extent: DtVector::new(2.0, 4.0, 2.0),
             max_path_points: 512
         }
     }
+}
+
+impl NavQuerySettings {
 
     pub fn lenient() -> Self {
         NavQuerySettings {
@@ -26,34 +30,182 @@ impl NavQuerySettings {
     }
 }
 
+/// Per area traversal costs and polygon flag masks.  Polygon area ids come from the areas given to TileInputBuilder.
+/// A polygon is visited if (flags & include_flags) != 0 and (flags & exclude_flags) == 0
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(C)]
+pub struct NavQueryFilter {
+    pub area_cost: [f32; DtArea::MAX_AREAS],
+    pub include_flags: u16,
+    pub exclude_flags: u16
+}
+
+impl Default for NavQueryFilter {
+    fn default() -> Self {
+        NavQueryFilter {
+            area_cost: [1.0; DtArea::MAX_AREAS],
+            include_flags: 0xffff,
+            exclude_flags: 0
+        }
+    }
+}
+
+impl NavQueryFilter {
+    /// area is masked to 0-63 like detour does
+    pub fn area_cost(&self, area: u8) -> f32 {
+        self.area_cost[(area & DtArea::MASK) as usize]
+    }
+
+    /// area is 0-63, higher values are masked to 6 bits like detour does
+    pub fn set_area_cost(&mut self, area: u8, cost: f32) {
+        self.area_cost[(area & DtArea::MASK) as usize] = cost;
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 #[repr(C)]
 pub struct DtPathFindQuery {
     pub source: DtVector,
     pub target: DtVector,
     pub find_nearest_poly_extent: DtVector,
-    pub max_path_points: i32
+    pub max_path_points: i32,
+    /// DtStraightPathOptions
+    pub straight_path_options: i32
 }
 
 impl DtPathFindQuery {
+    /// max_path_points is clamped to 0, buffers are sized from it
     pub fn new(source: DtVector, target: DtVector, settings: &NavQuerySettings) -> Self {
         DtPathFindQuery {
             source,
             target,
             find_nearest_poly_extent: settings.find_nearest_poly_extent,
-            max_path_points: settings.max_path_points
+            max_path_points: settings.max_path_points.max(0),
+            straight_path_options: 0
         }
     }
 }
 
+/// Flags set on each straight path point, see dtStraightPathFlags
+pub struct DtStraightPathFlags {}
+
+impl DtStraightPathFlags {
+    pub const START: u8 = 0x01;
+    pub const END: u8 = 0x02;
+    /// The point is the start of an off-mesh connection.  The next point in the path is the connection end.
+    pub const OFFMESH_CONNECTION: u8 = 0x04;
+}
+
+/// Options for adding extra straight path points, see dtStraightPathOptions
+pub struct DtStraightPathOptions {}
+
+impl DtStraightPathOptions {
+    pub const NONE: i32 = 0;
+    /// Add a point at every polygon edge crossing where the area changes
+    pub const AREA_CROSSINGS: i32 = 0x01;
+    /// Add a point at every polygon edge crossing
+    pub const ALL_CROSSINGS: i32 = 0x02;
+}
+
+/// How far a path reaches toward the target
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PathStatus {
+    /// The path ends at the target
+    Complete,
+    /// The target can't be reached.  The path ends at the point closest to the target on the reachable polygon nearest to it
+    Partial,
+    /// Source or target isn't on the navmesh or the search failed
+    NoPath
+}
+
+impl PathStatus {
+    /// Status of a successful path search
+    pub fn from_status(status: DtStatus) -> Self {
+        if status.failed() {
+            PathStatus::NoPath
+        } else if status.has_detail(DtStatus::PARTIAL_RESULT) {
+            PathStatus::Partial
+        } else {
+            PathStatus::Complete
+        }
+    }
+
+    pub fn from_result(result: Result<DtStatus, NavError>) -> Self {
+        match result {
+            Ok(status) => PathStatus::from_status(status),
+            Err(_) => PathStatus::NoPath
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        *self == PathStatus::Complete
+    }
+
+    /// Complete or Partial
+    pub fn has_path(&self) -> bool {
+        *self != PathStatus::NoPath
+    }
+}
+
+/// State of a SlicedPathRequest
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SliceStatus {
+    /// The search needs more steps
+    InProgress,
+    /// The search finished, finalize returns the path
+    Done,
+    /// The search failed, eg a polygon on the open list was removed with its tile
+    Failed(DtStatus)
+}
+
+impl SliceStatus {
+    pub fn from_status(status: DtStatus) -> Self {
+        if status.failed() {
+            SliceStatus::Failed(status)
+        } else if status.in_progress() {
+            SliceStatus::InProgress
+        } else {
+            SliceStatus::Done
+        }
+    }
+}
+
+/// A straight path point with the flags and polygon detour returned for it
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StraightPathPoint {
+    pub position: DtVector,
+    /// DtStraightPathFlags
+    pub flags: u8,
+    /// The polygon the path enters at this point.  For off-mesh connection starts this is the connection polygon
+    pub poly: PolyRef
+}
+
+impl StraightPathPoint {
+    pub fn is_off_mesh_connection(&self) -> bool {
+        self.flags & DtStraightPathFlags::OFFMESH_CONNECTION != 0
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 #[repr(C)]
 pub struct DtPathFindResult {
     pub path_found: bool,
     pub path_points: *mut DtVector,
+    /// DtStraightPathFlags for each path point
+    pub path_flags: *mut u8,
+    /// Optional, null if not wanted
+    pub path_polys: *mut PolyRef,
     pub num_path_points: i32
 }
 
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct DtPolyPathResult {
+    pub path_found: bool,
+    pub polys: *mut PolyRef,
+    pub num_polys: i32
+}
+
 #[derive(Clone, Copy, Debug)]
 #[repr(C)]
 pub struct DtRaycastQuery {
@@ -63,10 +215,34 @@ pub struct DtRaycastQuery {
     pub max_path_points: i32
 }
 
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug)]
 #[repr(C)]
 pub struct DtRaycastResult {
+    /// false if no start polygon was found or the raycast failed
+    pub found: bool,
+    pub hit: bool,
+    pub t: f32,
+    pub position: DtVector,
+    pub normal: DtVector,
+    /// Buffer of max_path_points poly refs that receives the visited polygons
+    pub polys: *mut PolyRef,
+    pub num_polys: i32,
+    pub path_cost: f32
+}
+
+/// Result of a walkability raycast along the navmesh surface
+#[derive(Clone, Debug, Default)]
+pub struct NavRaycastHit {
+    /// true if a wall was hit before the ray reached the target
     pub hit: bool,
+    /// Fraction along source -> target where the wall was hit.  1.0 when there was no hit
+    pub t: f32,
+    /// Normal of the wall that was hit.  Zero when there was no hit
+    pub normal: DtVector,
+    /// The hit position, or the target if there was no hit.  Height is on the surface of the last visited polygon
     pub position: DtVector,
-    pub normal: DtVector
+    /// Polygons visited by the ray, starting with the start polygon
+    pub polys: Vec<PolyRef>,
+    /// Cost of the visited polygons using the filter's area costs
+    pub path_cost: f32
 }
\ No newline at end of file
@@ -1,7 +1,10 @@
 
-use crate::{bindings::{QueryDestroy, RawNavqueryPtr, RawNavmeshPtr, QueryCreate, QueryFindStraightPath, QuerySamplePosition, QueryGetLocation, QueryRaycast, QueryHasPath}, common::DtVector};
+use crate::{bindings::{QueryDestroy, RawNavqueryPtr, QueryCreate, QueryFindStraightPath, QuerySamplePosition, QueryGetLocation, QueryRaycast, QueryHasPath, QueryFindPolyPath, QueryGetRandomPosition, QueryGetRandomPositionAround, QueryInitSlicedPath, QueryUpdateSlicedPath, QueryFinalizeSlicedPath}, common::{DtVector, PolyRef, nav_error::NavError, navmesh::NavmeshPtr}};
 
-use super::{NavQuerySettings, DtPathFindQuery, DtPathFindResult, DtRaycastQuery, DtRaycastResult};
+use super::{NavQuerySettings, DtPathFindQuery, DtPathFindResult, DtRaycastQuery, DtRaycastResult, NavRaycastHit, NavQueryFilter, DtPolyPathResult, StraightPathPoint, PathStatus, SliceStatus};
+
+use std::{any::Any, ffi::c_void, panic::{self, AssertUnwindSafe}, sync::Arc};
+use rand::Rng;
 
 struct NavqueryPtr(*mut RawNavqueryPtr);
 unsafe impl Send for NavqueryPtr {}
@@ -9,103 +12,225 @@ unsafe impl Send for NavqueryPtr {}
 pub struct NavQuery {
     query_ptr: NavqueryPtr,
     result_path: Vec<DtVector>,
-    max_path_points: i32
+    result_path_flags: Vec<u8>,
+    max_path_points: i32,
+    /// Keeps the C navmesh alive while the query points into it.  Dropped after the query is destroyed in drop
+    _navmesh: Arc<NavmeshPtr>
 }
 
 impl NavQuery {
-    pub fn new(navmesh_ptr: *mut RawNavmeshPtr, max_nodes: i32, max_path_points: i32) -> Option<Self> {
-        let query_ptr = unsafe {QueryCreate(navmesh_ptr, max_nodes)};
+    pub(crate) fn new(navmesh: Arc<NavmeshPtr>, max_nodes: i32, max_path_points: i32) -> Result<Self, NavError> {
+        let query_ptr = unsafe {QueryCreate(navmesh.raw(), max_nodes)};
         if query_ptr.is_null() {
-            return None;
+            return Err(NavError::CreateFailed("navmesh query"));
         }
         
+        let max_path_points = max_path_points.max(0);
         let query = NavQuery {
             query_ptr: NavqueryPtr(query_ptr),
             result_path: vec![DtVector::default();max_path_points as usize],
-            max_path_points
+            result_path_flags: vec![0;max_path_points as usize],
+            max_path_points,
+            _navmesh: navmesh
         };
-        Some(query)
+        Ok(query)
     }
 
     pub fn get_path(&self, len: usize) -> &[DtVector] {
         &self.result_path[0..len]
     }
 
-    pub fn has_path(&self, query_settings: NavQuerySettings, source: DtVector, target: DtVector) -> bool {
+    /// DtStraightPathFlags for each point returned by get_path.
+    /// Use these to detect off-mesh connections along the path.
+    pub fn get_path_flags(&self, len: usize) -> &[u8] {
+        &self.result_path_flags[0..len]
+    }
+
+    /// Partial if only a partial path reaches toward target.  NoPath if source or target aren't on the navmesh
+    pub fn has_path(&self, query_settings: NavQuerySettings, source: DtVector, target: DtVector, filter: &NavQueryFilter) -> PathStatus {
         let query = DtPathFindQuery {
             source,
             target,
             find_nearest_poly_extent: query_settings.find_nearest_poly_extent,
-            max_path_points: query_settings.max_path_points.min(self.max_path_points),
+            max_path_points: query_settings.max_path_points.clamp(0, self.max_path_points),
+            straight_path_options: 0,
         };
         
-        unsafe {
-            QueryHasPath(self.query_ptr.0, &query as *const DtPathFindQuery) == 1
-        }
+        let status = unsafe {
+            QueryHasPath(self.query_ptr.0, &query as *const DtPathFindQuery, filter as *const NavQueryFilter)
+        };
+        PathStatus::from_status(status)
     }
 
-    pub fn find_path(&mut self, query_settings: NavQuerySettings, source: DtVector, target: DtVector) -> i32 {
+    /// The number of path points, read them with get_path.  A Partial path ends at the reachable point closest to target
+    pub fn find_path(&mut self, query_settings: NavQuerySettings, source: DtVector, target: DtVector, filter: &NavQueryFilter) -> Result<(usize, PathStatus), NavError> {
         let query = DtPathFindQuery {
             source,
             target,
             find_nearest_poly_extent: query_settings.find_nearest_poly_extent,
-            max_path_points: query_settings.max_path_points.min(self.max_path_points),
+            max_path_points: query_settings.max_path_points.clamp(0, self.max_path_points),
+            straight_path_options: 0,
         };
         
         unsafe {
             let mut result = DtPathFindResult {
                 path_found: false,
-                path_points: self.result_path.as_mut_ptr() as *mut DtVector,
+                path_points: self.result_path.as_mut_ptr(),
+                path_flags: self.result_path_flags.as_mut_ptr(),
+                path_polys: std::ptr::null_mut(),
                 num_path_points: 0,
             };
-            QueryFindStraightPath(self.query_ptr.0, &query as *const DtPathFindQuery, &mut result as *mut DtPathFindResult);
-            result.num_path_points
+            let status = QueryFindStraightPath(self.query_ptr.0, &query as *const DtPathFindQuery, filter as *const NavQueryFilter, &mut result as *mut DtPathFindResult).to_result()?;
+            Ok((result.num_path_points as usize, PathStatus::from_status(status)))
         }
     }
 
+    /// The polygon corridor from the polygon nearest source to the polygon nearest target.
+    /// A Partial corridor ends at the reachable polygon closest to target
+    pub fn find_poly_path(&self, query_settings: NavQuerySettings, source: DtVector, target: DtVector, filter: &NavQueryFilter) -> Result<(Vec<PolyRef>, PathStatus), NavError> {
+        let query = DtPathFindQuery::new(source, target, &query_settings);
+        let mut polys: Vec<PolyRef> = vec![0; query.max_path_points as usize];
+        let mut result = DtPolyPathResult {
+            path_found: false,
+            polys: polys.as_mut_ptr(),
+            num_polys: 0,
+        };
+
+        let status = unsafe {
+            QueryFindPolyPath(self.query_ptr.0, &query as *const DtPathFindQuery, filter as *const NavQueryFilter, &mut result as *mut DtPolyPathResult).to_result()?
+        };
+        polys.truncate(result.num_polys as usize);
+        Ok((polys, PathStatus::from_status(status)))
+    }
+
+    /// Same as find_path but returns each point with its DtStraightPathFlags and poly ref.
+    /// straight_path_options is DtStraightPathOptions, use AREA_CROSSINGS to get a point wherever the area changes.
+    pub fn find_straight_path_detailed(&self, query_settings: NavQuerySettings, source: DtVector, target: DtVector, filter: &NavQueryFilter, straight_path_options: i32) -> Result<(Vec<StraightPathPoint>, PathStatus), NavError> {
+        let mut query = DtPathFindQuery::new(source, target, &query_settings);
+        query.straight_path_options = straight_path_options;
+
+        let max_path_points = query.max_path_points as usize;
+        let mut points: Vec<DtVector> = vec![DtVector::default(); max_path_points];
+        let mut flags: Vec<u8> = vec![0; max_path_points];
+        let mut polys: Vec<PolyRef> = vec![0; max_path_points];
+        let mut result = DtPathFindResult {
+            path_found: false,
+            path_points: points.as_mut_ptr(),
+            path_flags: flags.as_mut_ptr(),
+            path_polys: polys.as_mut_ptr(),
+            num_path_points: 0,
+        };
+
+        let status = unsafe {
+            QueryFindStraightPath(self.query_ptr.0, &query as *const DtPathFindQuery, filter as *const NavQueryFilter, &mut result as *mut DtPathFindResult).to_result()?
+        };
+
+        let points = (0..result.num_path_points as usize)
+            .map(|i| StraightPathPoint { position: points[i], flags: flags[i], poly: polys[i] })
+            .collect();
+        Ok((points, PathStatus::from_status(status)))
+    }
+
+    /// Starts a path search that is run a few iterations at a time with SlicedPathRequest::step, so long paths can be spread over frames.
+    /// The request borrows the query until it is finalized or dropped.  straight_path_options is DtStraightPathOptions for the final path
+    pub fn start_sliced_path(&mut self, query_settings: NavQuerySettings, source: DtVector, target: DtVector, filter: &NavQueryFilter, straight_path_options: i32) -> Result<SlicedPathRequest<'_>, NavError> {
+        let mut query = DtPathFindQuery::new(source, target, &query_settings);
+        query.straight_path_options = straight_path_options;
+
+        let status = unsafe {
+            QueryInitSlicedPath(self.query_ptr.0, &query as *const DtPathFindQuery, filter as *const NavQueryFilter).to_result()?
+        };
+        Ok(SlicedPathRequest {
+            query: self,
+            status: SliceStatus::from_status(status),
+            iterations: 0,
+            max_path_points: query.max_path_points as usize
+        })
+    }
+
     /// sample_position does not use the detail mesh, height will not match surface
-    pub fn sample_position(&self, point: &DtVector, extent: &DtVector) -> Option<DtVector> {
+    pub fn sample_position(&self, point: &DtVector, extent: &DtVector, filter: &NavQueryFilter) -> Result<DtVector, NavError> {
         let mut result = DtVector::default();
 
         unsafe {
-            let res = QuerySamplePosition(self.query_ptr.0, point as *const DtVector, extent as *const DtVector, &mut result as *mut DtVector);
-            if res == 1 {
-                Some(result)
-            } else {
-                None
-            }
+            QuerySamplePosition(self.query_ptr.0, point as *const DtVector, extent as *const DtVector, filter as *const NavQueryFilter, &mut result as *mut DtVector).to_result()?;
         }
+        Ok(result)
     }
 
     /// get_location is the same as SamplePosition but it does use the detail mesh, returning the surface height
     /// You call this every frame while moving an agent over the path to get the correct height to place them at
-    pub fn get_location(&self, point: &DtVector, extent: &DtVector) -> Option<DtVector> {
+    pub fn get_location(&self, point: &DtVector, extent: &DtVector, filter: &NavQueryFilter) -> Result<DtVector, NavError> {
         let mut result = DtVector::default();
 
         unsafe {
-            let res = QueryGetLocation(self.query_ptr.0, point as *const DtVector, extent as *const DtVector, &mut result as *mut DtVector);
-            if res == 1 {
-                Some(result)
-            } else {
-                None
-            }
+            QueryGetLocation(self.query_ptr.0, point as *const DtVector, extent as *const DtVector, filter as *const NavQueryFilter, &mut result as *mut DtVector).to_result()?;
         }
+        Ok(result)
     }
 
-    /// Does not work as expected.  Need to redo C implementation.
-    pub fn raycast(&self, query_settings: NavQuerySettings, source: DtVector, target: DtVector) -> DtRaycastResult {
+    /// Random point on the navmesh, chosen uniformly by area.  All randomness comes from rng so a seeded rng gives repeatable results.
+    /// A panic in rng is carried across the C call and resumed once detour returns
+    pub fn random_point<R: Rng>(&self, filter: &NavQueryFilter, rng: &mut R) -> Result<DtVector, NavError> {
+        let mut result = DtVector::default();
+        let mut context = RandomContext { rng, panic: None };
+
+        let status = unsafe {
+            QueryGetRandomPosition(self.query_ptr.0, filter as *const NavQueryFilter, random_float::<R>, &mut context as *mut RandomContext<R> as *mut c_void, &mut result as *mut DtVector)
+        };
+        context.resume_panic();
+        status.to_result()?;
+        Ok(result)
+    }
+
+    /// Random point reachable from center within roughly radius.  center is snapped to the navmesh using extent.
+    /// Polygons are picked by area so the point can fall slightly outside radius on large polygons.  Panics in rng are resumed like random_point
+    pub fn random_point_around<R: Rng>(&self, center: &DtVector, extent: &DtVector, radius: f32, filter: &NavQueryFilter, rng: &mut R) -> Result<DtVector, NavError> {
+        let mut result = DtVector::default();
+        let mut context = RandomContext { rng, panic: None };
+
+        let status = unsafe {
+            QueryGetRandomPositionAround(self.query_ptr.0, center as *const DtVector, extent as *const DtVector, radius, filter as *const NavQueryFilter, random_float::<R>, &mut context as *mut RandomContext<R> as *mut c_void, &mut result as *mut DtVector)
+        };
+        context.resume_panic();
+        status.to_result()?;
+        Ok(result)
+    }
+
+    /// Casts a walkability ray along the navmesh surface from source toward target.
+    /// Fails with NO_NEAREST_POLY if source is not on the navmesh.  Line of sight is clear when the result's hit is false.
+    pub fn raycast(&self, query_settings: NavQuerySettings, source: DtVector, target: DtVector, filter: &NavQueryFilter) -> Result<NavRaycastHit, NavError> {
         let query = DtRaycastQuery {
             source,
             target,
             find_nearest_poly_extent: query_settings.find_nearest_poly_extent,
-            max_path_points: query_settings.max_path_points,
+            max_path_points: query_settings.max_path_points.max(0),
         };
 
-        let mut result = DtRaycastResult::default();
+        let mut polys: Vec<PolyRef> = vec![0; query.max_path_points as usize];
+        let mut result = DtRaycastResult {
+            found: false,
+            hit: false,
+            t: 1.0,
+            position: DtVector::default(),
+            normal: DtVector::default(),
+            polys: polys.as_mut_ptr(),
+            num_polys: 0,
+            path_cost: 0.0
+        };
         unsafe {
-            QueryRaycast(self.query_ptr.0, &query as *const DtRaycastQuery, &mut result as *mut DtRaycastResult);
-            result
+            QueryRaycast(self.query_ptr.0, &query as *const DtRaycastQuery, filter as *const NavQueryFilter, &mut result as *mut DtRaycastResult).to_result()?;
         }
+
+        polys.truncate(result.num_polys as usize);
+        Ok(NavRaycastHit {
+            hit: result.hit,
+            t: result.t,
+            normal: result.normal,
+            position: result.position,
+            polys,
+            path_cost: result.path_cost
+        })
     }
 
     pub fn test(&self) {
@@ -113,6 +238,103 @@ impl NavQuery {
     }
 }
 
+/// An in progress sliced path search, see NavQuery::start_sliced_path
+pub struct SlicedPathRequest<'a> {
+    query: &'a mut NavQuery,
+    status: SliceStatus,
+    iterations: usize,
+    max_path_points: usize
+}
+
+impl<'a> SlicedPathRequest<'a> {
+    /// Runs up to max_iters more iterations of the search.  Each iteration visits one polygon
+    pub fn step(&mut self, max_iters: i32) -> SliceStatus {
+        if self.status != SliceStatus::InProgress {
+            return self.status;
+        }
+
+        let mut done_iters = 0;
+        let status = unsafe { QueryUpdateSlicedPath(self.query.query_ptr.0, max_iters, &mut done_iters as *mut i32) };
+        self.iterations += done_iters.max(0) as usize;
+        self.status = SliceStatus::from_status(status);
+        self.status
+    }
+
+    pub fn status(&self) -> SliceStatus {
+        self.status
+    }
+
+    /// Total iterations run by step
+    pub fn iterations(&self) -> usize {
+        self.iterations
+    }
+
+    /// The straight path to target.  Can be called while the search is still InProgress,
+    /// the path then ends at the best polygon found so far and is Partial
+    pub fn finalize(self) -> Result<(Vec<StraightPathPoint>, PathStatus), NavError> {
+        self.finalize_internal(&[])
+    }
+
+    /// Same as finalize but keeps as much of existing as possible, ending at the furthest polygon of existing the search visited.
+    /// Use it when replanning an existing corridor so the agent doesn't turn back
+    pub fn finalize_partial(self, existing: &[PolyRef]) -> Result<(Vec<StraightPathPoint>, PathStatus), NavError> {
+        self.finalize_internal(existing)
+    }
+
+    fn finalize_internal(self, existing: &[PolyRef]) -> Result<(Vec<StraightPathPoint>, PathStatus), NavError> {
+        if let SliceStatus::Failed(status) = self.status {
+            return Err(NavError::Status(status));
+        }
+
+        let mut points: Vec<DtVector> = vec![DtVector::default(); self.max_path_points];
+        let mut flags: Vec<u8> = vec![0; self.max_path_points];
+        let mut polys: Vec<PolyRef> = vec![0; self.max_path_points];
+        let mut result = DtPathFindResult {
+            path_found: false,
+            path_points: points.as_mut_ptr(),
+            path_flags: flags.as_mut_ptr(),
+            path_polys: polys.as_mut_ptr(),
+            num_path_points: 0,
+        };
+
+        let existing_ptr = if existing.is_empty() { std::ptr::null() } else { existing.as_ptr() };
+        let status = unsafe {
+            QueryFinalizeSlicedPath(self.query.query_ptr.0, existing_ptr, existing.len() as i32, &mut result as *mut DtPathFindResult).to_result()?
+        };
+
+        let points = (0..result.num_path_points as usize)
+            .map(|i| StraightPathPoint { position: points[i], flags: flags[i], poly: polys[i] })
+            .collect();
+        Ok((points, PathStatus::from_status(status)))
+    }
+}
+
+/// Passed through detour to random_float.  Unwinding into C aborts, so a panic in rng is stored here instead
+struct RandomContext<'a, R> {
+    rng: &'a mut R,
+    panic: Option<Box<dyn Any + Send>>
+}
+
+impl<R> RandomContext<'_, R> {
+    fn resume_panic(&mut self) {
+        if let Some(payload) = self.panic.take() {
+            panic::resume_unwind(payload);
+        }
+    }
+}
+
+extern "C" fn random_float<R: Rng>(context: *mut c_void) -> f32 {
+    let context = unsafe { &mut *(context as *mut RandomContext<R>) };
+    if context.panic.is_some() {
+        return 0.0;
+    }
+    let rng = &mut *context.rng;
+    panic::catch_unwind(AssertUnwindSafe(|| rng.gen::<f32>())).unwrap_or_else(|payload| {
+        context.panic = Some(payload);
+        0.0
+    })
+}
+
 impl Drop for NavQuery {
     fn drop(&mut self) {
         unsafe {QueryDestroy(self.query_ptr.0)};
@@ -122,48 +344,411 @@ impl Drop for NavQuery {
 
 #[cfg(test)]
 mod tests {
-    use crate::{building::{navmesh_builder::NavmeshBuilder, NavBuildSettings}, common::{navmesh::{Navmesh, NavmeshSettings}, DtVector}, queries::NavQuerySettings};
+    use crate::{building::{navmesh_builder::NavmeshBuilder, NavBuildSettings, navmesh_build_utils::NavmeshBuildUtils, tile_input_builder::TileInputBuilder, DtOffMeshConnection}, common::{nav_error::DtStatus, navmesh::{Navmesh, NavmeshSettings}, DtVector, DtInt2, DtArea, NavAgentSettings}, queries::{NavQuerySettings, DtStraightPathFlags, NavQueryFilter, DtStraightPathOptions, PathStatus, SliceStatus}};
+    use rand::{rngs::StdRng, RngCore, SeedableRng};
+    use std::panic::{self, AssertUnwindSafe};
 
 
     #[test]
     fn basic_queries() {
-        let result = NavmeshBuilder::build_test_tile(30.0);
+        let result = NavmeshBuilder::build_test_tile(30.0).unwrap();
         let tile = result.tile.unwrap();
 
         let build_settings = NavBuildSettings::default();
         let navmesh_settings = NavmeshSettings::default(build_settings, 2048.0, 10);
         let mut navmesh = Navmesh::new(navmesh_settings).unwrap();
-        navmesh.add_or_replace_tile(tile);
+        navmesh.add_or_replace_tile(tile).unwrap();
 
         let mut query = navmesh.query_pool.pop().unwrap();
+        let filter = NavQueryFilter::default();
 
         let start = DtVector::new(1.0, 1.0, 1.0);
         let end = DtVector::new(10.0, 1.0, 10.0);
-        let path_len = query.find_path(NavQuerySettings::default(), start, end);
+        let (path_len, status) = query.find_path(NavQuerySettings::default(), start, end, &filter).unwrap();
+        assert_eq!(PathStatus::Complete, status);
         println!("path len {:?}", path_len);
-        for point in query.get_path(path_len as usize) {
+        for point in query.get_path(path_len) {
             println!("{:?}", point);
         }
-        let has_path = query.has_path(NavQuerySettings::default(), start, end);
-        assert!(has_path);
+        assert_eq!(PathStatus::Complete, query.has_path(NavQuerySettings::default(), start, end, &filter));
 
         let start = DtVector::new(1.0, 1.0, 1.0);
         let end = DtVector::new(1000.0, 1.0, 1000.0);
-        let path_len = query.find_path(NavQuerySettings::default(), start, end);
-        assert_eq!(0, path_len);
+        let error = query.find_path(NavQuerySettings::default(), start, end, &filter).unwrap_err();
+        assert!(error.has_detail(DtStatus::NO_NEAREST_POLY));
+        assert_eq!(PathStatus::NoPath, query.has_path(NavQuerySettings::default(), start, end, &filter));
 
-        let result = query.sample_position(&start, &DtVector::new(2.0, 2.0, 2.0));
-        assert!(result.is_some());
+        let result = query.sample_position(&start, &DtVector::new(2.0, 2.0, 2.0), &filter);
+        assert!(result.is_ok());
 
-        let result = query.get_location(&start, &DtVector::new(2.0, 2.0, 2.0));
-        assert!(result.is_some());
+        let result = query.get_location(&start, &DtVector::new(2.0, 2.0, 2.0), &filter);
+        assert!(result.is_ok());
         let result = result.unwrap();
         println!("{:?}", result);
 
         let start = DtVector::new(1.0, 4.0, 1.0);
         let end = DtVector::new(10.0, 4.0, 10.0);
-        let result = query.raycast(NavQuerySettings::default(), start, end);
+        let result = query.raycast(NavQuerySettings::default(), start, end, &filter).unwrap();
         println!("{:?}", result);
         assert!(result.hit);
     }
+
+    #[test]
+    fn random_points() {
+        let result = NavmeshBuilder::build_test_tile(30.0).unwrap();
+        let tile = result.tile.unwrap();
+
+        let build_settings = NavBuildSettings::default();
+        let navmesh_settings = NavmeshSettings::default(build_settings, 2048.0, 10);
+        let mut navmesh = Navmesh::new(navmesh_settings).unwrap();
+        navmesh.add_or_replace_tile(tile).unwrap();
+        let query = navmesh.query_pool.pop().unwrap();
+        let filter = NavQueryFilter::default();
+
+        let mut rng = StdRng::seed_from_u64(7);
+        let points: Vec<DtVector> = (0..10).map(|_| query.random_point(&filter, &mut rng).unwrap()).collect();
+        for point in &points {
+            // Only the x < z half of the test tile is walkable
+            assert!(point.x <= point.z + 0.5);
+            assert!(point.x >= 0.0 && point.z <= 20.0);
+        }
+
+        // Same seed, same points
+        let mut rng = StdRng::seed_from_u64(7);
+        for point in &points {
+            assert_eq!(*point, query.random_point(&filter, &mut rng).unwrap());
+        }
+
+        let center = DtVector::new(4.0, 1.0, 12.0);
+        let extent = DtVector::new(2.0, 2.0, 2.0);
+        let mut rng = StdRng::seed_from_u64(11);
+        let around: Vec<DtVector> = (0..10).map(|_| query.random_point_around(&center, &extent, 3.0, &filter, &mut rng).unwrap()).collect();
+        for point in &around {
+            assert!(point.x <= point.z + 0.5);
+        }
+        let mut rng = StdRng::seed_from_u64(11);
+        for point in &around {
+            assert_eq!(*point, query.random_point_around(&center, &extent, 3.0, &filter, &mut rng).unwrap());
+        }
+
+        let outside = DtVector::new(1000.0, 1.0, 1000.0);
+        assert!(query.random_point_around(&outside, &extent, 3.0, &filter, &mut rng).is_err());
+
+        let blocked = NavQueryFilter { include_flags: 0, ..Default::default() };
+        assert!(query.random_point(&blocked, &mut rng).is_err());
+
+        // A panicking rng unwinds out of random_point instead of aborting in the C callback
+        let result = panic::catch_unwind(AssertUnwindSafe(|| query.random_point(&filter, &mut PanickingRng)));
+        assert_eq!(Some(&"rng failed"), result.unwrap_err().downcast_ref::<&str>());
+        let result = panic::catch_unwind(AssertUnwindSafe(|| query.random_point_around(&center, &extent, 3.0, &filter, &mut PanickingRng)));
+        assert!(result.is_err());
+        query.random_point(&filter, &mut rng).unwrap();
+    }
+
+    struct PanickingRng;
+
+    impl RngCore for PanickingRng {
+        fn next_u32(&mut self) -> u32 {
+            panic!("rng failed")
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            panic!("rng failed")
+        }
+
+        fn fill_bytes(&mut self, _dest: &mut [u8]) {
+            panic!("rng failed")
+        }
+
+        fn try_fill_bytes(&mut self, _dest: &mut [u8]) -> Result<(), rand::Error> {
+            panic!("rng failed")
+        }
+    }
+
+    #[test]
+    fn raycast() {
+        let result = NavmeshBuilder::build_test_tile(30.0).unwrap();
+        let tile = result.tile.unwrap();
+
+        let build_settings = NavBuildSettings::default();
+        let navmesh_settings = NavmeshSettings::default(build_settings, 2048.0, 10);
+        let mut navmesh = Navmesh::new(navmesh_settings).unwrap();
+        navmesh.add_or_replace_tile(tile).unwrap();
+        let query = navmesh.query_pool.pop().unwrap();
+        let filter = NavQueryFilter::default();
+
+        // Only the x < z half of the test tile is walkable
+        // Clear line of sight ends at the target on the surface
+        let start = DtVector::new(2.0, 1.0, 8.0);
+        let end = DtVector::new(4.0, 1.0, 16.0);
+        let result = query.raycast(NavQuerySettings::default(), start, end, &filter).unwrap();
+        assert!(!result.hit);
+        assert_eq!(1.0, result.t);
+        assert!((result.position.x - end.x).abs() < 0.01 && (result.position.z - end.z).abs() < 0.01);
+        assert!((result.position.y - 1.0).abs() < 0.5);
+        assert!(!result.polys.is_empty());
+        assert!(result.path_cost > 0.0);
+
+        // Ray crossing the diagonal edge hits it
+        let end = DtVector::new(40.0, 1.0, 8.0);
+        let result = query.raycast(NavQuerySettings::default(), start, end, &filter).unwrap();
+        assert!(result.hit);
+        assert!(result.t > 0.0 && result.t < 1.0);
+        assert!(result.position.x > start.x && result.position.x < 8.0);
+        assert!((result.position.y - 1.0).abs() < 0.5);
+        assert!(result.normal.x < 0.0);
+
+        let outside = DtVector::new(1000.0, 1.0, 1000.0);
+        let error = query.raycast(NavQuerySettings::default(), outside, end, &filter).unwrap_err();
+        assert_eq!(Some(DtStatus(DtStatus::FAILURE | DtStatus::NO_NEAREST_POLY)), error.status());
+    }
+
+    #[test]
+    fn off_mesh_connection_path() {
+        let mut builder = NavmeshBuilder::new(NavBuildSettings::default(), NavAgentSettings::default());
+        let coord = DtInt2::new(0, 0);
+        let bounds = NavmeshBuildUtils::calculate_tile_bounding_box(builder.build_settings, coord);
+        let mut input = TileInputBuilder::new(coord, bounds);
+
+        // Two platforms with a gap too wide to walk across
+        for (min_x, max_x) in [(0.0, 8.0), (12.0, 18.0)] {
+            input.append_triangle(&[DtVector::new(min_x, 1.0, 0.0), DtVector::new(min_x, 1.0, 10.0), DtVector::new(max_x, 1.0, 10.0)], DtArea::WALKABLE);
+            input.append_triangle(&[DtVector::new(min_x, 1.0, 0.0), DtVector::new(max_x, 1.0, 10.0), DtVector::new(max_x, 1.0, 0.0)], DtArea::WALKABLE);
+        }
+
+        let mut connection = DtOffMeshConnection::new(DtVector::new(7.0, 1.2, 5.0), DtVector::new(13.0, 1.2, 5.0), 0.5, true);
+        connection.user_id = 42;
+        input.add_off_mesh_connection(connection);
+
+        let result = builder.build_tile(input).unwrap();
+        let tile = result.tile.unwrap();
+
+        let connections = tile.get_off_mesh_connections();
+        assert_eq!(1, connections.len());
+        assert_eq!(42, connections[0].user_id);
+        assert!(connections[0].is_bidirectional());
+        // Connection polys have no surface
+        let (_, indices) = tile.get_tile_vertices().unwrap();
+        let ground_polys = tile.view().unwrap().polys().filter(|poly| !poly.is_off_mesh_connection()).count();
+        assert!(indices.len() >= ground_polys * 3);
+
+        let build_settings = NavBuildSettings::default();
+        let navmesh_settings = NavmeshSettings::default(build_settings, 2048.0, 10);
+        let mut navmesh = Navmesh::new(navmesh_settings).unwrap();
+        navmesh.add_or_replace_tile(tile).unwrap();
+
+        let mut query = navmesh.query_pool.pop().unwrap();
+        let filter = NavQueryFilter::default();
+        let start = DtVector::new(2.0, 1.0, 5.0);
+        let end = DtVector::new(16.0, 1.0, 5.0);
+        let (path_len, _) = query.find_path(NavQuerySettings::default(), start, end, &filter).unwrap();
+        assert!(path_len > 0);
+
+        let flags = query.get_path_flags(path_len);
+        assert!(flags.iter().any(|flags| flags & DtStraightPathFlags::OFFMESH_CONNECTION != 0));
+
+        // And back again over the bidirectional connection
+        let (path_len, _) = query.find_path(NavQuerySettings::default(), end, start, &filter).unwrap();
+        let flags = query.get_path_flags(path_len);
+        assert!(flags.iter().any(|flags| flags & DtStraightPathFlags::OFFMESH_CONNECTION != 0));
+        navmesh.query_pool.push(query);
+    }
+
+    #[test]
+    fn partial_paths() {
+        let mut builder = NavmeshBuilder::new(NavBuildSettings::default(), NavAgentSettings::default());
+        let coord = DtInt2::new(0, 0);
+        let bounds = NavmeshBuildUtils::calculate_tile_bounding_box(builder.build_settings, coord);
+        let mut input = TileInputBuilder::new(coord, bounds);
+
+        // Two platforms with nothing connecting them
+        for (min_x, max_x) in [(0.0, 8.0), (12.0, 18.0)] {
+            input.append_triangle(&[DtVector::new(min_x, 1.0, 0.0), DtVector::new(min_x, 1.0, 10.0), DtVector::new(max_x, 1.0, 10.0)], DtArea::WALKABLE);
+            input.append_triangle(&[DtVector::new(min_x, 1.0, 0.0), DtVector::new(max_x, 1.0, 10.0), DtVector::new(max_x, 1.0, 0.0)], DtArea::WALKABLE);
+        }
+        let tile = builder.build_tile(input).unwrap().tile.unwrap();
+
+        let build_settings = NavBuildSettings::default();
+        let navmesh_settings = NavmeshSettings::default(build_settings, 2048.0, 10);
+        let mut navmesh = Navmesh::new(navmesh_settings).unwrap();
+        navmesh.add_or_replace_tile(tile).unwrap();
+
+        let mut query = navmesh.query_pool.pop().unwrap();
+        let filter = NavQueryFilter::default();
+        let start = DtVector::new(2.0, 1.0, 5.0);
+        let end = DtVector::new(16.0, 1.0, 5.0);
+        assert_eq!(PathStatus::Partial, query.has_path(NavQuerySettings::default(), start, end, &filter));
+        assert_eq!(PathStatus::Complete, query.has_path(NavQuerySettings::default(), start, DtVector::new(6.0, 1.0, 5.0), &filter));
+
+        // The path walks to the edge of the first platform closest to the target
+        let (path_len, status) = query.find_path(NavQuerySettings::default(), start, end, &filter).unwrap();
+        assert_eq!(PathStatus::Partial, status);
+        let last = query.get_path(path_len)[path_len - 1];
+        assert!(last.x > 6.0 && last.x < 8.0);
+        assert!((last.z - 5.0).abs() < 0.5);
+
+        let (polys, status) = query.find_poly_path(NavQuerySettings::default(), start, end, &filter).unwrap();
+        assert_eq!(PathStatus::Partial, status);
+        assert!(!polys.is_empty());
+
+        let (points, status) = query.find_straight_path_detailed(NavQuerySettings::default(), start, end, &filter, DtStraightPathOptions::NONE).unwrap();
+        assert_eq!(PathStatus::Partial, status);
+        assert!(points[points.len() - 1].flags & DtStraightPathFlags::END != 0);
+        assert_eq!(last, points[points.len() - 1].position);
+        navmesh.query_pool.push(query);
+    }
+
+    #[test]
+    fn sliced_paths() {
+        let mut builder = NavmeshBuilder::new(NavBuildSettings::default(), NavAgentSettings::default());
+        let coord = DtInt2::new(0, 0);
+        let bounds = NavmeshBuildUtils::calculate_tile_bounding_box(builder.build_settings, coord);
+        let mut input = TileInputBuilder::new(coord, bounds);
+
+        // Strips of different areas so the corridor crosses several polygons
+        for strip in 0..6 {
+            let (min_x, max_x) = (strip as f32 * 3.0, strip as f32 * 3.0 + 3.0);
+            let area = strip as u8 + 1;
+            input.append_triangle(&[DtVector::new(min_x, 1.0, 0.0), DtVector::new(min_x, 1.0, 10.0), DtVector::new(max_x, 1.0, 10.0)], area);
+            input.append_triangle(&[DtVector::new(min_x, 1.0, 0.0), DtVector::new(max_x, 1.0, 10.0), DtVector::new(max_x, 1.0, 0.0)], area);
+        }
+        let tile = builder.build_tile(input).unwrap().tile.unwrap();
+
+        let build_settings = NavBuildSettings::default();
+        let navmesh_settings = NavmeshSettings::default(build_settings, 2048.0, 10);
+        let mut navmesh = Navmesh::new(navmesh_settings).unwrap();
+        navmesh.add_or_replace_tile(tile).unwrap();
+
+        let mut query = navmesh.query_pool.pop().unwrap();
+        let filter = NavQueryFilter::default();
+        let start = DtVector::new(1.0, 1.0, 2.0);
+        let end = DtVector::new(17.0, 1.0, 8.0);
+        let (expected, _) = query.find_straight_path_detailed(NavQuerySettings::default(), start, end, &filter, DtStraightPathOptions::NONE).unwrap();
+        let (corridor, _) = query.find_poly_path(NavQuerySettings::default(), start, end, &filter).unwrap();
+        assert!(corridor.len() > 2);
+
+        // One polygon per step
+        let mut request = query.start_sliced_path(NavQuerySettings::default(), start, end, &filter, DtStraightPathOptions::NONE).unwrap();
+        let mut steps = 0;
+        while request.step(1) == SliceStatus::InProgress {
+            steps += 1;
+        }
+        assert_eq!(SliceStatus::Done, request.status());
+        assert!(steps > 1);
+        assert!(request.iterations() >= steps);
+        let (points, status) = request.finalize().unwrap();
+        assert_eq!(PathStatus::Complete, status);
+        let positions: Vec<DtVector> = points.iter().map(|point| point.position).collect();
+        let expected_positions: Vec<DtVector> = expected.iter().map(|point| point.position).collect();
+        assert_eq!(expected_positions, positions);
+
+        // Finalizing early gives a partial path toward the best polygon so far
+        let mut request = query.start_sliced_path(NavQuerySettings::default(), start, end, &filter, DtStraightPathOptions::NONE).unwrap();
+        assert_eq!(SliceStatus::InProgress, request.step(1));
+        let (points, status) = request.finalize().unwrap();
+        assert_eq!(PathStatus::Partial, status);
+        assert!(!points.is_empty());
+
+        let mut request = query.start_sliced_path(NavQuerySettings::default(), start, end, &filter, DtStraightPathOptions::NONE).unwrap();
+        assert_eq!(SliceStatus::InProgress, request.step(1));
+        let (_, status) = request.finalize_partial(&corridor).unwrap();
+        assert_eq!(PathStatus::Partial, status);
+
+        assert!(query.start_sliced_path(NavQuerySettings::default(), start, DtVector::new(1000.0, 1.0, 1000.0), &filter, DtStraightPathOptions::NONE).is_err());
+        navmesh.query_pool.push(query);
+    }
+
+    #[test]
+    fn area_costs() {
+        let mut builder = NavmeshBuilder::new(NavBuildSettings::default(), NavAgentSettings::default());
+        let coord = DtInt2::new(0, 0);
+        let bounds = NavmeshBuildUtils::calculate_tile_bounding_box(builder.build_settings, coord);
+        let mut input = TileInputBuilder::new(coord, bounds);
+
+        // A block of water between start and end that can be walked around
+        let water: u8 = 5;
+        let rects = [(0.0, 6.0, 0.0, 18.0, DtArea::WALKABLE), (6.0, 12.0, 0.0, 12.0, water), (6.0, 12.0, 12.0, 18.0, DtArea::WALKABLE), (12.0, 18.0, 0.0, 18.0, DtArea::WALKABLE)];
+        for (min_x, max_x, min_z, max_z, area) in rects {
+            input.append_triangle(&[DtVector::new(min_x, 1.0, min_z), DtVector::new(min_x, 1.0, max_z), DtVector::new(max_x, 1.0, max_z)], area);
+            input.append_triangle(&[DtVector::new(min_x, 1.0, min_z), DtVector::new(max_x, 1.0, max_z), DtVector::new(max_x, 1.0, min_z)], area);
+        }
+        let tile = builder.build_tile(input).unwrap().tile.unwrap();
+
+        let build_settings = NavBuildSettings::default();
+        let navmesh_settings = NavmeshSettings::default(build_settings, 2048.0, 10);
+        let mut navmesh = Navmesh::new(navmesh_settings).unwrap();
+        navmesh.add_or_replace_tile(tile).unwrap();
+
+        let mut query = navmesh.query_pool.pop().unwrap();
+        let start = DtVector::new(2.0, 1.0, 2.0);
+        let end = DtVector::new(16.0, 1.0, 2.0);
+
+        let filter = NavQueryFilter::default();
+        let (path_len, _) = query.find_path(NavQuerySettings::default(), start, end, &filter).unwrap();
+        assert!(path_len > 0);
+        assert!(query.get_path(path_len).iter().all(|point| point.z < 11.0));
+
+        let mut filter = NavQueryFilter::default();
+        filter.set_area_cost(water, 100.0);
+        let (path_len, _) = query.find_path(NavQuerySettings::default(), start, end, &filter).unwrap();
+        assert!(path_len > 0);
+        assert!(query.get_path(path_len).iter().any(|point| point.z > 11.0));
+
+        navmesh.query_pool.push(query);
+    }
+
+    #[test]
+    fn poly_paths() {
+        let result = NavmeshBuilder::build_test_tile(30.0).unwrap();
+        let build_settings = NavBuildSettings::default();
+        let navmesh_settings = NavmeshSettings::default(build_settings, 2048.0, 10);
+        let mut navmesh = Navmesh::new(navmesh_settings).unwrap();
+        navmesh.add_or_replace_tile(result.tile.unwrap()).unwrap();
+
+        let query = navmesh.query_pool.pop().unwrap();
+        let filter = NavQueryFilter::default();
+        let start = DtVector::new(1.0, 1.0, 1.0);
+        let end = DtVector::new(15.0, 1.0, 12.0);
+
+        let (polys, status) = query.find_poly_path(NavQuerySettings::default(), start, end, &filter).unwrap();
+        assert_eq!(PathStatus::Complete, status);
+        assert!(!polys.is_empty());
+        assert!(polys.iter().all(|poly| *poly != 0));
+
+        let (points, _) = query.find_straight_path_detailed(NavQuerySettings::default(), start, end, &filter, DtStraightPathOptions::ALL_CROSSINGS).unwrap();
+        assert!(points.len() >= 2);
+        assert!(points[0].flags & DtStraightPathFlags::START != 0);
+        assert!(points[points.len() - 1].flags & DtStraightPathFlags::END != 0);
+        assert_eq!(polys[0], points[0].poly);
+        assert!(points.iter().all(|point| !point.is_off_mesh_connection()));
+
+        assert!(query.find_poly_path(NavQuerySettings::default(), start, DtVector::new(1000.0, 1.0, 1000.0), &filter).is_err());
+        navmesh.query_pool.push(query);
+    }
+
+    #[test]
+    fn negative_max_path_points() {
+        let result = NavmeshBuilder::build_test_tile(30.0).unwrap();
+        let build_settings = NavBuildSettings::default();
+        let navmesh_settings = NavmeshSettings::default(build_settings, 2048.0, 10);
+        let mut navmesh = Navmesh::new(navmesh_settings).unwrap();
+        navmesh.add_or_replace_tile(result.tile.unwrap()).unwrap();
+
+        let mut query = navmesh.query_pool.pop().unwrap();
+        let filter = NavQueryFilter::default();
+        let start = DtVector::new(1.0, 1.0, 1.0);
+        let end = DtVector::new(15.0, 1.0, 12.0);
+
+        // Clamped to an empty buffer instead of overflowing the allocation
+        let settings = NavQuerySettings { max_path_points: -1, ..Default::default() };
+        assert!(query.find_poly_path(settings, start, end, &filter).is_err());
+        assert!(query.find_straight_path_detailed(settings, start, end, &filter, DtStraightPathOptions::NONE).is_err());
+        assert!(query.find_path(settings, start, end, &filter).is_err());
+        assert_eq!(PathStatus::NoPath, query.has_path(settings, start, end, &filter));
+        query.raycast(settings, start, end, &filter).unwrap();
+        let mut request = query.start_sliced_path(settings, start, end, &filter, DtStraightPathOptions::NONE).unwrap();
+        request.step(100);
+        assert!(request.finalize().is_err());
+        navmesh.query_pool.push(query);
+    }
 }
\ No newline at end of file
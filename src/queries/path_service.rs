@@ -0,0 +1,408 @@
+use std::{cmp::Ordering, collections::BinaryHeap, sync::{Arc, Condvar, Mutex, MutexGuard, Weak, atomic::{AtomicBool, Ordering as AtomicOrdering}}, thread::{self, JoinHandle}, time::Duration};
+
+use rustc_hash::FxHashMap;
+
+use crate::common::{DtArea, DtVector, navmesh::{NavQueryGuard, NavQueryPool}, nav_error::NavError};
+
+use super::{DtStraightPathOptions, NavQueryFilter, NavQuerySettings, PathStatus, StraightPathPoint};
+
+/// How often idle and paused workers check for drains and shutdown
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+pub type PathResult = Result<(Vec<StraightPathPoint>, PathStatus), NavError>;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PathRequest {
+    pub start: DtVector,
+    pub end: DtVector,
+    pub filter: NavQueryFilter,
+    pub settings: NavQuerySettings
+}
+
+impl PathRequest {
+    pub fn new(start: DtVector, end: DtVector, filter: NavQueryFilter, settings: NavQuerySettings) -> Self {
+        PathRequest {
+            start,
+            end,
+            filter,
+            settings
+        }
+    }
+
+    fn key(&self) -> PathRequestKey {
+        let vector_bits = |vector: DtVector| [vector.x.to_bits(), vector.y.to_bits(), vector.z.to_bits()];
+        let mut area_cost = [0; DtArea::MAX_AREAS];
+        for (bits, cost) in area_cost.iter_mut().zip(self.filter.area_cost.iter()) {
+            *bits = cost.to_bits();
+        }
+
+        PathRequestKey {
+            start: vector_bits(self.start),
+            end: vector_bits(self.end),
+            extent: vector_bits(self.settings.find_nearest_poly_extent),
+            max_path_points: self.settings.max_path_points,
+            include_flags: self.filter.include_flags,
+            exclude_flags: self.filter.exclude_flags,
+            area_cost
+        }
+    }
+}
+
+/// Bit exact identity of a request, used for deduplication
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct PathRequestKey {
+    start: [u32; 3],
+    end: [u32; 3],
+    extent: [u32; 3],
+    max_path_points: i32,
+    include_flags: u16,
+    exclude_flags: u16,
+    area_cost: [u32; DtArea::MAX_AREAS]
+}
+
+struct PathJob {
+    request: PathRequest,
+    key: PathRequestKey,
+    /// Highest priority the job was submitted with
+    priority: Mutex<i32>,
+    started: AtomicBool,
+    result: Mutex<Option<PathResult>>,
+    ready: Condvar
+}
+
+impl PathJob {
+    fn complete(&self, result: PathResult) {
+        let mut slot = self.result.lock().unwrap_or_else(|error| error.into_inner());
+        *slot = Some(result);
+        self.ready.notify_all();
+    }
+}
+
+/// Result of PathService::submit.  Handles for duplicate requests share the same result.
+/// The request is skipped if every handle is dropped before a worker gets to it
+#[derive(Clone)]
+pub struct PathHandle {
+    job: Arc<PathJob>
+}
+
+impl PathHandle {
+    pub fn request(&self) -> &PathRequest {
+        &self.job.request
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.lock_result().is_some()
+    }
+
+    /// The result if the path has been found
+    pub fn try_result(&self) -> Option<PathResult> {
+        self.lock_result().clone()
+    }
+
+    /// Blocks until the path has been found
+    pub fn wait(&self) -> PathResult {
+        let mut result = self.lock_result();
+        loop {
+            if let Some(result) = result.as_ref() {
+                return result.clone();
+            }
+            result = self.job.ready.wait(result).unwrap_or_else(|error| error.into_inner());
+        }
+    }
+
+    /// None if the path wasn't found within timeout
+    pub fn wait_timeout(&self, timeout: Duration) -> Option<PathResult> {
+        let result = self.lock_result();
+        let (result, _) = self.job.ready.wait_timeout_while(result, timeout, |result| result.is_none()).unwrap_or_else(|error| error.into_inner());
+        result.clone()
+    }
+
+    /// True if both handles are for the same deduplicated request
+    pub fn same_request(&self, other: &PathHandle) -> bool {
+        Arc::ptr_eq(&self.job, &other.job)
+    }
+
+    fn lock_result(&self) -> MutexGuard<'_, Option<PathResult>> {
+        self.job.result.lock().unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+struct QueuedPath {
+    priority: i32,
+    /// Submit order, lower runs first within a priority
+    sequence: u64,
+    job: Weak<PathJob>
+}
+
+impl PartialEq for QueuedPath {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for QueuedPath {}
+
+impl PartialOrd for QueuedPath {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueuedPath {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority.cmp(&other.priority).then_with(|| other.sequence.cmp(&self.sequence))
+    }
+}
+
+#[derive(Default)]
+struct PathQueue {
+    heap: BinaryHeap<QueuedPath>,
+    /// Queued jobs that haven't started, by request
+    pending: FxHashMap<PathRequestKey, Weak<PathJob>>,
+    sequence: u64,
+    shutdown: bool
+}
+
+impl PathQueue {
+    fn submit(&mut self, request: PathRequest, priority: i32) -> Arc<PathJob> {
+        let key = request.key();
+        if let Some(job) = self.pending.get(&key).and_then(|job| job.upgrade()) {
+            let mut job_priority = job.priority.lock().unwrap_or_else(|error| error.into_inner());
+            if priority > *job_priority {
+                // The old heap entry is skipped once the job has started
+                *job_priority = priority;
+                self.push(priority, &job);
+            }
+            drop(job_priority);
+            return job;
+        }
+
+        let job = Arc::new(PathJob {
+            request,
+            key,
+            priority: Mutex::new(priority),
+            started: AtomicBool::new(false),
+            result: Mutex::new(None),
+            ready: Condvar::new()
+        });
+        self.pending.insert(key, Arc::downgrade(&job));
+        self.push(priority, &job);
+        job
+    }
+
+    fn push(&mut self, priority: i32, job: &Arc<PathJob>) {
+        self.heap.push(QueuedPath { priority, sequence: self.sequence, job: Arc::downgrade(job) });
+        self.sequence += 1;
+    }
+
+    /// Highest priority job that still has a handle and hasn't started
+    fn pop(&mut self) -> Option<Arc<PathJob>> {
+        while let Some(queued) = self.heap.pop() {
+            let Some(job) = queued.job.upgrade() else {
+                continue;
+            };
+            if job.started.swap(true, AtomicOrdering::AcqRel) {
+                continue;
+            }
+            self.pending.remove(&job.key);
+            return Some(job);
+        }
+        self.pending.clear();
+        None
+    }
+
+    fn len(&self) -> usize {
+        self.pending.values().filter(|job| job.strong_count() > 0).count()
+    }
+}
+
+struct PathServiceShared {
+    queue: Mutex<PathQueue>,
+    available: Condvar,
+    query_pool: Arc<NavQueryPool>
+}
+
+impl PathServiceShared {
+    fn lock_queue(&self) -> MutexGuard<'_, PathQueue> {
+        self.queue.lock().unwrap_or_else(|error| error.into_inner())
+    }
+
+    fn is_shutdown(&self) -> bool {
+        self.lock_queue().shutdown
+    }
+
+    /// Waits up to POLL_INTERVAL for a job
+    fn next_job(&self) -> Option<Arc<PathJob>> {
+        let mut queue = self.lock_queue();
+        if let Some(job) = queue.pop() {
+            return Some(job);
+        }
+        if queue.shutdown {
+            return None;
+        }
+        queue = self.available.wait_timeout(queue, POLL_INTERVAL).unwrap_or_else(|error| error.into_inner()).0;
+        queue.pop()
+    }
+}
+
+/// Finds paths on a pool of worker threads.  Each worker holds a NavQuery from the navmesh's query pool while it works.
+///
+/// Requests are run highest priority first, in submit order within a priority.  A request identical to one still queued
+/// shares its result instead of being queued again.
+///
+/// When the navmesh changes tiles it drains the query pool.  Workers finish the request they are on, push their query back
+/// and wait for the drain to end before continuing, so tiles can be added and removed while the service runs.
+/// Use fewer threads than the pool size to leave queries for other callers.
+///
+/// The service's queries keep the navmesh alive, so it can outlive the Navmesh.  Requests still queued when the service is dropped fail with ServiceStopped.
+pub struct PathService {
+    shared: Arc<PathServiceShared>,
+    threads: Vec<JoinHandle<()>>
+}
+
+impl PathService {
+    pub fn new(query_pool: Arc<NavQueryPool>, thread_count: usize) -> Self {
+        let shared = Arc::new(PathServiceShared {
+            queue: Mutex::new(PathQueue::default()),
+            available: Condvar::new(),
+            query_pool
+        });
+
+        let threads = (0..thread_count.max(1)).map(|_| {
+            let shared = shared.clone();
+            thread::spawn(move || run_worker(&shared))
+        }).collect();
+
+        PathService {
+            shared,
+            threads
+        }
+    }
+
+    pub fn submit(&self, request: PathRequest, priority: i32) -> PathHandle {
+        let job = self.shared.lock_queue().submit(request, priority);
+        self.shared.available.notify_one();
+        PathHandle { job }
+    }
+
+    /// Queued requests that haven't started
+    pub fn pending(&self) -> usize {
+        self.shared.lock_queue().len()
+    }
+
+    pub fn thread_count(&self) -> usize {
+        self.threads.len()
+    }
+}
+
+impl Drop for PathService {
+    fn drop(&mut self) {
+        self.shared.lock_queue().shutdown = true;
+        self.shared.available.notify_all();
+        for thread in self.threads.drain(..) {
+            thread.join().unwrap_or_default();
+        }
+
+        let mut queue = self.shared.lock_queue();
+        while let Some(job) = queue.pop() {
+            job.complete(Err(NavError::ServiceStopped));
+        }
+    }
+}
+
+fn run_worker(shared: &PathServiceShared) {
+    let mut query: Option<NavQueryGuard> = None;
+    while !shared.is_shutdown() {
+        if shared.query_pool.is_draining() {
+            // Give the query back so the navmesh can change tiles
+            query = None;
+            thread::sleep(POLL_INTERVAL);
+            continue;
+        }
+
+        let Some(nav_query) = query.as_ref() else {
+            query = shared.query_pool.lease();
+            if query.is_none() {
+                thread::sleep(POLL_INTERVAL);
+            }
+            continue;
+        };
+
+        if let Some(job) = shared.next_job() {
+            let request = &job.request;
+            let result = nav_query.find_straight_path_detailed(request.settings, request.start, request.end, &request.filter, DtStraightPathOptions::NONE);
+            job.complete(result);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use crate::{building::{navmesh_builder::NavmeshBuilder, NavBuildSettings}, common::{navmesh::{Navmesh, NavmeshSettings}, DtInt2, DtVector}, queries::{NavQueryFilter, NavQuerySettings, PathStatus}};
+
+    use super::{PathQueue, PathRequest, PathService};
+
+    fn request(end_x: f32) -> PathRequest {
+        PathRequest::new(DtVector::new(1.0, 1.0, 2.0), DtVector::new(end_x, 1.0, 18.0), NavQueryFilter::default(), NavQuerySettings::default())
+    }
+
+    #[test]
+    fn queue_order() {
+        let mut queue = PathQueue::default();
+        let low = queue.submit(request(2.0), 0);
+        let high = queue.submit(request(4.0), 5);
+        let low_second = queue.submit(request(6.0), 0);
+
+        // Duplicates share the job, a higher priority bumps it
+        let duplicate = queue.submit(request(6.0), 10);
+        assert!(Arc::ptr_eq(&low_second, &duplicate));
+        assert_eq!(3, queue.len());
+
+        let order: Vec<f32> = std::iter::from_fn(|| queue.pop()).map(|job| job.request.end.x).collect();
+        assert_eq!(vec![6.0, 4.0, 2.0], order);
+        drop((low, high));
+
+        // Jobs without handles are skipped
+        drop(queue.submit(request(8.0), 0));
+        assert!(queue.pop().is_none());
+    }
+
+    #[test]
+    fn path_service() {
+        let result = NavmeshBuilder::build_test_tile(30.0).unwrap();
+        let tile = result.tile.unwrap();
+
+        let build_settings = NavBuildSettings::default();
+        let navmesh_settings = NavmeshSettings::default(build_settings, 2048.0, 4);
+        let mut navmesh = Navmesh::new(navmesh_settings).unwrap();
+        navmesh.add_or_replace_tile(tile.clone()).unwrap();
+
+        let service = PathService::new(navmesh.query_pool.clone(), 2);
+        let handles: Vec<_> = (0..8).map(|i| service.submit(request(2.0 + i as f32), i)).collect();
+        for handle in handles.iter() {
+            let (points, status) = handle.wait().unwrap();
+            assert_eq!(PathStatus::Complete, status);
+            assert!(points.len() >= 2);
+        }
+
+        // Tile changes drain the workers' queries and the service picks up again afterwards
+        navmesh.add_or_replace_tile(tile).unwrap();
+        navmesh.remove_tile(&DtInt2::new(0, 0)).unwrap();
+        let handle = service.submit(request(4.0), 0);
+        let error = handle.wait_timeout(Duration::from_secs(5)).unwrap().unwrap_err();
+        assert!(error.status().is_some());
+
+        let pool = navmesh.query_pool.clone();
+        drop(service);
+        assert!(pool.is_full());
+
+        // The workers' queries keep the navmesh alive
+        navmesh.add_or_replace_tile(NavmeshBuilder::build_test_tile(30.0).unwrap().tile.unwrap()).unwrap();
+        let service = PathService::new(pool, 2);
+        drop(navmesh);
+        let (_, status) = service.submit(request(4.0), 0).wait().unwrap();
+        assert_eq!(PathStatus::Complete, status);
+    }
+}
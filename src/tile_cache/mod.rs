@@ -0,0 +1,149 @@
+use std::{ffi::c_void, slice};
+
+use crate::common::DtVector;
+
+
+#[allow(clippy::module_inception)]
+pub mod tile_cache;
+
+/// dtObstacleRef
+pub type ObstacleRef = u32;
+
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct DtTileCacheParams {
+    pub origin: [f32; 3],
+    pub cell_size: f32,
+    pub cell_height: f32,
+    /// Tile size in cells
+    pub width: i32,
+    pub height: i32,
+    pub walkable_height: f32,
+    pub walkable_radius: f32,
+    pub walkable_climb: f32,
+    pub max_simplification_error: f32,
+    pub max_tiles: i32,
+    pub max_obstacles: i32
+}
+
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct DtTileCacheLayersResult {
+    pub success: bool,
+    pub error: i32,
+    pub layers: *mut *mut u8,
+    pub layer_sizes: *mut i32,
+    pub num_layers: i32
+}
+
+/// Compressor callbacks handed to the C side.  Callbacks return 1 on success
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct DtTileCacheCompressorCallbacks {
+    pub context: *mut c_void,
+    pub max_compressed_size: extern "C" fn(context: *mut c_void, buffer_size: i32) -> i32,
+    pub compress: extern "C" fn(context: *mut c_void, buffer: *const u8, buffer_size: i32, compressed: *mut u8, max_compressed_size: i32, compressed_size: *mut i32) -> i32,
+    pub decompress: extern "C" fn(context: *mut c_void, compressed: *const u8, compressed_size: i32, buffer: *mut u8, max_buffer_size: i32, buffer_size: *mut i32) -> i32
+}
+
+impl DtTileCacheCompressorCallbacks {
+    /// compressor has to outlive any use of the callbacks on the C side
+    pub fn new<C: TileCacheCompressor>(compressor: &C) -> Self {
+        DtTileCacheCompressorCallbacks {
+            context: compressor as *const C as *mut c_void,
+            max_compressed_size: max_compressed_size::<C>,
+            compress: compress::<C>,
+            decompress: decompress::<C>
+        }
+    }
+}
+
+/// Compression for tile cache layers.  Layers are stored compressed and decompressed each time a tile is rebuilt,
+/// so this trades memory for rebuild time.  Called from whichever thread builds layers or updates the tile cache.
+pub trait TileCacheCompressor: Send + Sync {
+    /// Upper bound of the compressed size of buffer_size bytes
+    fn max_compressed_size(&self, buffer_size: usize) -> usize;
+    /// Returns the compressed size, None on failure.  compressed is max_compressed_size long
+    fn compress(&self, buffer: &[u8], compressed: &mut [u8]) -> Option<usize>;
+    /// Returns the decompressed size, None on failure
+    fn decompress(&self, compressed: &[u8], buffer: &mut [u8]) -> Option<usize>;
+}
+
+/// Stores layers as is.  Plug in an LZ4 or similar TileCacheCompressor if layer memory matters
+#[derive(Clone, Copy, Debug, Default)]
+pub struct UncompressedCompressor;
+
+impl TileCacheCompressor for UncompressedCompressor {
+    fn max_compressed_size(&self, buffer_size: usize) -> usize {
+        buffer_size
+    }
+
+    fn compress(&self, buffer: &[u8], compressed: &mut [u8]) -> Option<usize> {
+        compressed.get_mut(..buffer.len())?.copy_from_slice(buffer);
+        Some(buffer.len())
+    }
+
+    fn decompress(&self, compressed: &[u8], buffer: &mut [u8]) -> Option<usize> {
+        buffer.get_mut(..compressed.len())?.copy_from_slice(compressed);
+        Some(compressed.len())
+    }
+}
+
+extern "C" fn max_compressed_size<C: TileCacheCompressor>(context: *mut c_void, buffer_size: i32) -> i32 {
+    let compressor = unsafe { &*(context as *const C) };
+    compressor.max_compressed_size(buffer_size as usize) as i32
+}
+
+extern "C" fn compress<C: TileCacheCompressor>(context: *mut c_void, buffer: *const u8, buffer_size: i32, compressed: *mut u8, max_compressed_size: i32, compressed_size: *mut i32) -> i32 {
+    let compressor = unsafe { &*(context as *const C) };
+    let buffer = unsafe { slice::from_raw_parts(buffer, buffer_size as usize) };
+    let compressed = unsafe { slice::from_raw_parts_mut(compressed, max_compressed_size as usize) };
+    match compressor.compress(buffer, compressed) {
+        Some(size) => {
+            unsafe { *compressed_size = size as i32 };
+            1
+        },
+        None => 0
+    }
+}
+
+extern "C" fn decompress<C: TileCacheCompressor>(context: *mut c_void, compressed: *const u8, compressed_size: i32, buffer: *mut u8, max_buffer_size: i32, buffer_size: *mut i32) -> i32 {
+    let compressor = unsafe { &*(context as *const C) };
+    let compressed = unsafe { slice::from_raw_parts(compressed, compressed_size as usize) };
+    let buffer = unsafe { slice::from_raw_parts_mut(buffer, max_buffer_size as usize) };
+    match compressor.decompress(compressed, buffer) {
+        Some(size) => {
+            unsafe { *buffer_size = size as i32 };
+            1
+        },
+        None => 0
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TileCacheSettings {
+    /// Obstacles that can exist at once
+    pub max_obstacles: i32,
+    /// Layers per tile coord.  Overlapping walkable floors (bridges, multi story buildings) each get a layer
+    pub max_layers_per_tile: i32
+}
+
+impl Default for TileCacheSettings {
+    fn default() -> Self {
+        TileCacheSettings {
+            max_obstacles: 128,
+            max_layers_per_tile: 4
+        }
+    }
+}
+
+/// Temporary obstacles.  Obstacles cut holes in the navmesh of the tiles they overlap until removed.
+#[derive(Clone, Copy, Debug)]
+pub enum TileCacheObstacle {
+    /// position is the bottom center
+    Cylinder { position: DtVector, radius: f32, height: f32 },
+    /// Axis aligned
+    Box { min: DtVector, max: DtVector },
+    /// Rotated around the y axis
+    OrientedBox { center: DtVector, half_extents: DtVector, y_radians: f32 }
+}
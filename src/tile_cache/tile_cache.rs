@@ -0,0 +1,280 @@
+use std::sync::Arc;
+
+use rustc_hash::FxHashSet;
+
+use crate::{
+    bindings::{
+        RawTileCachePtr, TileCacheAddBoxObstacle, TileCacheAddObstacle,
+        TileCacheAddOrientedBoxObstacle, TileCacheAddTileLayer, TileCacheBuildNavMeshTile, TileCacheCreate,
+        TileCacheDestroy, TileCacheRemoveObstacle, TileCacheRemoveTileLayers, TileCacheUpdate,
+    },
+    building::{navmesh_builder::NavmeshBuilder, tile_input_builder::TileInputBuilder, NavBuildSettings},
+    common::{nav_error::NavError, navmesh::{Navmesh, NavmeshPtr, QueryPoolDrain}, DtInt2, DtVector, NavAgentSettings},
+};
+
+use super::{
+    DtTileCacheCompressorCallbacks, DtTileCacheParams, ObstacleRef, TileCacheCompressor,
+    TileCacheObstacle, TileCacheSettings, UncompressedCompressor,
+};
+
+struct TileCachePtr {
+    tile_cache: *mut RawTileCachePtr,
+    /// Keeps the C navmesh the tile cache points at alive, and checks we are handed the navmesh the tile cache was created for.
+    /// Dropped after the tile cache is destroyed in drop
+    navmesh: Arc<NavmeshPtr>
+}
+unsafe impl Send for TileCachePtr {}
+
+/// DetourTileCache on top of a Navmesh.  Tiles are kept as compressed heightfield layers and rebuilt into the navmesh
+/// when temporary obstacles are added or removed.  Rebuilding from layers is much cheaper than a full tile build.
+///
+/// The tile cache builds tiles straight into the navmesh, so functions that change navmesh tiles take the navmesh and
+/// have the same rule as Navmesh::add_or_replace_tile, all queries have to be in the pool.
+/// Tiles at coords managed by the tile cache shouldn't also be added with Navmesh::add_or_replace_tile.
+pub struct TileCache<C: TileCacheCompressor = UncompressedCompressor> {
+    tile_cache_ptr: TileCachePtr,
+    builder: NavmeshBuilder,
+    /// Boxed so the callback context the C side holds stays put
+    compressor: Box<C>,
+    layer_coords: FxHashSet<DtInt2>,
+    /// Obstacle changes not yet synced into the navmesh tile coords
+    pending: bool
+}
+
+impl<C: TileCacheCompressor> TileCache<C> {
+    /// The navmesh has to use the same tile size as build_settings
+    pub fn new(navmesh: &Navmesh, build_settings: NavBuildSettings, agent_settings: NavAgentSettings, settings: TileCacheSettings, compressor: C) -> Result<Self, NavError> {
+        let navmesh_params = navmesh.params();
+        let params = DtTileCacheParams {
+            origin: navmesh_params.origin,
+            cell_size: build_settings.cell_size,
+            cell_height: build_settings.cell_height,
+            width: build_settings.tile_size,
+            height: build_settings.tile_size,
+            walkable_height: agent_settings.height,
+            walkable_radius: agent_settings.radius,
+            walkable_climb: agent_settings.max_climb,
+            max_simplification_error: build_settings.max_edge_error,
+            max_tiles: navmesh_params.max_tiles * settings.max_layers_per_tile,
+            max_obstacles: settings.max_obstacles
+        };
+
+        let compressor = Box::new(compressor);
+        let callbacks = DtTileCacheCompressorCallbacks::new(compressor.as_ref());
+
+        let ptr = unsafe { TileCacheCreate(navmesh.raw_ptr(), &params as *const DtTileCacheParams, &callbacks as *const DtTileCacheCompressorCallbacks, build_settings.area_flags.as_ptr()) };
+        if ptr.is_null() {
+            return Err(NavError::CreateFailed("tile cache"));
+        }
+
+        let tile_cache = TileCache {
+            tile_cache_ptr: TileCachePtr { tile_cache: ptr, navmesh: navmesh.shared_ptr().clone() },
+            builder: NavmeshBuilder::new(build_settings, agent_settings),
+            compressor,
+            layer_coords: FxHashSet::default(),
+            pending: false
+        };
+        Ok(tile_cache)
+    }
+
+    pub fn compressor(&self) -> &C {
+        &self.compressor
+    }
+
+    /// Build the compressed layers for a tile with the tile cache's settings and compressor.
+    /// To build on other threads use NavmeshBuilder::build_tile_cache_layers with the same settings.
+    pub fn build_tile_layers(&mut self, input: TileInputBuilder) -> Result<Vec<Vec<u8>>, NavError> {
+        self.builder.build_tile_cache_layers(input, self.compressor.as_ref())
+    }
+
+    /// Replaces the layers at coord and rebuilds the navmesh tile from them, applying current obstacles.
+    /// Empty layers just removes the tile.
+    pub fn add_tile_layers(&mut self, coord: DtInt2, layers: &[Vec<u8>], navmesh: &mut Navmesh) -> Result<(), NavError> {
+        let _drain = self.check_mutate(navmesh)?;
+
+        unsafe { TileCacheRemoveTileLayers(self.tile_cache_ptr.tile_cache, &coord as *const DtInt2) };
+        let _ = navmesh.remove_tile_internal(&coord);
+        self.layer_coords.remove(&coord);
+
+        if layers.is_empty() {
+            return Ok(());
+        }
+
+        for layer in layers.iter() {
+            let mut data = layer.clone();
+            let status = unsafe { TileCacheAddTileLayer(self.tile_cache_ptr.tile_cache, data.as_mut_ptr(), data.len() as i32) };
+            if let Err(error) = status.to_result() {
+                unsafe { TileCacheRemoveTileLayers(self.tile_cache_ptr.tile_cache, &coord as *const DtInt2) };
+                return Err(error);
+            }
+        }
+        self.layer_coords.insert(coord);
+
+        let status = unsafe { TileCacheBuildNavMeshTile(self.tile_cache_ptr.tile_cache, &coord as *const DtInt2) };
+        navmesh.sync_tile_coord(coord);
+        status.to_result()?;
+        Ok(())
+    }
+
+    /// build_tile_layers + add_tile_layers.  Use those directly to build layers on other threads
+    pub fn add_tile(&mut self, input: TileInputBuilder, navmesh: &mut Navmesh) -> Result<(), NavError> {
+        let coord = input.coord;
+        let layers = self.build_tile_layers(input)?;
+        self.add_tile_layers(coord, &layers, navmesh)
+    }
+
+    /// Removes the layers and the navmesh tile at coord
+    pub fn remove_tile(&mut self, coord: DtInt2, navmesh: &mut Navmesh) -> Result<(), NavError> {
+        let _drain = self.check_mutate(navmesh)?;
+        if !self.layer_coords.remove(&coord) {
+            return Err(NavError::TileNotFound(coord));
+        }
+
+        unsafe { TileCacheRemoveTileLayers(self.tile_cache_ptr.tile_cache, &coord as *const DtInt2) };
+        let _ = navmesh.remove_tile_internal(&coord);
+        Ok(())
+    }
+
+    /// The obstacle is applied to the navmesh over the following update calls.
+    /// Fails with OUT_OF_MEMORY if max_obstacles is reached, or BUFFER_TOO_SMALL if too many obstacle changes are queued, update and try again.
+    pub fn add_obstacle(&mut self, obstacle: TileCacheObstacle) -> Result<ObstacleRef, NavError> {
+        let mut obstacle_ref: ObstacleRef = 0;
+        let status = unsafe {
+            match obstacle {
+                TileCacheObstacle::Cylinder { position, radius, height } => {
+                    TileCacheAddObstacle(self.tile_cache_ptr.tile_cache, &position as *const DtVector, radius, height, &mut obstacle_ref)
+                },
+                TileCacheObstacle::Box { min, max } => {
+                    TileCacheAddBoxObstacle(self.tile_cache_ptr.tile_cache, &min as *const DtVector, &max as *const DtVector, &mut obstacle_ref)
+                },
+                TileCacheObstacle::OrientedBox { center, half_extents, y_radians } => {
+                    TileCacheAddOrientedBoxObstacle(self.tile_cache_ptr.tile_cache, &center as *const DtVector, &half_extents as *const DtVector, y_radians, &mut obstacle_ref)
+                }
+            }
+        };
+
+        status.to_result()?;
+        self.pending = true;
+        Ok(obstacle_ref)
+    }
+
+    /// Like add_obstacle the navmesh is updated over the following update calls
+    pub fn remove_obstacle(&mut self, obstacle: ObstacleRef) -> Result<(), NavError> {
+        unsafe { TileCacheRemoveObstacle(self.tile_cache_ptr.tile_cache, obstacle) }.to_result()?;
+        self.pending = true;
+        Ok(())
+    }
+
+    /// Processes queued obstacle changes and rebuilds affected tiles, a limited number of tiles per call.
+    /// Returns Ok(true) once everything is up to date.  Fails if queries are rented out or this isn't the tile cache's navmesh.
+    pub fn update(&mut self, delta_time: f32, navmesh: &mut Navmesh) -> Result<bool, NavError> {
+        let _drain = self.check_mutate(navmesh)?;
+
+        let mut up_to_date = 0;
+        unsafe { TileCacheUpdate(self.tile_cache_ptr.tile_cache, delta_time, &mut up_to_date) }.to_result()?;
+
+        // Rebuilt tiles can come back empty when an obstacle covers the whole tile, and vice versa
+        if self.pending {
+            for &coord in self.layer_coords.iter() {
+                navmesh.sync_tile_coord(coord);
+            }
+            self.pending = up_to_date == 0;
+        }
+        Ok(up_to_date == 1)
+    }
+
+    fn check_mutate(&self, navmesh: &Navmesh) -> Result<QueryPoolDrain, NavError> {
+        if !Arc::ptr_eq(navmesh.shared_ptr(), &self.tile_cache_ptr.navmesh) {
+            return Err(NavError::WrongNavmesh);
+        }
+        navmesh.drain_queries()
+    }
+}
+
+impl<C: TileCacheCompressor> Drop for TileCache<C> {
+    fn drop(&mut self) {
+        unsafe { TileCacheDestroy(self.tile_cache_ptr.tile_cache) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        building::{navmesh_build_utils::NavmeshBuildUtils, tile_input_builder::TileInputBuilder, NavBuildSettings},
+        common::{nav_error::NavError, navmesh::{Navmesh, NavmeshSettings}, DtArea, DtInt2, DtVector, NavAgentSettings},
+        queries::{NavQueryFilter, NavQuerySettings},
+        tile_cache::{TileCacheObstacle, TileCacheSettings, UncompressedCompressor},
+    };
+
+    use super::TileCache;
+
+    fn plane_input(build_settings: NavBuildSettings, agent_settings: NavAgentSettings, coord: DtInt2) -> TileInputBuilder {
+        let mut bounds = NavmeshBuildUtils::calculate_tile_input_bounds(build_settings, agent_settings, coord);
+        bounds.min.y = 0.0;
+        bounds.max.y = 1.0 + agent_settings.height;
+
+        let mut input = TileInputBuilder::new(coord, bounds);
+        let (min, max) = (bounds.min, bounds.max);
+        input.append_triangle(&[DtVector::new(min.x, 1.0, min.z), DtVector::new(min.x, 1.0, max.z), DtVector::new(max.x, 1.0, max.z)], DtArea::WALKABLE);
+        input.append_triangle(&[DtVector::new(min.x, 1.0, min.z), DtVector::new(max.x, 1.0, max.z), DtVector::new(max.x, 1.0, min.z)], DtArea::WALKABLE);
+        input
+    }
+
+    fn is_blocked(navmesh: &Navmesh, start: DtVector, end: DtVector) -> bool {
+        let query = navmesh.query_pool.pop().unwrap();
+        let hit = query.raycast(NavQuerySettings::default(), start, end, &NavQueryFilter::default()).unwrap();
+        navmesh.query_pool.push(query);
+        hit.hit
+    }
+
+    fn update_until_done(tile_cache: &mut TileCache, navmesh: &mut Navmesh) {
+        for _ in 0..10 {
+            if tile_cache.update(0.1, navmesh).unwrap() {
+                return;
+            }
+        }
+        panic!("tile cache never finished updating");
+    }
+
+    #[test]
+    fn temporary_obstacles() {
+        let build_settings = NavBuildSettings::default();
+        let agent_settings = NavAgentSettings::default();
+        let mut navmesh = Navmesh::new(NavmeshSettings::default(build_settings, 2048.0, 10)).unwrap();
+        let mut tile_cache = TileCache::new(&navmesh, build_settings, agent_settings, TileCacheSettings::default(), UncompressedCompressor).unwrap();
+
+        let coord = DtInt2::new(0, 0);
+        let layers = tile_cache.build_tile_layers(plane_input(build_settings, agent_settings, coord)).unwrap();
+        assert_eq!(1, layers.len());
+        tile_cache.add_tile_layers(coord, &layers, &mut navmesh).unwrap();
+        assert!(navmesh.get_tile_base_ref(&coord).is_some());
+
+        let start = DtVector::new(2.0, 1.0, 10.0);
+        let end = DtVector::new(17.0, 1.0, 10.0);
+        assert!(!is_blocked(&navmesh, start, end));
+
+        let obstacle = TileCacheObstacle::Cylinder { position: DtVector::new(10.0, 0.0, 10.0), radius: 1.5, height: 3.0 };
+        let obstacle_ref = tile_cache.add_obstacle(obstacle).unwrap();
+
+        // Mutating needs every query back in the pool
+        let query = navmesh.query_pool.pop().unwrap();
+        assert_eq!(Err(NavError::QueriesInUse), tile_cache.update(0.1, &mut navmesh));
+        navmesh.query_pool.push(query);
+
+        update_until_done(&mut tile_cache, &mut navmesh);
+        assert!(is_blocked(&navmesh, start, end));
+
+        tile_cache.remove_obstacle(obstacle_ref).unwrap();
+        update_until_done(&mut tile_cache, &mut navmesh);
+        assert!(!is_blocked(&navmesh, start, end));
+
+        tile_cache.remove_tile(coord, &mut navmesh).unwrap();
+        assert!(navmesh.get_tile_base_ref(&coord).is_none());
+        assert_eq!(Err(NavError::TileNotFound(coord)), tile_cache.remove_tile(coord, &mut navmesh));
+
+        // A navmesh created after the cache's navmesh is dropped is still a different navmesh
+        drop(navmesh);
+        let mut other = Navmesh::new(NavmeshSettings::default(build_settings, 2048.0, 10)).unwrap();
+        assert_eq!(Err(NavError::WrongNavmesh), tile_cache.update(0.1, &mut other));
+    }
+}